@@ -10,6 +10,7 @@ pub fn print_project_scope(_project_description: &str) {
     ///     "is_crud_required": bool, // true if site needs CRUD functionality
     ///     "is_user_login_and_logout": bool // true if site needs users to be able to log in and log out
     ///     "is_external_urls_required": bool // true if site needs to fetch data from third part providers
+    ///     "is_database_required": bool // true if data must be kept in a SQL database (e.g. SQLite) rather than a file
     ///   }
     /// Example 1:
     ///   user_request = "I need a full stack website that accepts users and gets stock price data"
@@ -18,6 +19,7 @@ pub fn print_project_scope(_project_description: &str) {
     ///     "is_crud_required": true
     ///     "is_user_login_and_logout": true
     ///     "is_external_urls_required": bool true
+    ///     "is_database_required": bool false
     ///   }
     /// Example 2:
     ///   user_request = "I need a simple TODO app"
@@ -26,6 +28,7 @@ pub fn print_project_scope(_project_description: &str) {
     ///     "is_crud_required": true
     ///     "is_user_login_and_logout": false
     ///     "is_external_urls_required": bool false
+    ///     "is_database_required": bool false
     ///   }
     println!(OUTPUT)
 }
@@ -6,13 +6,29 @@ pub fn print_backend_webserver_code(_project_description_and_template: &str) {
     /// IMPORTANT: The backend code is ONLY an example. If the Project Description requires it, make as many changes as you like.
     /// IMPORTANT: You do not need to follow the backend code exactly. Write functions that make sense for the users request if required.
     /// FUNCTION: Takes an existing set of code marked as CODE_TEMPLATE and updates or re-writes it to work for the purpose in the PROJECT_DESCRIPTION
+    /// IMPORTANT: If EXTERNAL_API_SAMPLES are provided, use each sample's json_shape and body_sample to write serde structs that deserialize that API's response exactly
     /// IMPORTANT: The following libraries are already installed
-    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, rusqlite
     /// No other external libraries should be used. Write functions that fit with the description from the PROJECT_DESCRIPTION
     /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
     println!(OUTPUT)
 }
 
+#[ai_function]
+pub fn print_backend_project_manifest(_project_description_and_template: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+    /// FUNCTION: Rewrites the CODE_TEMPLATE for the purpose in the PROJECT_DESCRIPTION as a multi-file Rust project
+    /// IMPORTANT: Split the code into modules where it makes sense: src/main.rs (server setup and routes), src/models.rs, src/handlers.rs, src/persistence.rs, src/config.rs
+    /// IMPORTANT: src/main.rs must declare every other file with `mod name;`. Every path is relative to the project root and ends in .rs
+    /// IMPORTANT: If EXTERNAL_API_SAMPLES are provided, use each sample's json_shape and body_sample to write serde structs that deserialize that API's response exactly
+    /// IMPORTANT: The following libraries are already installed
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, rusqlite
+    /// List any other crate the code needs under "dependencies". Only crates from ALLOWED_CRATES can be used
+    /// OUTPUT: Prints ONLY a JSON file manifest, nothing else:
+    ///   {"files": [{"path": "src/main.rs", "contents": "..."}, {"path": "src/models.rs", "contents": "..."}], "dependencies": {"crate_name": "1.0.0"}}
+    println!(OUTPUT)
+}
+
 #[ai_function]
 pub fn print_improved_webserver_code(_project_description_and_template: &str) {
     /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
@@ -21,7 +37,73 @@ pub fn print_improved_webserver_code(_project_description_and_template: &str) {
     ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature. No code should be implemented later. Everything should be written now.
     ///   3. ONLY writes the code. No commentary.
     /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
-    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, rusqlite
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_improved_webserver_patches(_project_description_and_code: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION and the current CODE_TEMPLATE for a website backend build, and optionally REJECTED_PATCHES that could not be applied last time
+    /// The code may contain several files, each starting with a "// === FILE: path ===" line
+    /// FUNCTION: Performs the following tasks:
+    ///   1. Removes any bugs in the code and adds minor additional functionality
+    ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature. No code should be implemented later. Everything should be written now.
+    /// OUTPUT: Prints ONLY edit blocks in this exact format, one block per change, copying the SEARCH lines exactly from the code:
+    ///   <<<<<<< SEARCH
+    ///   lines from the current code
+    ///   =======
+    ///   the new lines
+    ///   >>>>>>> REPLACE
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, rusqlite
+    /// IMPORTANT: No commentary or anything else
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_code_critique(_requirements_and_code: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION with the requirements (including planned tasks and acceptance criteria), the API_SCHEMA if known and the current CODE_TEMPLATE for a website backend build
+    /// The code may contain several files, each starting with a "// === FILE: path ===" line
+    /// FUNCTION: Reviews the code as a strict senior reviewer. Checks every requirement, endpoint and acceptance criterion against the code and lists what is missing or wrong
+    /// IMPORTANT: Gaps are short and concrete, e.g. "POST /todos is missing" or "Todo has no completed field"
+    /// OUTPUT: Prints ONLY an object in the following format:
+    ///   {
+    ///     "score": u8, // 0 to 10, 10 when every requirement is met
+    ///     "gaps": [String, ...],
+    ///     "summary": String // one sentence about the overall state of the code
+    ///   }
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_critique_revision_patches(_code_and_critique: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, the current CODE_TEMPLATE for a website backend build and a CRITIQUE listing the GAPS found in review, and optionally REJECTED_PATCHES that could not be applied last time
+    /// The code may contain several files, each starting with a "// === FILE: path ===" line
+    /// FUNCTION: Revises the code so that every gap in the critique is closed. Does not change anything else
+    /// OUTPUT: Prints ONLY edit blocks in this exact format, one block per change, copying the SEARCH lines exactly from the code:
+    ///   <<<<<<< SEARCH
+    ///   lines from the current code
+    ///   =======
+    ///   the new lines
+    ///   >>>>>>> REPLACE
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, rusqlite
+    /// IMPORTANT: No commentary or anything else
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_code_patches(_broken_code_with_bugs: &str) {
+    /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found, and optionally REJECTED_PATCHES that could not be applied last time
+    /// The code may contain several files, each starting with a "// === FILE: path ===" line
+    /// FUNCTION: Removes bugs from code
+    /// OUTPUT: Prints ONLY edit blocks in this exact format, one block per change, copying the SEARCH lines exactly from the code:
+    ///   <<<<<<< SEARCH
+    ///   lines from the broken code
+    ///   =======
+    ///   the fixed lines
+    ///   >>>>>>> REPLACE
+    /// IMPORTANT: No commentary or anything else
     println!(OUTPUT)
 }
 
@@ -33,9 +115,20 @@ pub fn print_fixed_code(_broken_code_with_bugs: &str) {
     println!(OUTPUT)
 }
 
+#[ai_function]
+pub fn print_fixed_items(_broken_items_with_bugs: &str) {
+    /// INPUT: Takes in BROKEN_ITEMS, a few top-level Rust items (structs, impls, functions) taken from larger files, and the ERROR_BUGS the compiler reported for them
+    /// FUNCTION: Removes bugs by rewriting only the items in BROKEN_ITEMS
+    /// IMPORTANT: Print every item from BROKEN_ITEMS, corrected, keeping each item's name. Only add new items (such as use statements) if the fix needs them
+    /// IMPORTANT: Keep each "// === FILE: path ===" line before the items of that file
+    /// IMPORTANT: Do not print any other part of the file. Only prints out the code. No commentary or anything else
+    println!(OUTPUT)
+}
+
 #[ai_function]
 pub fn print_rest_api_endpoints(_code_input: &str) {
     /// INPUT: Takes in Rust webserver CODE_INPUT based on actix-web
+    /// The code may contain several files, each starting with a "// === FILE: path ===" line. Look for routes and structs across all files
     /// FUNCTION: Prints out the JSON schema for url endpoints and their respective types
     /// LOGIC: Script analyses all code and can categorize into the following object keys:
     ///   "route": This represents the url path of the endpoint
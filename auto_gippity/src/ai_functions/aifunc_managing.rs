@@ -1,5 +1,25 @@
 use ai_functions::ai_function;
 
+#[ai_function]
+pub fn print_clarifying_questions(_user_request: &str) {
+    /// Input: Takes in a user request for a website build
+    /// Function: Finds requirements that are missing or ambiguous in the request and asks one question for each
+    /// Important: Only ask about requirements that change what gets built, ordered by importance
+    /// Important: Each question has a topic: "authentication", "data_retention", "external_apis" or "other"
+    /// Important: Prints an empty list if the request is clear enough
+    /// Output: Prints a JSON list in the following format:
+    ///   [{"topic": String, "question": String}, ...]
+    /// Example:
+    ///   user_request = "I need a website that shows crypto prices and saves favourites"
+    ///   prints:
+    ///   [
+    ///     {"topic": "authentication", "question": "Do users need to log in to save favourites?"},
+    ///     {"topic": "external_apis", "question": "Which exchange or price API should the prices come from?"},
+    ///     {"topic": "data_retention", "question": "How long should saved favourites be kept?"}
+    ///   ]
+    println!(OUTPUT)
+}
+
 #[ai_function]
 pub fn convert_user_input_to_goal(_user_request: &str) {
     /// Input: Takes in a user request
@@ -13,3 +33,31 @@ pub fn convert_user_input_to_goal(_user_request: &str) {
     ///   OUTPUT = "build a website that fetches and stores crypto price data within a supabase setup including a frontend UI to fetch the data."
     println!(OUTPUT)
 }
+
+#[ai_function]
+pub fn print_project_tasks(_project_description: &str) {
+    /// Input: Takes in a project description of a website build
+    /// Function: Expands the project description into an ordered list of build tasks with acceptance criteria
+    /// Important: Each task has a kind: "endpoint", "data_entity", "integration" or "ui_page"
+    /// Important: The target of an endpoint is "METHOD /path" (dynamic segments as {id}), of a data_entity the struct name,
+    ///   of an integration the domain of the external API and of a ui_page the page path
+    /// Important: Acceptance criteria are short checkable statements
+    /// Output: Prints a JSON list in the following format:
+    ///   [
+    ///     {
+    ///       "kind": "endpoint" | "data_entity" | "integration" | "ui_page",
+    ///       "target": String,
+    ///       "description": String,
+    ///       "acceptance_criteria": [String, ...]
+    ///     }
+    ///   ]
+    /// Example:
+    ///   project_description = "build a website that stores todo items and lets users list and create them"
+    ///   prints:
+    ///   [
+    ///     {"kind": "data_entity", "target": "Todo", "description": "Todo item with id, title and completed flag", "acceptance_criteria": ["Todo has id, title and completed fields"]},
+    ///     {"kind": "endpoint", "target": "GET /todos", "description": "List all todos", "acceptance_criteria": ["Returns 200 with a JSON list of todos"]},
+    ///     {"kind": "endpoint", "target": "POST /todos", "description": "Create a todo", "acceptance_criteria": ["Accepts a JSON todo", "Returns the created todo"]}
+    ///   ]
+    println!(OUTPUT)
+}
@@ -1,11 +1,37 @@
-use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
+use crate::helpers::run_budget::record_tokens;
+use crate::models::general::llm::{APIMessage, APIResponse, ChatCompletion, Message, ToolSchema};
 use dotenv::dotenv;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{Client, ClientBuilder, RequestBuilder};
-use std::{env, process::Output};
+use reqwest::Client;
+use std::env;
+
+// 使用的模型
+pub const LLM_MODEL: &str = "gpt-3.5-turbo";
 
 // Call Large language model(ie gpt-4)
+// 代理改用 call_gpt_with_model 後，只有測試還在使用
+#[cfg_attr(not(test), allow(dead_code))]
 pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error + Send>> {
+    call_gpt_with_model(LLM_MODEL, messages).await
+}
+
+// 使用指定的模型調用 LLM
+pub async fn call_gpt_with_model(
+    model: &str,
+    messages: Vec<Message>,
+) -> Result<String, Box<dyn std::error::Error + Send>> {
+    let message: APIMessage = call_gpt_with_tools(model, messages, vec![]).await?;
+
+    // Send Response 返回回應的文本內容
+    Ok(message.content.unwrap_or_default())
+}
+
+// 提供工具調用 LLM：返回的消息可能包含文本，也可能包含工具調用
+pub async fn call_gpt_with_tools(
+    model: &str,
+    messages: Vec<Message>,
+    tools: Vec<ToolSchema>,
+) -> Result<APIMessage, Box<dyn std::error::Error + Send>> {
     dotenv().ok();
 
     // Extract API key information
@@ -42,9 +68,10 @@ pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::err
 
     // Create chat completion  創建聊天請求/创建聊天完成对象
     let chat_completion = ChatCompletion {
-        model: "gpt-3.5-turbo".to_string(),
+        model: model.to_string(),
         messages,
         temperature: 0.1,  // 低温度值有助于生成更一致、预测性的回答
+        tools,
     };
 
     // Extract API Response
@@ -58,19 +85,26 @@ pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::err
         .await
         .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
 
-    // Send Response 返回回應的文本內容
-    Ok(res.choices[0].message.content.clone())
+    // Record usage 記錄本次調用消耗的 token，計入整次運行的預算
+    if let Some(usage) = &res.usage {
+        record_tokens(usage.total_tokens);
+    }
+
+    // Send Response 返回回應的消息
+    Ok(res.choices[0].message.clone())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     #[tokio::test]
+    #[allow(clippy::assertions_on_constants)]
     async fn tests_call_to_openai() {
         // 創建測試訊息
         let message: Message = Message {
             role: "user".to_string(), // 訊息的角色是 user
             content: "Hi there, this is a test. Give me a short response.".to_string(), // 訊息內容
+            ..Message::default()
         };
 
         // 將訊息放入向量中
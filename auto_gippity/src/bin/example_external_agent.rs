@@ -0,0 +1,88 @@
+// 示例外部代理：通過 stdio JSON-RPC 與 auto_gippity 通信（協議見 src/models/agents/external_agent.rs）。
+// 它讀取 FactSheet 中的 API 端點，通過主程序寫入 API.md，並在項目描述後追加一句說明。
+// 只依賴 serde_json，可以作為用其他語言編寫外部代理的參考。
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+// 與主程序的連接：發送請求並等待對應的響應
+struct Host {
+    lines: io::Lines<io::StdinLock<'static>>,
+    next_id: u64,
+}
+
+impl Host {
+    fn send(&self, message: Value) {
+        let mut stdout: io::StdoutLock = io::stdout().lock();
+        writeln!(stdout, "{}", message).expect("Failed to write to host");
+        stdout.flush().expect("Failed to write to host");
+    }
+
+    fn read(&mut self) -> Value {
+        let line: String = self
+            .lines
+            .next()
+            .expect("Host closed the connection")
+            .expect("Failed to read from host");
+        serde_json::from_str(&line).expect("Invalid message from host")
+    }
+
+    fn log(&self, message: &str) {
+        self.send(json!({"jsonrpc": "2.0", "method": "log", "params": {"message": message}}));
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.next_id += 1;
+        let id: u64 = self.next_id;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        let response: Value = self.read();
+        match response.get("error") {
+            Some(error) => Err(error["message"].as_str().unwrap_or("request failed").to_string()),
+            None => Ok(response["result"].clone()),
+        }
+    }
+}
+
+// 把端點列表寫成 Markdown
+fn render_api_docs(endpoints: &[Value]) -> String {
+    let mut docs: String = String::from("# API\n\n| Method | Route |\n| --- | --- |\n");
+    for endpoint in endpoints {
+        docs.push_str(&format!(
+            "| {} | `{}` |\n",
+            endpoint["method"].as_str().unwrap_or("?").to_uppercase(),
+            endpoint["route"].as_str().unwrap_or("?")
+        ));
+    }
+    docs
+}
+
+fn run(host: &mut Host, params: &Value) -> Result<Value, String> {
+    let factsheet: &Value = &params["factsheet"];
+    let endpoints: Vec<Value> = factsheet["api_endpoint_schema"].as_array().cloned().unwrap_or_default();
+    host.log(&format!("documenting {} endpoint(s)", endpoints.len()));
+
+    host.call("file.write", json!({"path": "API.md", "contents": render_api_docs(&endpoints)}))?;
+
+    let description: String = factsheet["project_description"].as_str().unwrap_or_default().to_string();
+    Ok(json!({"patch": {"project_description": format!("{} (API documented in API.md)", description)}}))
+}
+
+fn main() {
+    let mut host: Host = Host {
+        lines: io::stdin().lock().lines(),
+        next_id: 0,
+    };
+
+    let request: Value = host.read();
+    if request["method"] != "run" {
+        eprintln!("expected a run request, got {}", request);
+        std::process::exit(1);
+    }
+    let params: Value = request["params"].clone();
+
+    let response: Value = match run(&mut host, &params) {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": request["id"], "result": result}),
+        Err(message) => json!({"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32000, "message": message}}),
+    };
+    host.send(response);
+}
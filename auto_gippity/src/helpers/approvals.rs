@@ -0,0 +1,279 @@
+// 人工審批子系統：代理在執行有風險的操作之前必須通過命名的審批關卡。
+// 策略文件 config/approval_policy.toml 為每個關卡設定自動批准、自動拒絕或詢問用戶。
+// 每次審批都會記錄到 FactSheet 上；被拒絕時返回 ApprovalDenied 錯誤，讓運行乾淨地結束。
+
+use crate::helpers::command_line::{confirm_action, PrintCommand};
+use crate::helpers::config::{config_path, load_toml_config};
+use crate::helpers::event_bus::{publish, AgentEvent};
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_traits::FactSheet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+// 審批策略文件名
+pub const APPROVAL_POLICY_FILE: &str = "approval_policy.toml";
+
+// 命名的審批關卡，名稱與策略文件中的鍵一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+pub enum ApprovalGate {
+    BeforeBuild,
+    BeforeRun,
+    BeforeExternalUrls,
+    BeforeOverwriteFiles,
+}
+
+impl ApprovalGate {
+    // 詢問用戶時顯示的問題
+    fn question(&self) -> &'static str {
+        match self {
+            Self::BeforeBuild => "Are you sure the code is safe to build?",
+            Self::BeforeRun => "Are you sure the code is safe to execute?",
+            Self::BeforeExternalUrls => "Allow contacting these external URLs?",
+            Self::BeforeOverwriteFiles => "Allow overwriting these files?",
+        }
+    }
+}
+
+// 關卡的處理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GateDecision {
+    AutoApprove,
+    AutoDeny,
+    Ask,
+}
+
+// 審批策略：未在 gates 中列出的關卡使用 default
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ApprovalPolicy {
+    pub default: GateDecision,
+    pub gates: HashMap<ApprovalGate, GateDecision>,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self {
+            default: GateDecision::Ask,
+            gates: HashMap::new(),
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    // 所有關卡使用同一種處理方式
+    #[cfg(test)]
+    pub fn uniform(decision: GateDecision) -> Self {
+        Self {
+            default: decision,
+            gates: HashMap::new(),
+        }
+    }
+
+    // 查詢某個關卡的處理方式
+    pub fn decision_for(&self, gate: ApprovalGate) -> GateDecision {
+        *self.gates.get(&gate).unwrap_or(&self.default)
+    }
+}
+
+// 一條審批記錄
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalRecord {
+    pub gate: ApprovalGate,
+    pub detail: String,
+    pub approved: bool,
+    pub decided_by: String, // "policy" 或 "user"
+    pub reason: String,
+}
+
+// 審批被拒絕時返回的錯誤
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApprovalDenied {
+    pub gate: ApprovalGate,
+    pub reason: String,
+}
+
+impl fmt::Display for ApprovalDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "approval denied at {:?}: {}", self.gate, self.reason)
+    }
+}
+
+impl std::error::Error for ApprovalDenied {}
+
+// 審批者：按策略處理審批請求；詢問時調用 ask 函數
+#[derive(Debug, Clone)]
+pub struct Approvals {
+    pub policy: ApprovalPolicy,
+    ask: fn(&str) -> bool,
+}
+
+impl Approvals {
+    pub fn new(policy: ApprovalPolicy) -> Self {
+        Self {
+            policy,
+            ask: confirm_action,
+        }
+    }
+
+    // 策略文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(APPROVAL_POLICY_FILE)
+    }
+
+    // 從預設路徑讀取策略，文件不存在時每個關卡都詢問用戶
+    pub fn load_default() -> Self {
+        Self::new(load_toml_config(&Self::default_path()).expect("Failed to parse approval policy"))
+    }
+
+    // 替換詢問用戶的方式（例如測試或無人值守模式）
+    #[cfg(test)]
+    pub fn with_ask(mut self, ask: fn(&str) -> bool) -> Self {
+        self.ask = ask;
+        self
+    }
+
+    // 請求通過某個關卡；結果記錄到 FactSheet，被拒絕時返回 ApprovalDenied
+    pub fn request(
+        &self,
+        gate: ApprovalGate,
+        detail: &str,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), ApprovalDenied> {
+        let (approved, decided_by, reason): (bool, &str, String) = match self.policy.decision_for(gate) {
+            GateDecision::AutoApprove => (true, "policy", "auto-approved by policy".to_string()),
+            GateDecision::AutoDeny => (false, "policy", "auto-denied by policy".to_string()),
+            GateDecision::Ask => {
+                PrintCommand::UnitTest.print_agent_message(
+                    "Approvals",
+                    format!("{:?}: {}", gate, detail).as_str(),
+                );
+                if (self.ask)(gate.question()) {
+                    (true, "user", "approved by user".to_string())
+                } else {
+                    (false, "user", "denied by user".to_string())
+                }
+            }
+        };
+
+        publish(AgentEvent::ApprovalRequested {
+            gate: format!("{:?}", gate),
+            detail: detail.to_string(),
+            approved,
+            decided_by: decided_by.to_string(),
+        });
+        factsheet.approvals.get_or_insert_with(Vec::new).push(ApprovalRecord {
+            gate,
+            detail: detail.to_string(),
+            approved,
+            decided_by: decided_by.to_string(),
+            reason: reason.clone(),
+        });
+
+        if approved {
+            Ok(())
+        } else {
+            PrintCommand::Issue.print_agent_message(
+                "Approvals",
+                format!("{:?} denied: {}", gate, reason).as_str(),
+            );
+            Err(ApprovalDenied { gate, reason })
+        }
+    }
+
+    // 代理請求通過某個關卡；需要詢問用戶時代理在等待期間處於 Blocked 狀態，
+    // 批准後回到原來的狀態，被拒絕時保持 Blocked，由代理轉為 Failed
+    pub fn request_for(
+        &self,
+        agent: &mut BasicAgent,
+        gate: ApprovalGate,
+        detail: &str,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), ApprovalDenied> {
+        if self.policy.decision_for(gate) != GateDecision::Ask {
+            return self.request(gate, detail, factsheet);
+        }
+
+        let previous: AgentState = agent.state.clone();
+        let blocked: bool = agent
+            .update_state(AgentState::Blocked(format!("awaiting approval: {:?}", gate)))
+            .is_ok();
+        self.request(gate, detail, factsheet)?;
+        if blocked {
+            agent.update_state(previous).expect("Blocked agent can resume its previous state");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+
+    #[test]
+    fn tests_policy_file_parsing() {
+        let policy: ApprovalPolicy = parse_toml_config(
+            r#"
+            default = "auto_deny"
+
+            [gates]
+            before_build = "ask"
+            before_external_urls = "auto_approve"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(policy.decision_for(ApprovalGate::BeforeBuild), GateDecision::Ask);
+        assert_eq!(policy.decision_for(ApprovalGate::BeforeExternalUrls), GateDecision::AutoApprove);
+        assert_eq!(policy.decision_for(ApprovalGate::BeforeRun), GateDecision::AutoDeny);
+    }
+
+    #[test]
+    fn tests_shipped_policy_file_parses() {
+        let approvals: Approvals = Approvals::load_default();
+        assert_eq!(approvals.policy.decision_for(ApprovalGate::BeforeRun), GateDecision::Ask);
+    }
+
+    #[test]
+    fn tests_requests_are_recorded() {
+        let mut factsheet: FactSheet = FactSheet::default();
+        let mut policy: ApprovalPolicy = ApprovalPolicy::uniform(GateDecision::Ask);
+        policy.gates.insert(ApprovalGate::BeforeRun, GateDecision::AutoDeny);
+        let approvals: Approvals = Approvals::new(policy).with_ask(|_| true);
+
+        assert!(approvals.request(ApprovalGate::BeforeBuild, "cargo build", &mut factsheet).is_ok());
+        let denied: ApprovalDenied = approvals
+            .request(ApprovalGate::BeforeRun, "cargo run", &mut factsheet)
+            .unwrap_err();
+        assert_eq!(denied.gate, ApprovalGate::BeforeRun);
+
+        let records: Vec<ApprovalRecord> = factsheet.approvals.unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].approved);
+        assert_eq!(records[0].decided_by, "user");
+        assert!(!records[1].approved);
+        assert_eq!(records[1].reason, "auto-denied by policy");
+    }
+
+    #[test]
+    fn tests_agent_is_blocked_while_asking() {
+        let mut factsheet: FactSheet = FactSheet::default();
+        let mut agent: BasicAgent = BasicAgent::new("build".to_string(), "Backend Developer".to_string());
+        agent.update_state(AgentState::UnitTesting).unwrap();
+
+        let approving: Approvals = Approvals::new(ApprovalPolicy::uniform(GateDecision::Ask)).with_ask(|_| true);
+        approving.request_for(&mut agent, ApprovalGate::BeforeBuild, "cargo build", &mut factsheet).unwrap();
+        assert_eq!(agent.state, AgentState::UnitTesting);
+        assert!(matches!(agent.transitions[1].to, AgentState::Blocked(_)));
+
+        let denying: Approvals = Approvals::new(ApprovalPolicy::uniform(GateDecision::Ask)).with_ask(|_| false);
+        assert!(denying.request_for(&mut agent, ApprovalGate::BeforeRun, "cargo run", &mut factsheet).is_err());
+        assert_eq!(agent.state, AgentState::Blocked("awaiting approval: BeforeRun".to_string()));
+    }
+}
@@ -0,0 +1,174 @@
+// 生成項目的離線構建：setup 命令把模板和依賴允許列表中所有可用的 crate vendor 到本地目錄，
+// 並在生成項目的 .cargo/config.toml 中把 crates.io 替換為該目錄。
+// 所有生成項目共用一個 target 目錄，重複運行時只需要增量構建。
+
+use crate::helpers::config::{config_path, load_toml_config};
+use crate::helpers::dependency_policy::DependencyPolicy;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use toml_edit::{value, DocumentMut, Item, Table};
+
+// 構建緩存配置文件名
+pub const BUILD_CACHE_FILE: &str = "build_cache.toml";
+
+// 未配置時使用的緩存目錄
+const DEFAULT_CACHE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/.build_cache");
+
+// .cargo/config.toml 中 vendor 源的名稱
+const VENDORED_SOURCE: &str = "vendored-sources";
+
+// 構建緩存配置
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct BuildCache {
+    pub vendor_dir: Option<PathBuf>,
+    pub target_dir: Option<PathBuf>,
+}
+
+impl BuildCache {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(BUILD_CACHE_FILE)
+    }
+
+    // 從預設路徑讀取配置，文件不存在時使用 .build_cache 下的目錄
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse build cache config")
+    }
+
+    // vendor 目錄
+    pub fn vendor_dir(&self) -> PathBuf {
+        self.vendor_dir
+            .clone()
+            .unwrap_or_else(|| Path::new(DEFAULT_CACHE_DIR).join("vendor"))
+    }
+
+    // 所有生成項目共用的 target 目錄
+    pub fn target_dir(&self) -> PathBuf {
+        self.target_dir
+            .clone()
+            .unwrap_or_else(|| Path::new(DEFAULT_CACHE_DIR).join("target"))
+    }
+
+    // vendor 所有可用 crate：生成一個依賴允許列表中全部 crate 的臨時項目，
+    // 與模板項目的 Cargo.toml 一起交給 cargo vendor，然後配置生成項目使用 vendor 目錄
+    pub fn setup(&self, policy: &DependencyPolicy, project_dir: &Path) -> Result<PathBuf, String> {
+        let scratch_dir: PathBuf = Path::new(DEFAULT_CACHE_DIR).join("vendor_manifest");
+        fs::create_dir_all(scratch_dir.join("src")).map_err(|e| e.to_string())?;
+        fs::write(scratch_dir.join("Cargo.toml"), vendor_manifest(policy)).map_err(|e| e.to_string())?;
+        fs::write(scratch_dir.join("src/lib.rs"), "").map_err(|e| e.to_string())?;
+
+        let vendor_dir: PathBuf = self.vendor_dir();
+        let output: Output = Command::new("cargo")
+            .arg("vendor")
+            .arg("--versioned-dirs")
+            .arg("--manifest-path")
+            .arg(scratch_dir.join("Cargo.toml"))
+            .arg("--sync")
+            .arg(project_dir.join("Cargo.toml"))
+            .arg(&vendor_dir)
+            .output()
+            .map_err(|e| format!("failed to run cargo vendor: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("cargo vendor failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        self.write_cargo_config(project_dir)?;
+        Ok(vendor_dir)
+    }
+
+    // 在項目的 .cargo/config.toml 中使用 vendor 目錄和共用的 target 目錄，保留其他設置
+    pub fn write_cargo_config(&self, project_dir: &Path) -> Result<(), String> {
+        let config_file: PathBuf = project_dir.join(".cargo").join("config.toml");
+        let contents: String = fs::read_to_string(&config_file).unwrap_or_default();
+        let mut document: DocumentMut = contents
+            .parse()
+            .map_err(|e| format!("{}: {}", config_file.display(), e))?;
+
+        let source: &mut Table = table_entry(document.as_table_mut(), "source")?;
+        source.set_implicit(true);
+        table_entry(source, "crates-io")?.insert("replace-with", value(VENDORED_SOURCE));
+        table_entry(source, VENDORED_SOURCE)?
+            .insert("directory", value(self.vendor_dir().to_string_lossy().as_ref()));
+        table_entry(document.as_table_mut(), "build")?
+            .insert("target-dir", value(self.target_dir().to_string_lossy().as_ref()));
+
+        fs::create_dir_all(config_file.parent().unwrap()).map_err(|e| e.to_string())?;
+        fs::write(&config_file, document.to_string()).map_err(|e| format!("{}: {}", config_file.display(), e))
+    }
+}
+
+// 取得（必要時創建）子表
+fn table_entry<'a>(table: &'a mut Table, key: &str) -> Result<&'a mut Table, String> {
+    table
+        .entry(key)
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| format!("[{}] is not a table", key))
+}
+
+// 包含依賴允許列表中所有 crate 的 Cargo.toml，用於 cargo vendor
+pub fn vendor_manifest(policy: &DependencyPolicy) -> String {
+    let mut manifest: String = String::from(
+        "[package]\nname = \"auto_gippity_vendor\"\nversion = \"0.0.0\"\nedition = \"2021\"\npublish = false\n\n[workspace]\n\n[dependencies]\n",
+    );
+    for (name, allowed) in &policy.crates {
+        let features: Vec<String> = allowed.features.iter().map(|feature| format!("{:?}", feature)).collect();
+        manifest.push_str(&format!(
+            "{} = {{ version = {:?}, features = [{}] }}\n",
+            name,
+            allowed.version,
+            features.join(", ")
+        ));
+    }
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+
+    #[test]
+    fn tests_vendor_manifest_lists_allowed_crates() {
+        let policy: DependencyPolicy = parse_toml_config(
+            r#"
+            [crates]
+            uuid = { version = "=1.8.0", features = ["v4", "serde"] }
+            log = { version = "=0.4.21" }
+            "#,
+        )
+        .unwrap();
+
+        let manifest: toml::Table = parse_toml_config(&vendor_manifest(&policy)).unwrap();
+        assert_eq!(manifest["dependencies"]["uuid"]["version"].as_str(), Some("=1.8.0"));
+        assert_eq!(manifest["dependencies"]["uuid"]["features"].as_array().unwrap().len(), 2);
+        assert!(manifest["dependencies"]["log"].is_table());
+    }
+
+    #[test]
+    fn tests_cargo_config_uses_vendor_and_shared_target() {
+        let project_dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_cache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_dir);
+        fs::create_dir_all(project_dir.join(".cargo")).unwrap();
+        fs::write(project_dir.join(".cargo/config.toml"), "# 本地設置\n[net]\nretry = 3\n").unwrap();
+
+        let cache: BuildCache = BuildCache {
+            vendor_dir: Some(PathBuf::from("/opt/vendor")),
+            target_dir: Some(PathBuf::from("/opt/target")),
+        };
+        cache.write_cargo_config(&project_dir).unwrap();
+        cache.write_cargo_config(&project_dir).unwrap();
+
+        let contents: String = fs::read_to_string(project_dir.join(".cargo/config.toml")).unwrap();
+        assert!(contents.starts_with("# 本地設置\n[net]\nretry = 3\n"));
+        let config: toml::Table = parse_toml_config(&contents).unwrap();
+        assert_eq!(config["source"]["crates-io"]["replace-with"].as_str(), Some(VENDORED_SOURCE));
+        assert_eq!(config["source"][VENDORED_SOURCE]["directory"].as_str(), Some("/opt/vendor"));
+        assert_eq!(config["build"]["target-dir"].as_str(), Some("/opt/target"));
+
+        fs::remove_dir_all(&project_dir).unwrap();
+    }
+}
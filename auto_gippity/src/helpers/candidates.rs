@@ -0,0 +1,240 @@
+// 並行候選生成：同時生成 K 個候選實現，每個候選在自己的工作區副本中構建、運行並測試端點，
+// 第一個全部通過的候選勝出，其餘候選被取消。候選數量受 token 預算限制。
+
+use crate::helpers::config::{config_path, load_toml_config};
+use serde::Deserialize;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// 候選生成配置文件名
+pub const CANDIDATES_FILE: &str = "candidates.toml";
+
+// 未配置時候選工作區所在的目錄
+const DEFAULT_WORKSPACE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/.build_cache/candidates");
+
+// 複製到候選工作區的模板項目文件（src 由候選自己寫入）
+const WORKSPACE_FILES: [&str; 3] = ["Cargo.toml", "Cargo.lock", ".cargo/config.toml"];
+
+// 候選生成配置
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CandidateConfig {
+    pub enabled: bool,
+    pub candidates: usize,
+    pub concurrency: usize,
+    pub tokens_per_candidate: u64,
+    pub base_port: u16,
+    pub workspace_dir: Option<PathBuf>,
+}
+
+impl Default for CandidateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            candidates: 3,
+            concurrency: 2,
+            tokens_per_candidate: 8000,
+            base_port: 18080,
+            workspace_dir: None,
+        }
+    }
+}
+
+impl CandidateConfig {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(CANDIDATES_FILE)
+    }
+
+    // 從預設路徑讀取配置，文件不存在時不啟用候選生成
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse candidates config")
+    }
+
+    // 剩餘預算能負擔的候選數量，不超過配置的 K
+    pub fn candidate_count(&self, remaining_tokens: Option<u64>) -> usize {
+        let affordable: usize = match remaining_tokens {
+            Some(remaining) => (remaining / self.tokens_per_candidate.max(1)) as usize,
+            None => usize::MAX,
+        };
+        self.candidates.min(affordable)
+    }
+
+    // 第 index 個候選的工作區
+    pub fn workspace(&self, index: usize) -> PathBuf {
+        let root: PathBuf = self
+            .workspace_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_WORKSPACE_DIR));
+        root.join(format!("candidate-{}", index + 1))
+    }
+
+    // 第 index 個候選的伺服器端口
+    pub fn port(&self, index: usize) -> u16 {
+        self.base_port + index as u16
+    }
+}
+
+// 從模板項目建立乾淨的候選工作區
+pub fn prepare_workspace(template_dir: &Path, workspace: &Path) -> Result<(), String> {
+    if workspace.exists() {
+        fs::remove_dir_all(workspace).map_err(|e| format!("{}: {}", workspace.display(), e))?;
+    }
+    fs::create_dir_all(workspace.join("src")).map_err(|e| format!("{}: {}", workspace.display(), e))?;
+
+    for file in WORKSPACE_FILES {
+        let source: PathBuf = template_dir.join(file);
+        if !source.is_file() {
+            continue;
+        }
+        let target: PathBuf = workspace.join(file);
+        fs::create_dir_all(target.parent().unwrap()).map_err(|e| format!("{}: {}", target.display(), e))?;
+        fs::copy(&source, &target).map_err(|e| format!("{}: {}", source.display(), e))?;
+    }
+    Ok(())
+}
+
+// 並行運行 count 個候選，同時最多運行 concurrency 個；
+// 返回第一個成功的候選編號和結果並取消其餘候選（等到它們都被丟棄、子進程被終止後才返回），
+// 全部失敗時返回每個候選的失敗原因
+pub async fn race_candidates<T, F, Fut>(count: usize, concurrency: usize, run: F) -> Result<(usize, T), Vec<String>>
+where
+    T: Send + 'static,
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = Result<T, String>> + Send + 'static,
+{
+    let slots: Arc<Semaphore> = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks: JoinSet<(usize, Result<T, String>)> = JoinSet::new();
+    for index in 0..count {
+        let slots: Arc<Semaphore> = slots.clone();
+        let candidate: Fut = run(index);
+        tasks.spawn(async move {
+            let _slot = slots.acquire_owned().await.expect("Candidate semaphore closed");
+            (index, candidate.await)
+        });
+    }
+
+    let mut failures: Vec<String> = vec![];
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, Ok(result))) => {
+                tasks.shutdown().await;
+                return Ok((index, result));
+            }
+            Ok((index, Err(e))) => failures.push(format!("candidate {}: {}", index + 1, e)),
+            Err(e) => failures.push(format!("candidate task failed: {}", e)),
+        }
+    }
+    Err(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn tests_candidate_count_bounded_by_budget() {
+        let config: CandidateConfig = parse_toml_config("enabled = true\ncandidates = 4\ntokens_per_candidate = 1000\n").unwrap();
+        assert_eq!(config.candidate_count(None), 4);
+        assert_eq!(config.candidate_count(Some(2500)), 2);
+        assert_eq!(config.candidate_count(Some(500)), 0);
+        assert_eq!(config.port(2), 18082);
+        assert!(config.workspace(0).ends_with("candidate-1"));
+    }
+
+    // 被丟棄時計數的守衛：候選被取消時它隨候選的 future 一起被丟棄
+    struct DropGuard(Arc<AtomicUsize>);
+
+    impl Drop for DropGuard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_first_passing_candidate_wins_and_losers_are_cancelled() {
+        let started: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let dropped: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let finished: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let result: Result<(usize, String), Vec<String>> = race_candidates(3, 3, |index| {
+            let started: Arc<AtomicUsize> = started.clone();
+            let dropped: Arc<AtomicUsize> = dropped.clone();
+            let finished: Arc<AtomicUsize> = finished.clone();
+            async move {
+                match index {
+                    0 => Err("build failed".to_string()),
+                    1 => {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        Ok("second".to_string())
+                    }
+                    _ => {
+                        let _guard: DropGuard = DropGuard(dropped);
+                        started.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_secs(30)).await;
+                        finished.fetch_add(1, Ordering::SeqCst);
+                        Ok("slow".to_string())
+                    }
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok((1, "second".to_string())));
+        // 慢的候選已經開始，並在 race_candidates 返回前被取消（守衛被丟棄），沒有運行完
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn tests_all_candidates_failing_reports_each_failure() {
+        let running: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let result: Result<(usize, ()), Vec<String>> = race_candidates(3, 1, |index| {
+            let running: Arc<AtomicUsize> = running.clone();
+            async move {
+                // 同時只允許一個候選運行
+                assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Err(format!("tests failed {}", index))
+            }
+        })
+        .await;
+
+        let mut failures: Vec<String> = result.unwrap_err();
+        failures.sort();
+        assert_eq!(failures.len(), 3);
+        assert_eq!(failures[0], "candidate 1: tests failed 0");
+    }
+
+    #[test]
+    fn tests_prepare_workspace_copies_manifest_only() {
+        let root: PathBuf = std::env::temp_dir().join(format!("auto_gippity_candidates_{}", std::process::id()));
+        let template_dir: PathBuf = root.join("template");
+        let workspace: PathBuf = root.join("candidate-1");
+        fs::create_dir_all(template_dir.join("src")).unwrap();
+        fs::create_dir_all(template_dir.join(".cargo")).unwrap();
+        fs::write(template_dir.join("Cargo.toml"), "[package]\nname = \"web_template\"\n").unwrap();
+        fs::write(template_dir.join(".cargo/config.toml"), "[build]\n").unwrap();
+        fs::write(template_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::write(workspace.join("src/stale.rs"), "").unwrap();
+
+        prepare_workspace(&template_dir, &workspace).unwrap();
+
+        assert!(workspace.join("Cargo.toml").is_file());
+        assert!(workspace.join(".cargo/config.toml").is_file());
+        assert!(!workspace.join("Cargo.lock").exists());
+        assert!(!workspace.join("src/main.rs").exists());
+        assert!(!workspace.join("src/stale.rs").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
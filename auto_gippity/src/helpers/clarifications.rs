@@ -0,0 +1,165 @@
+// 規劃前的澄清問題：模型找出請求中缺失或含糊的需求（認證、數據保存、外部 API 等），
+// 最多提出配置數量的問題，由用戶或無人值守模式的答案文件回答；回答連同來源寫入 FactSheet 並併入項目描述。
+
+use crate::helpers::command_line::get_user_response;
+use crate::helpers::config::{config_path, load_toml_config};
+use crate::helpers::headless::{headless_clarification, AnsweredPrompt};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// 澄清問題配置文件名
+pub const CLARIFICATIONS_FILE: &str = "clarifications.toml";
+
+// 澄清問題配置
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ClarificationConfig {
+    pub enabled: bool,
+    pub max_questions: usize,
+}
+
+impl Default for ClarificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_questions: 3,
+        }
+    }
+}
+
+impl ClarificationConfig {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(CLARIFICATIONS_FILE)
+    }
+
+    // 從預設路徑讀取配置，文件不存在時使用預設值
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse clarifications config")
+    }
+
+    // 是否需要向模型要澄清問題
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.max_questions > 0
+    }
+}
+
+// 模型提出的澄清問題
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ClarifyingQuestion {
+    pub topic: String,
+    pub question: String,
+}
+
+// 一個已回答的澄清問題；source 是 "user"、"answers_file" 或沒有答案時的 "default"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Clarification {
+    pub topic: String,
+    pub question: String,
+    pub answer: String,
+    pub source: String,
+}
+
+impl Clarification {
+    // 答案為空時需求留給代理自行決定
+    pub fn is_answered(&self) -> bool {
+        !self.answer.is_empty()
+    }
+}
+
+// 回答一個澄清問題：無人值守模式下使用答案文件，否則詢問用戶
+pub fn ask_user(question: &ClarifyingQuestion) -> AnsweredPrompt {
+    if let Some(answered) = headless_clarification(&question.topic, &question.question) {
+        return answered;
+    }
+    AnsweredPrompt {
+        question: question.question.clone(),
+        answer: get_user_response(question.question.as_str()),
+        source: "user".to_string(),
+    }
+}
+
+// 依次回答最多 max_questions 個問題
+pub fn ask_clarifying_questions(
+    questions: &[ClarifyingQuestion],
+    max_questions: usize,
+    ask: fn(&ClarifyingQuestion) -> AnsweredPrompt,
+) -> Vec<Clarification> {
+    questions
+        .iter()
+        .take(max_questions)
+        .map(|question| {
+            let answered: AnsweredPrompt = ask(question);
+            Clarification {
+                topic: question.topic.clone(),
+                question: question.question.clone(),
+                answer: answered.answer,
+                source: answered.source,
+            }
+        })
+        .collect()
+}
+
+// 把已回答的澄清併入項目描述，每條註明答案來源
+pub fn fold_into_description(description: &str, clarifications: &[Clarification]) -> String {
+    let answered: Vec<String> = clarifications
+        .iter()
+        .filter(|clarification| clarification.is_answered())
+        .map(|clarification| {
+            format!(
+                "- {}: {} {} (answered by {})",
+                clarification.topic, clarification.question, clarification.answer, clarification.source
+            )
+        })
+        .collect();
+    if answered.is_empty() {
+        return description.to_string();
+    }
+    format!("{}\nClarifications:\n{}", description, answered.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scripted_answer(question: &ClarifyingQuestion) -> AnsweredPrompt {
+        let answer: &str = match question.topic.as_str() {
+            "authentication" => "email and password",
+            _ => "",
+        };
+        AnsweredPrompt {
+            question: question.question.clone(),
+            answer: answer.to_string(),
+            source: "user".to_string(),
+        }
+    }
+
+    #[test]
+    fn tests_answers_are_capped_and_folded_with_provenance() {
+        let questions: Vec<ClarifyingQuestion> = ["authentication", "data_retention", "external_apis"]
+            .iter()
+            .map(|topic| ClarifyingQuestion {
+                topic: topic.to_string(),
+                question: format!("What about {}?", topic),
+            })
+            .collect();
+
+        let clarifications: Vec<Clarification> = ask_clarifying_questions(&questions, 2, scripted_answer);
+        assert_eq!(clarifications.len(), 2);
+        assert!(!clarifications[1].is_answered());
+
+        assert_eq!(
+            fold_into_description("build a website that stores notes", &clarifications),
+            "build a website that stores notes\nClarifications:\n\
+             - authentication: What about authentication? email and password (answered by user)"
+        );
+        assert_eq!(fold_into_description("build a blog", &clarifications[1..]), "build a blog");
+    }
+
+    #[test]
+    fn tests_default_config_file_parses() {
+        let config: ClarificationConfig = ClarificationConfig::load_default();
+        assert!(config.is_active());
+        assert_eq!(config.max_questions, 3);
+    }
+}
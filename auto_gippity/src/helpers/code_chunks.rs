@@ -0,0 +1,397 @@
+// 按語法樹切分生成的代碼：用 syn 把 main.rs 切成頂層條目（結構體、impl、處理函數、main），
+// 把編譯錯誤的行號映射到所在條目，只讓模型重寫這些條目，再按字節範圍把改好的條目拼回原文件（保留註釋和格式）。
+// 拼接後不運行 rustfmt：改好的條目保持模型給出的格式，其餘代碼的行號不變，下一輪的編譯錯誤仍能對應到同樣的條目。
+
+use proc_macro2::{LineColumn, Span};
+use quote::ToTokens;
+use std::ops::Range;
+use syn::spanned::Spanned;
+use syn::{File, ImplItem, Item};
+
+// 代碼中的一個頂層條目，range 是條目（含屬性）在代碼中的字節範圍
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeItem {
+    pub key: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub range: Range<usize>,
+    pub source: String,
+}
+
+// 代碼中的一個函數（頂層函數或 impl 中的方法）：item 是整個函數、body 是函數體（含大括號）在代碼中的字節範圍
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionBody {
+    pub name: String,
+    pub item: Range<usize>,
+    pub body: Range<usize>,
+}
+
+// 把 span 的行列位置（行從 1 開始，列按字符計）換算成代碼中的字節位置
+pub fn byte_offset(code: &str, position: LineColumn) -> usize {
+    let line_start: usize = code
+        .split_inclusive('\n')
+        .take(position.line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let column: usize = code[line_start..]
+        .char_indices()
+        .nth(position.column)
+        .map(|(idx, _)| idx)
+        .unwrap_or(code.len() - line_start);
+    line_start + column
+}
+
+fn span_range(code: &str, span: Span) -> Range<usize> {
+    byte_offset(code, span.start())..byte_offset(code, span.end())
+}
+
+// 找出代碼中所有頂層函數和 impl 方法的函數體，按在代碼中的順序
+pub fn function_bodies(code: &str) -> Result<Vec<FunctionBody>, syn::Error> {
+    let file: File = syn::parse_file(code)?;
+    let mut bodies: Vec<FunctionBody> = vec![];
+    for item in &file.items {
+        match item {
+            Item::Fn(item_fn) => bodies.push(FunctionBody {
+                name: item_fn.sig.ident.to_string(),
+                item: span_range(code, item_fn.span()),
+                body: span_range(code, item_fn.block.span()),
+            }),
+            Item::Impl(item_impl) => {
+                for impl_item in &item_impl.items {
+                    if let ImplItem::Fn(method) = impl_item {
+                        bodies.push(FunctionBody {
+                            name: method.sig.ident.to_string(),
+                            item: span_range(code, method.span()),
+                            body: span_range(code, method.block.span()),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(bodies)
+}
+
+// 條目的名稱，用於把模型改好的條目對應回原文件；impl 包括泛型、特質和 where 子句
+fn item_key(item: &Item) -> Option<String> {
+    let key: String = match item {
+        Item::Fn(item) => format!("fn {}", item.sig.ident),
+        Item::Struct(item) => format!("struct {}", item.ident),
+        Item::Enum(item) => format!("enum {}", item.ident),
+        Item::Const(item) => format!("const {}", item.ident),
+        Item::Static(item) => format!("static {}", item.ident),
+        Item::Type(item) => format!("type {}", item.ident),
+        Item::Trait(item) => format!("trait {}", item.ident),
+        Item::Mod(item) => format!("mod {}", item.ident),
+        Item::Use(item) => format!("use {}", item.tree.to_token_stream()),
+        Item::Impl(item) => {
+            let generics: String = item.generics.to_token_stream().to_string();
+            let trait_for: String = match &item.trait_ {
+                Some((bang, path, _)) => format!(
+                    "{}{} for ",
+                    if bang.is_some() { "!" } else { "" },
+                    path.to_token_stream()
+                ),
+                None => String::new(),
+            };
+            let where_clause: String = match &item.generics.where_clause {
+                Some(clause) => format!(" {}", clause.to_token_stream()),
+                None => String::new(),
+            };
+            format!(
+                "impl{} {}{}{}",
+                generics,
+                trait_for,
+                item.self_ty.to_token_stream(),
+                where_clause
+            )
+        }
+        _ => return None,
+    };
+    Some(key)
+}
+
+// 把代碼切分成頂層條目，行號從 1 開始
+pub fn split_code_items(code: &str) -> Result<Vec<CodeItem>, syn::Error> {
+    let file: File = syn::parse_file(code)?;
+    let lines: Vec<&str> = code.lines().collect();
+
+    let items: Vec<CodeItem> = file
+        .items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let span: Span = item.span();
+            let start_line: usize = span.start().line;
+            let end_line: usize = span.end().line;
+            CodeItem {
+                key: item_key(item).unwrap_or_else(|| format!("item {}", idx)),
+                start_line,
+                end_line,
+                range: span_range(code, span),
+                source: lines[start_line - 1..end_line].join("\n"),
+            }
+        })
+        .collect();
+    Ok(items)
+}
+
+// 統一路徑寫法：反斜線換成斜線，去掉開頭的 ./
+fn normalize_path(path: &str) -> String {
+    let path: String = path.trim().replace('\\', "/");
+    let mut rest: &str = path.as_str();
+    while let Some(stripped) = rest.strip_prefix("./") {
+        rest = stripped;
+    }
+    rest.to_string()
+}
+
+// 從 cargo 的錯誤輸出中提取指向某個文件的行號，例如 "--> src/main.rs:12:5"；
+// 路徑必須與 file_path（相對於項目根目錄）相同，絕對路徑則要以完整的 file_path 組件結尾
+pub fn error_lines(build_errors: &str, file_path: &str) -> Vec<usize> {
+    let file_path: String = normalize_path(file_path);
+    let mut lines: Vec<usize> = vec![];
+
+    for line in build_errors.lines() {
+        let Some(location) = line.trim_start().strip_prefix("--> ") else {
+            continue;
+        };
+        // 從右邊拆出列號和行號，路徑中可能有冒號（Windows 盤符）
+        let mut parts = location.trim().rsplitn(3, ':');
+        let (Some(_column), Some(line_no), Some(path)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+        let path: String = normalize_path(path);
+        let is_absolute: bool = path.starts_with('/') || path.get(1..2) == Some(":");
+        let same_file: bool = path == file_path || (is_absolute && path.ends_with(&format!("/{}", file_path)));
+        if !same_file {
+            continue;
+        }
+        if let Ok(line_no) = line_no.parse::<usize>() {
+            if !lines.contains(&line_no) {
+                lines.push(line_no);
+            }
+        }
+    }
+    lines
+}
+
+// 找出包含這些行的條目，保持文件中的順序
+pub fn items_for_lines(items: &[CodeItem], lines: &[usize]) -> Vec<CodeItem> {
+    items
+        .iter()
+        .filter(|item| lines.iter().any(|&line| item.start_line <= line && line <= item.end_line))
+        .cloned()
+        .collect()
+}
+
+// 去掉模型回應外層的 markdown 代碼塊標記
+pub fn strip_code_fences(text: &str) -> String {
+    let trimmed: &str = text.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let body: &str = rest.split_once('\n').map(|(_, body)| body).unwrap_or("");
+            body.trim_end().trim_end_matches("```").to_string()
+        }
+        None => trimmed.to_string(),
+    }
+}
+
+// 把改好的條目拼回原代碼，只替換條目的字節範圍，其餘文字（包括註釋）原樣保留。
+// 改好的條目先對應發給模型的 targets（同名時按順序），再對應原代碼中唯一同名的條目；
+// 同名條目不止一個又不在 targets 中時無法確定替換哪個，返回錯誤。
+// 新增的 use 放在第一個條目之前，其他新條目放在末尾。
+pub fn splice_items(code: &str, targets: &[CodeItem], edited_items: &str) -> Result<String, String> {
+    let items: Vec<CodeItem> = split_code_items(code).map_err(|e| e.to_string())?;
+    let edited_code: String = strip_code_fences(edited_items);
+    let edited: File = syn::parse_file(&edited_code).map_err(|e| e.to_string())?;
+
+    let mut replaced: Vec<usize> = vec![];
+    let mut replacements: Vec<(Range<usize>, String)> = vec![];
+    let mut new_uses: Vec<String> = vec![];
+    let mut new_items: Vec<String> = vec![];
+    for edited_item in &edited.items {
+        let text: String = edited_code[span_range(&edited_code, edited_item.span())].to_string();
+        let Some(key) = item_key(edited_item) else {
+            new_items.push(text);
+            continue;
+        };
+
+        let unreplaced = |item: &&CodeItem| item.key == key && !replaced.contains(&item.range.start);
+        let same_key: Vec<&CodeItem> = items.iter().filter(|item| item.key == key).collect();
+        let existing: Option<&CodeItem> = match targets.iter().find(unreplaced) {
+            Some(target) => items.iter().find(|item| item.range == target.range),
+            None if same_key.len() > 1 => {
+                return Err(format!("{} appears {} times, cannot tell which one was edited", key, same_key.len()))
+            }
+            None => same_key.into_iter().find(unreplaced),
+        };
+
+        match existing {
+            Some(item) => {
+                replaced.push(item.range.start);
+                replacements.push((item.range.clone(), text));
+            }
+            None if matches!(edited_item, Item::Use(_)) => new_uses.push(text),
+            None => new_items.push(text),
+        }
+    }
+
+    // 從後往前替換，前面的字節位置不會變
+    let mut spliced: String = code.to_string();
+    replacements.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, text) in replacements {
+        spliced.replace_range(range, &text);
+    }
+    if !new_uses.is_empty() {
+        let insert_at: usize = items.first().map(|item| item.range.start).unwrap_or(0);
+        spliced.insert_str(insert_at, &format!("{}\n", new_uses.join("\n")));
+    }
+    for text in new_items {
+        spliced = format!("{}\n\n{}\n", spliced.trim_end(), text);
+    }
+
+    syn::parse_file(&spliced).map_err(|e| format!("spliced code does not parse: {}", e))?;
+    Ok(spliced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "use actix_web::{web, App, HttpServer};
+
+#[derive(Debug)]
+struct Task {
+    id: u64,
+}
+
+async fn get_task() -> String {
+    let task = Task { id: 1 };
+    format!(\"{}\", tsk.id)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    HttpServer::new(|| App::new().route(\"/task\", web::get().to(get_task)))
+        .bind((\"127.0.0.1\", 8080))?
+        .run()
+        .await
+}
+";
+
+    const ERRORS: &str = "error[E0425]: cannot find value `tsk` in this scope
+  --> src/main.rs:10:20
+   |
+10 |     format!(\"{}\", tsk.id)
+   |                    ^^^ help: a local variable with a similar name exists: `task`
+";
+
+    #[test]
+    fn tests_errors_map_to_items() {
+        let items: Vec<CodeItem> = split_code_items(CODE).unwrap();
+        let keys: Vec<&str> = items.iter().map(|item| item.key.as_str()).collect();
+        assert_eq!(keys[1..], ["struct Task", "fn get_task", "fn main"]);
+        assert_eq!((items[1].start_line, items[1].end_line), (3, 6));
+
+        let affected: Vec<CodeItem> = items_for_lines(&items, &error_lines(ERRORS, "src/main.rs"));
+        assert_eq!(affected.len(), 1);
+        assert_eq!(affected[0].key, "fn get_task");
+        assert!(affected[0].source.contains("tsk.id"));
+        assert_eq!(&CODE[affected[0].range.clone()], affected[0].source);
+    }
+
+    #[test]
+    fn tests_error_lines_compare_whole_paths() {
+        let errors: &str = "  --> src/bin/main.rs:3:1\n  --> .\\src\\main.rs:10:20\n  --> /work/project/src/main.rs:14:5\n  --> other/src/main.rs:2:1\n";
+        assert_eq!(error_lines(errors, "src/main.rs"), vec![10, 14]);
+        assert_eq!(error_lines(errors, "src/bin/main.rs"), vec![3]);
+    }
+
+    #[test]
+    fn tests_splice_replaces_only_edited_items() {
+        let edited: &str = "```rust
+use std::fmt::Write;
+
+async fn get_task() -> String {
+    let task = Task { id: 1 };
+    format!(\"{}\", task.id)
+}
+```";
+
+        let targets: Vec<CodeItem> = items_for_lines(&split_code_items(CODE).unwrap(), &[10]);
+        let spliced: String = splice_items(CODE, &targets, edited).unwrap();
+        assert!(spliced.starts_with("use std::fmt::Write;\nuse actix_web::{web, App, HttpServer};"));
+        assert!(spliced.contains("task.id"));
+        assert!(!spliced.contains("tsk.id"));
+        assert!(spliced.contains("HttpServer::new"));
+        assert_eq!(split_code_items(&spliced).unwrap().len(), 5);
+        // 沒有改動的條目原樣保留
+        assert!(spliced.ends_with(&CODE[CODE.find("#[actix_web::main]").unwrap()..]));
+    }
+
+    #[test]
+    fn tests_splice_keeps_comments_and_picks_the_targeted_impl() {
+        let code: &str = "// 任務模型
+struct Task {
+    id: u64,
+}
+
+// 讀取
+impl Task {
+    fn id(&self) -> u64 {
+        self.id // 編號
+    }
+}
+
+// 寫入
+impl Task {
+    fn set_id(&mut self, id: u64) {
+        self.id = idd;
+    }
+}
+
+impl std::fmt::Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, \"{}\", self.id)
+    }
+}
+";
+        let items: Vec<CodeItem> = split_code_items(code).unwrap();
+        let keys: Vec<&str> = items.iter().map(|item| item.key.as_str()).collect();
+        assert_eq!(keys, ["struct Task", "impl Task", "impl Task", "impl std :: fmt :: Display for Task"]);
+
+        let targets: Vec<CodeItem> = items_for_lines(&items, &[16]);
+        let edited: &str = "impl Task {
+    fn set_id(&mut self, id: u64) {
+        // 更新編號
+        self.id = id;
+    }
+}";
+        let spliced: String = splice_items(code, &targets, edited).unwrap();
+        assert!(spliced.contains("self.id // 編號"));
+        assert!(spliced.contains("// 寫入\nimpl Task {\n    fn set_id(&mut self, id: u64) {\n        // 更新編號"));
+        assert!(!spliced.contains("idd"));
+        assert_eq!(split_code_items(&spliced).unwrap().len(), 4);
+
+        // 沒有 targets 時無法確定改的是哪個 impl Task
+        assert!(splice_items(code, &[], edited).is_err());
+    }
+
+    #[test]
+    fn tests_function_bodies_are_byte_ranges() {
+        let code: &str = "// 任務\nstruct Task;\n\nimpl Task {\n    fn id(&self) -> u64 { 1 }\n}\n\nfn main() {\n    let _ = Task.id();\n}\n";
+        let bodies: Vec<FunctionBody> = function_bodies(code).unwrap();
+        let names: Vec<&str> = bodies.iter().map(|body| body.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "main"]);
+        assert_eq!(&code[bodies[0].body.clone()], "{ 1 }");
+        assert_eq!(&code[bodies[0].item.clone()], "fn id(&self) -> u64 { 1 }");
+        assert_eq!(&code[bodies[1].body.clone()], "{\n    let _ = Task.id();\n}");
+    }
+
+    #[test]
+    fn tests_invalid_edit_is_an_error() {
+        assert!(splice_items(CODE, &[], "fn get_task( {").is_err());
+    }
+}
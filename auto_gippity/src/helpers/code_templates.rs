@@ -0,0 +1,195 @@
+// 代碼模板庫：每個後端起始模板都帶有描述其能力的元數據，
+// 架構師得出的 ProjectScope 會與這些元數據匹配，以挑選最合適的模板。
+// 模板源碼位於 web_template/templates/ 下，並作為 web_template 的 example 編譯，
+// 因此 `cargo test --examples` 可以在離線狀態下構建它們並運行各自的冒煙測試。
+
+use crate::models::agents::agent_traits::ProjectScope;
+
+// 模板使用的持久化方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Persistence {
+    None,
+    InMemory,
+    JsonFile,
+    Sqlite,
+}
+
+// 模板的能力描述，與 ProjectScope 的欄位一一對應
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateCapabilities {
+    pub crud: bool,
+    pub user_login: bool,
+    pub external_urls: bool,
+    pub persistence: Persistence,
+}
+
+// 一個後端起始模板
+#[derive(Debug)]
+pub struct CodeTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub capabilities: TemplateCapabilities,
+    pub source: &'static str,
+}
+
+// 預設模板，在尚無項目範圍時使用（與舊的單一模板行為一致）
+pub const DEFAULT_TEMPLATE_NAME: &str = "actix_json_crud";
+
+// 模板註冊表
+pub static CODE_TEMPLATES: &[CodeTemplate] = &[
+    CodeTemplate {
+        name: "actix_json_crud",
+        description: "actix-web CRUD for tasks plus register/login, persisted to a JSON file",
+        capabilities: TemplateCapabilities {
+            crud: true,
+            user_login: true,
+            external_urls: false,
+            persistence: Persistence::JsonFile,
+        },
+        source: include_str!("../../../web_template/templates/actix_json_crud.rs"),
+    },
+    CodeTemplate {
+        name: "actix_sqlite_crud",
+        description: "actix-web CRUD for tasks plus register/login, persisted to SQLite via rusqlite",
+        capabilities: TemplateCapabilities {
+            crud: true,
+            user_login: true,
+            external_urls: false,
+            persistence: Persistence::Sqlite,
+        },
+        source: include_str!("../../../web_template/templates/actix_sqlite_crud.rs"),
+    },
+    CodeTemplate {
+        name: "actix_api_proxy",
+        description: "minimal read-only actix-web proxy that forwards GET requests to external APIs",
+        capabilities: TemplateCapabilities {
+            crud: false,
+            user_login: false,
+            external_urls: true,
+            persistence: Persistence::None,
+        },
+        source: include_str!("../../../web_template/templates/actix_api_proxy.rs"),
+    },
+    CodeTemplate {
+        name: "actix_auth_service",
+        description: "actix-web auth-only service with register, login, logout and bearer sessions",
+        capabilities: TemplateCapabilities {
+            crud: false,
+            user_login: true,
+            external_urls: false,
+            persistence: Persistence::InMemory,
+        },
+        source: include_str!("../../../web_template/templates/actix_auth_service.rs"),
+    },
+];
+
+impl CodeTemplate {
+    // 計算模板與項目範圍的匹配分數：
+    // 缺少必需的能力重罰，多出不需要的能力輕罰；需要數據庫時只有 SQLite 模板滿足
+    pub fn match_score(&self, scope: &ProjectScope) -> i32 {
+        let pairs: [(bool, bool); 4] = [
+            (scope.is_crud_required, self.capabilities.crud),
+            (scope.is_user_login_and_logout, self.capabilities.user_login),
+            (scope.is_external_urls_required, self.capabilities.external_urls),
+            (scope.is_database_required, self.capabilities.persistence == Persistence::Sqlite),
+        ];
+
+        pairs
+            .iter()
+            .map(|(required, provided)| match (required, provided) {
+                (true, true) => 2,
+                (true, false) => -3,
+                (false, true) => -1,
+                (false, false) => 0,
+            })
+            .sum()
+    }
+}
+
+// 按名稱查找模板
+pub fn find_code_template(name: &str) -> Option<&'static CodeTemplate> {
+    CODE_TEMPLATES.iter().find(|template| template.name == name)
+}
+
+// 根據項目範圍挑選最佳模板；分數相同時以註冊表中靠前的為準
+pub fn select_code_template(scope: Option<&ProjectScope>) -> &'static CodeTemplate {
+    let default_template: &'static CodeTemplate =
+        find_code_template(DEFAULT_TEMPLATE_NAME).expect("Default code template missing");
+
+    let scope: &ProjectScope = match scope {
+        Some(scope) => scope,
+        None => return default_template,
+    };
+
+    let mut best: &'static CodeTemplate = default_template;
+    let mut best_score: i32 = best.match_score(scope);
+    for template in CODE_TEMPLATES {
+        let score: i32 = template.match_score(scope);
+        if score > best_score {
+            best = template;
+            best_score = score;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(crud: bool, login: bool, external: bool) -> ProjectScope {
+        ProjectScope {
+            is_crud_required: crud,
+            is_user_login_and_logout: login,
+            is_external_urls_required: external,
+            is_database_required: false,
+        }
+    }
+
+    #[test]
+    fn tests_default_template_without_scope() {
+        assert_eq!(select_code_template(None).name, DEFAULT_TEMPLATE_NAME);
+    }
+
+    #[test]
+    fn tests_template_selection_matches_scope() {
+        assert_eq!(select_code_template(Some(&scope(true, true, false))).name, "actix_json_crud");
+        assert_eq!(select_code_template(Some(&scope(false, false, true))).name, "actix_api_proxy");
+        assert_eq!(select_code_template(Some(&scope(false, true, false))).name, "actix_auth_service");
+
+        let database: ProjectScope = ProjectScope {
+            is_database_required: true,
+            ..scope(true, true, false)
+        };
+        assert_eq!(select_code_template(Some(&database)).name, "actix_sqlite_crud");
+    }
+
+    #[test]
+    fn tests_every_template_is_selectable() {
+        for template in CODE_TEMPLATES {
+            let capabilities: TemplateCapabilities = template.capabilities;
+            let matching: ProjectScope = ProjectScope {
+                is_crud_required: capabilities.crud,
+                is_user_login_and_logout: capabilities.user_login,
+                is_external_urls_required: capabilities.external_urls,
+                is_database_required: capabilities.persistence == Persistence::Sqlite,
+            };
+            assert_eq!(select_code_template(Some(&matching)).name, template.name);
+        }
+    }
+
+    #[test]
+    fn tests_templates_are_well_formed() {
+        for template in CODE_TEMPLATES {
+            assert_eq!(
+                CODE_TEMPLATES.iter().filter(|t| t.name == template.name).count(),
+                1,
+                "duplicate template name {}",
+                template.name
+            );
+            assert!(template.source.contains("#[actix_web::main]"), "{} has no main", template.name);
+            assert!(template.source.contains("mod smoke_tests"), "{} has no smoke tests", template.name);
+            assert!(template.source.contains("\"PORT\""), "{} ignores PORT", template.name);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 // 引入所需的库和模块
 use crossterm::style::{Color, ResetColor, SetForegroundColor};
 use crossterm::ExecutableCommand;
+use crate::helpers::headless::{headless_answer, headless_required_answer, NO_ANSWER_DEFAULT};
 use std::io::{stdin, stdout, Write};
 
 // 定义PrintCommand枚举类型，用于确定打印时使用的具体颜色
@@ -42,8 +43,12 @@ impl PrintCommand {
     }
 }
 
-// 获取用户响应的函数
+// 获取用户响应的函数；无人值守模式下从答案文件回答，没有答案时使用 NO_ANSWER_DEFAULT
 pub fn get_user_response(question: &str) -> String {
+    if let Some(answer) = headless_answer(question, NO_ANSWER_DEFAULT) {
+        return answer;
+    }
+
     let mut stdout = stdout();
     // 用蓝色打印问题
     stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
@@ -63,39 +68,45 @@ pub fn get_user_response(question: &str) -> String {
     user_response.trim().to_string()
 }
 
-// src/helpers/command_line.rs 文件中的函数
-// 确认代码是否安全执行
-pub fn confirm_safe_code() -> bool {
-    use std::io::{self, Write};
-
-    // 提示用户确认
-    print!("Are you sure the code is safe to execute? (y/n): ");
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-
-    // 根据用户输入的响应返回布尔值
-    match input.trim().to_lowercase().as_str() {
-        "y" | "yes" => true,
-        "n" | "no" => false,
-        _ => {
-            println!("Invalid input. Please enter 'y' or 'n'.");
-            confirm_safe_code() // 递归调用，直到收到有效输入
+// 获取必须回答的用户响应；无人值守模式下答案文件中没有答案时返回错误
+pub fn get_required_user_response(question: &str) -> Result<String, String> {
+    match headless_required_answer(question) {
+        Some(answer) => answer,
+        None => Ok(get_user_response(question)),
+    }
+}
+
+// 向用户确认一个操作，直到收到有效的 y/n 输入；无人值守模式下默认拒绝
+pub fn confirm_action(question: &str) -> bool {
+    if let Some(answer) = headless_answer(question, "n") {
+        return matches!(answer.to_lowercase().as_str(), "y" | "yes" | "true");
+    }
+
+    loop {
+        // 提示用户确认
+        print!("{} (y/n): ", question);
+        stdout().flush().unwrap();
+
+        let mut input = String::new();
+        stdin().read_line(&mut input).unwrap();
+
+        // 根据用户输入的响应返回布尔值
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => println!("Invalid input. Please enter 'y' or 'n'."),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
     use std::io::Write;
-    use std::str;
 
     // 运行测试并捕获输出的辅助函数
     fn run_test_and_capture_output<F>(test_func: F) -> String
     where
-        F: FnOnce(&mut Vec<u8>) -> (),
+        F: FnOnce(&mut Vec<u8>),
     {
         let mut buffer = vec![];
         test_func(&mut buffer);
@@ -0,0 +1,29 @@
+// 配置文件輔助函數：所有配置都是 auto_gippity/config/ 下的 TOML 文件，
+// 文件不存在時使用預設值，格式錯誤時返回錯誤。
+
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 配置目錄
+pub const CONFIG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config");
+
+// 返回配置目錄下某個文件的路徑
+pub fn config_path(file_name: &str) -> PathBuf {
+    Path::new(CONFIG_DIR).join(file_name)
+}
+
+// 解析 TOML 配置字符串
+pub fn parse_toml_config<T: DeserializeOwned>(contents: &str) -> Result<T, Box<dyn std::error::Error>> {
+    let config: T = toml::from_str(contents)?;
+    Ok(config)
+}
+
+// 讀取 TOML 配置文件；文件不存在時返回預設值
+pub fn load_toml_config<T: DeserializeOwned + Default>(path: &Path) -> Result<T, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let contents: String = fs::read_to_string(path)?;
+    parse_toml_config(&contents)
+}
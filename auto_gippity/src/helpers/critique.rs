@@ -0,0 +1,129 @@
+// 自我評審：後端代碼改進之後，模型對照 FactSheet 的需求和 API 端點評審自己的代碼，給出分數和缺口；
+// 分數低於門檻時按缺口修改代碼，最多進行配置的輪數。每輪評審都記錄在 FactSheet 上，和同名的補丁記錄對應。
+
+use crate::helpers::config::{config_path, load_toml_config};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// 自我評審配置文件名
+pub const CRITIQUE_FILE: &str = "critique.toml";
+
+// 自我評審配置；threshold 是接受代碼的最低分數（0-10）
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CritiqueConfig {
+    pub enabled: bool,
+    pub max_rounds: usize,
+    pub threshold: u8,
+}
+
+impl Default for CritiqueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_rounds: 2,
+            threshold: 8,
+        }
+    }
+}
+
+impl CritiqueConfig {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(CRITIQUE_FILE)
+    }
+
+    // 從預設路徑讀取配置，文件不存在時不啟用自我評審
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse critique config")
+    }
+
+    // 是否進行自我評審
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.max_rounds > 0
+    }
+}
+
+// 模型給出的評審
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Critique {
+    pub score: u8,
+    #[serde(default)]
+    pub gaps: Vec<String>,
+    #[serde(default)]
+    pub summary: String,
+}
+
+impl Critique {
+    // 分數達到門檻時接受代碼
+    pub fn is_accepted(&self, threshold: u8) -> bool {
+        self.score >= threshold
+    }
+
+    // 發給模型修改代碼的評審文本
+    pub fn to_prompt(&self) -> String {
+        let gaps: Vec<String> = self.gaps.iter().map(|gap| format!("- {}", gap)).collect();
+        format!("SCORE: {}/10\n{}\nGAPS:\n{}", self.score, self.summary, gaps.join("\n"))
+    }
+
+    // 轉換成 FactSheet 上的記錄；step 和這一輪修改的補丁記錄同名，revised 在修改保存之後更新
+    pub fn record(&self, step: &str, threshold: u8, revised: bool) -> CritiqueRecord {
+        CritiqueRecord {
+            step: step.to_string(),
+            score: self.score,
+            threshold,
+            gaps: self.gaps.clone(),
+            summary: self.summary.clone(),
+            accepted: self.is_accepted(threshold),
+            revised,
+        }
+    }
+}
+
+// FactSheet 上的一輪評審記錄
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CritiqueRecord {
+    pub step: String,
+    pub score: u8,
+    pub threshold: u8,
+    pub gaps: Vec<String>,
+    pub summary: String,
+    pub accepted: bool,
+    pub revised: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+
+    #[test]
+    fn tests_critique_is_judged_against_the_threshold() {
+        let critique: Critique = serde_json::from_str(
+            r#"{"score": 6, "gaps": ["POST /todos is missing", "Todo has no completed field"], "summary": "Listing works"}"#,
+        )
+        .unwrap();
+        assert!(!critique.is_accepted(8));
+        assert!(critique.is_accepted(6));
+        assert_eq!(
+            critique.to_prompt(),
+            "SCORE: 6/10\nListing works\nGAPS:\n- POST /todos is missing\n- Todo has no completed field"
+        );
+
+        let record: CritiqueRecord = critique.record("critique round 1", 8, true);
+        assert!(!record.accepted && record.revised);
+        assert_eq!(record.step, "critique round 1");
+
+        let no_gaps: Critique = serde_json::from_str(r#"{"score": 3}"#).unwrap();
+        assert!(!no_gaps.is_accepted(8));
+        assert!(serde_json::from_str::<Critique>(r#"{"gaps": []}"#).is_err());
+    }
+
+    #[test]
+    fn tests_critique_config_defaults_and_file() {
+        let config: CritiqueConfig = parse_toml_config("enabled = true").unwrap();
+        assert_eq!((config.max_rounds, config.threshold), (2, 8));
+        assert!(config.is_active());
+        assert!(!CritiqueConfig::load_default().is_active());
+    }
+}
@@ -0,0 +1,262 @@
+// 生成項目的依賴管理：後端代理聲明需要的 crate，按 config/dependency_allowlist.toml 檢查並固定版本，
+// 再寫入生成項目的 Cargo.toml。配置了 offline 且 vendor 目錄已經準備好時，構建使用 --offline 從 vendor 目錄讀取依賴。
+
+use crate::helpers::config::{config_path, load_toml_config};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Array, DocumentMut, InlineTable, Item, Table};
+
+// 依賴允許列表文件名
+pub const DEPENDENCY_ALLOWLIST_FILE: &str = "dependency_allowlist.toml";
+
+// 允許使用的 crate 及其固定版本和特性
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AllowedCrate {
+    pub version: String,
+    pub features: Vec<String>,
+}
+
+// 依賴策略
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DependencyPolicy {
+    pub offline: bool,
+    pub crates: BTreeMap<String, AllowedCrate>,
+}
+
+// 檢查聲明的依賴之後的結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DependencyResolution {
+    pub accepted: BTreeMap<String, AllowedCrate>,
+    pub rejected: Vec<String>,
+}
+
+// crate 名稱中的 - 和 _ 等價
+fn normalize_crate_name(name: &str) -> String {
+    name.trim().replace('_', "-").to_lowercase()
+}
+
+impl DependencyPolicy {
+    // 策略文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(DEPENDENCY_ALLOWLIST_FILE)
+    }
+
+    // 從預設路徑讀取策略，文件不存在時不允許添加任何依賴
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse dependency allowlist")
+    }
+
+    // 查找允許列表中的 crate，返回允許列表中的寫法
+    pub fn find(&self, name: &str) -> Option<(&String, &AllowedCrate)> {
+        let normalized: String = normalize_crate_name(name);
+        self.crates
+            .iter()
+            .find(|(allowed, _)| normalize_crate_name(allowed) == normalized)
+    }
+
+    // 檢查聲明的依賴：允許的 crate 使用固定版本，其餘拒絕並說明原因
+    pub fn resolve(&self, requested: &BTreeMap<String, String>) -> DependencyResolution {
+        let mut resolution: DependencyResolution = DependencyResolution::default();
+        for (name, version) in requested {
+            match self.find(name) {
+                Some((allowed_name, allowed)) => {
+                    resolution.accepted.insert(allowed_name.clone(), allowed.clone());
+                }
+                None => resolution.rejected.push(format!(
+                    "crate {} ({}) is not in the dependency allowlist",
+                    name, version
+                )),
+            }
+        }
+        resolution
+    }
+
+    // 發給模型的允許列表：「名稱 版本」
+    pub fn describe(&self) -> String {
+        self.crates
+            .iter()
+            .map(|(name, allowed)| format!("{} {}", name, allowed.version.trim_start_matches('=')))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    // 是否離線構建：配置了 offline 但 vendor 目錄不存在（還沒運行 vendor-setup）時回退到聯網構建
+    pub fn builds_offline(&self, vendor_dir: &Path) -> bool {
+        self.offline && vendor_dir.is_dir()
+    }
+
+    // cargo build 的參數
+    pub fn build_args(&self, vendor_dir: &Path) -> Vec<&'static str> {
+        self.cargo_args("build", vendor_dir)
+    }
+
+    // cargo run 的參數：cargo run 也會解析依賴，和構建一樣離線
+    pub fn run_args(&self, vendor_dir: &Path) -> Vec<&'static str> {
+        self.cargo_args("run", vendor_dir)
+    }
+
+    fn cargo_args(&self, command: &'static str, vendor_dir: &Path) -> Vec<&'static str> {
+        if self.builds_offline(vendor_dir) {
+            vec![command, "--offline"]
+        } else {
+            vec![command]
+        }
+    }
+}
+
+// 把依賴寫入 Cargo.toml 的 [dependencies]，保留原有格式；已經存在的依賴不會被修改。返回新添加的 crate
+pub fn patch_cargo_toml(cargo_toml: &Path, crates: &BTreeMap<String, AllowedCrate>) -> Result<Vec<String>, String> {
+    let contents: String = fs::read_to_string(cargo_toml).map_err(|e| format!("{}: {}", cargo_toml.display(), e))?;
+    let mut document: DocumentMut = contents
+        .parse()
+        .map_err(|e| format!("{}: {}", cargo_toml.display(), e))?;
+
+    let dependencies: &mut Table = document
+        .entry("dependencies")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| format!("{}: [dependencies] is not a table", cargo_toml.display()))?;
+
+    let mut added: Vec<String> = vec![];
+    for (name, allowed) in crates {
+        let exists: bool = dependencies
+            .iter()
+            .any(|(existing, _)| normalize_crate_name(existing) == normalize_crate_name(name));
+        if exists {
+            continue;
+        }
+
+        if allowed.features.is_empty() {
+            dependencies.insert(name, value(allowed.version.as_str()));
+        } else {
+            let mut spec: InlineTable = InlineTable::new();
+            spec.insert("version", allowed.version.as_str().into());
+            spec.insert("features", allowed.features.iter().collect::<Array>().into());
+            dependencies.insert(name, value(spec));
+        }
+        added.push(name.clone());
+    }
+
+    if !added.is_empty() {
+        fs::write(cargo_toml, document.to_string()).map_err(|e| format!("{}: {}", cargo_toml.display(), e))?;
+    }
+    Ok(added)
+}
+
+// 從編譯錯誤中找出缺少的 crate：「unresolved import `uuid`」、「use of undeclared crate or module `chrono`」；
+// 與項目自己的模塊同名的路徑（例如子模塊中漏寫 crate:: 的 `models::Task`）不是缺少的 crate
+pub fn missing_crates(build_output: &str, local_modules: &[String]) -> Vec<String> {
+    let patterns: [&str; 3] = [
+        "unresolved import `",
+        "use of undeclared crate or module `",
+        "can't find crate for `",
+    ];
+
+    let mut crates: Vec<String> = vec![];
+    for line in build_output.lines() {
+        for pattern in patterns {
+            let Some(rest) = line.split(pattern).nth(1) else {
+                continue;
+            };
+            let path: &str = rest.split('`').next().unwrap_or("");
+            let name: &str = path.split("::").next().unwrap_or("");
+            let is_local: bool = matches!(name, "crate" | "self" | "super" | "std" | "core" | "alloc")
+                || local_modules.iter().any(|module| module == name);
+            if !name.is_empty() && !is_local && !crates.iter().any(|known| known == name) {
+                crates.push(name.to_string());
+            }
+        }
+    }
+    crates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+
+    fn policy() -> DependencyPolicy {
+        parse_toml_config(
+            r#"
+            offline = true
+
+            [crates]
+            uuid = { version = "=1.8.0", features = ["v4"] }
+            async-trait = { version = "=0.1.68" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tests_resolve_pins_allowed_crates() {
+        let requested: BTreeMap<String, String> = BTreeMap::from([
+            ("uuid".to_string(), "1".to_string()),
+            ("async_trait".to_string(), "0.1".to_string()),
+            ("openssl".to_string(), "0.10".to_string()),
+        ]);
+
+        let resolution: DependencyResolution = policy().resolve(&requested);
+        assert_eq!(resolution.accepted["uuid"].version, "=1.8.0");
+        assert!(resolution.accepted.contains_key("async-trait"));
+        assert_eq!(resolution.rejected, vec!["crate openssl (0.10) is not in the dependency allowlist"]);
+    }
+
+    #[test]
+    fn tests_offline_builds_need_a_vendor_dir() {
+        let vendor_dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_vendor_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&vendor_dir);
+        assert_eq!(policy().build_args(&vendor_dir), vec!["build"]);
+
+        fs::create_dir_all(&vendor_dir).unwrap();
+        assert_eq!(policy().build_args(&vendor_dir), vec!["build", "--offline"]);
+        assert_eq!(policy().run_args(&vendor_dir), vec!["run", "--offline"]);
+        assert_eq!(DependencyPolicy::default().build_args(&vendor_dir), vec!["build"]);
+        fs::remove_dir_all(&vendor_dir).unwrap();
+    }
+
+    #[test]
+    fn tests_shipped_allowlist_parses() {
+        let policy: DependencyPolicy = DependencyPolicy::load_default();
+        assert!(policy.find("actix_web").is_some());
+        assert!(!policy.offline);
+    }
+
+    #[test]
+    fn tests_missing_crates_from_build_errors() {
+        let output: &str = "error[E0432]: unresolved import `uuid`
+error[E0433]: failed to resolve: use of undeclared crate or module `chrono`
+error[E0432]: unresolved import `crate::models::Task`
+error[E0433]: failed to resolve: use of undeclared crate or module `models`
+error[E0432]: unresolved import `handlers`";
+        let local_modules: Vec<String> = vec!["models".to_string(), "handlers".to_string()];
+        assert_eq!(missing_crates(output, &local_modules), vec!["uuid", "chrono"]);
+        assert_eq!(missing_crates(output, &[]), vec!["uuid", "chrono", "models", "handlers"]);
+    }
+
+    #[test]
+    fn tests_patch_cargo_toml_keeps_formatting() {
+        let project_dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_deps_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_dir);
+        fs::create_dir_all(&project_dir).unwrap();
+        let cargo_toml: PathBuf = project_dir.join("Cargo.toml");
+        fs::write(&cargo_toml, "[package]\nname = \"web\"\n\n[dependencies]\n# 已有的依賴\nasync-trait=\"0.1.68\"\n").unwrap();
+
+        let resolution: DependencyResolution = policy().resolve(&BTreeMap::from([
+            ("uuid".to_string(), "1".to_string()),
+            ("async-trait".to_string(), "0.1".to_string()),
+        ]));
+        let added: Vec<String> = patch_cargo_toml(&cargo_toml, &resolution.accepted).unwrap();
+        assert_eq!(added, vec!["uuid"]);
+
+        let patched: String = fs::read_to_string(&cargo_toml).unwrap();
+        assert!(patched.contains("# 已有的依賴\nasync-trait=\"0.1.68\""));
+        assert!(patched.contains("uuid = { version = \"=1.8.0\", features = [\"v4\"] }"));
+
+        fs::remove_dir_all(&project_dir).unwrap();
+    }
+}
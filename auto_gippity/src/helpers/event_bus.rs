@@ -0,0 +1,350 @@
+// 代理生命週期事件總線：代理、LLM 調用、構建、端點測試和審批都會發布類型化的事件，
+// 訂閱者（終端打印、JSONL 日誌、指標收集、測試記錄器）按需掛載到全局總線上。
+
+use crate::helpers::command_line::PrintCommand;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 代理生命週期事件
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AgentEvent {
+    AgentStarted { agent: String },
+    StateChanged { agent: String, from: String, to: String },
+    LlmRequest { agent: String, operation: String, prompt_tokens: usize },
+    LlmResponse { agent: String, operation: String, response_chars: usize },
+    BuildStarted { agent: String, project_dir: String },
+    BuildFinished { agent: String, success: bool, error_count: usize },
+    EndpointTested { agent: String, route: String, status: Option<u16>, passed: bool },
+    ApprovalRequested { gate: String, detail: String, approved: bool, decided_by: String },
+    ToolCalled { agent: String, tool: String, allowed: bool, success: bool },
+    RunFinished { exit_code: i32 },
+}
+
+impl AgentEvent {
+    // 事件所屬的代理；與代理無關的事件返回 None
+    pub fn agent(&self) -> Option<&str> {
+        match self {
+            Self::AgentStarted { agent }
+            | Self::StateChanged { agent, .. }
+            | Self::LlmRequest { agent, .. }
+            | Self::LlmResponse { agent, .. }
+            | Self::BuildStarted { agent, .. }
+            | Self::BuildFinished { agent, .. }
+            | Self::EndpointTested { agent, .. }
+            | Self::ToolCalled { agent, .. } => Some(agent.as_str()),
+            Self::ApprovalRequested { .. } | Self::RunFinished { .. } => None,
+        }
+    }
+}
+
+// 事件訂閱者
+pub trait EventSubscriber: Send + Sync {
+    fn on_event(&self, event: &AgentEvent);
+}
+
+// 訂閱編號，用於取消訂閱
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionId(u64);
+
+// 事件總線：按訂閱順序把每個事件同步地交給所有訂閱者
+pub struct EventBus {
+    subscribers: RwLock<Vec<(SubscriptionId, Arc<dyn EventSubscriber>)>>,
+    next_id: AtomicU64,
+}
+
+impl EventBus {
+    pub const fn new() -> Self {
+        Self {
+            subscribers: RwLock::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    // 加入一個訂閱者
+    pub fn subscribe(&self, subscriber: Arc<dyn EventSubscriber>) -> SubscriptionId {
+        let id: SubscriptionId = SubscriptionId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.subscribers.write().unwrap().push((id, subscriber));
+        id
+    }
+
+    // 移除一個訂閱者
+    #[cfg(test)]
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.subscribers.write().unwrap().retain(|(subscribed, _)| *subscribed != id);
+    }
+
+    // 發布一個事件
+    pub fn publish(&self, event: AgentEvent) {
+        let subscribers: Vec<Arc<dyn EventSubscriber>> = self
+            .subscribers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect();
+        for subscriber in subscribers {
+            subscriber.on_event(&event);
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static EVENT_BUS: EventBus = EventBus::new();
+
+// 全局事件總線
+pub fn event_bus() -> &'static EventBus {
+    &EVENT_BUS
+}
+
+// 向全局事件總線發布一個事件
+pub fn publish(event: AgentEvent) {
+    EVENT_BUS.publish(event);
+}
+
+// 當前時間（Unix 毫秒）
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default()
+}
+
+// 終端打印：把事件打印成一行代理消息
+pub struct TerminalPrinter;
+
+impl EventSubscriber for TerminalPrinter {
+    fn on_event(&self, event: &AgentEvent) {
+        let agent: &str = event.agent().unwrap_or("Events");
+        let line: String = serde_json::to_string(event).expect("Failed to serialize event");
+        PrintCommand::UnitTest.print_agent_message(agent, line.as_str());
+    }
+}
+
+// 帶時間戳寫入 JSONL 日誌的事件
+#[derive(Serialize)]
+struct LoggedEvent<'a> {
+    at_ms: u128,
+    #[serde(flatten)]
+    event: &'a AgentEvent,
+}
+
+// JSONL 日誌：每個事件追加一行
+pub struct JsonlEventLog {
+    file: Mutex<File>,
+}
+
+impl JsonlEventLog {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl EventSubscriber for JsonlEventLog {
+    fn on_event(&self, event: &AgentEvent) {
+        let logged: LoggedEvent = LoggedEvent { at_ms: now_ms(), event };
+        let line: String = serde_json::to_string(&logged).expect("Failed to serialize event");
+        if let Err(e) = writeln!(self.file.lock().unwrap(), "{}", line) {
+            PrintCommand::Issue.print_agent_message("Events", format!("Failed to write event log: {}", e).as_str());
+        }
+    }
+}
+
+// 一次運行的指標
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct RunMetrics {
+    pub agents_started: usize,
+    pub llm_requests: usize,
+    pub llm_prompt_tokens: usize,
+    pub builds: usize,
+    pub failed_builds: usize,
+    pub endpoints_tested: usize,
+    pub endpoints_failed: usize,
+    pub approvals_requested: usize,
+    pub approvals_denied: usize,
+    pub tool_calls: usize,
+    pub tool_calls_denied: usize,
+    pub state_changes: BTreeMap<String, usize>,
+}
+
+// 指標收集：匯總事件數量
+#[derive(Default)]
+pub struct MetricsCollector {
+    metrics: Mutex<RunMetrics>,
+}
+
+impl MetricsCollector {
+    pub fn snapshot(&self) -> RunMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    // 一行匯總
+    pub fn summary(&self) -> String {
+        let metrics: RunMetrics = self.snapshot();
+        format!(
+            "{} LLM request(s) (~{} prompt tokens), {} build(s) ({} failed), {} endpoint test(s) ({} failed), {} approval(s) ({} denied), {} tool call(s) ({} denied)",
+            metrics.llm_requests,
+            metrics.llm_prompt_tokens,
+            metrics.builds,
+            metrics.failed_builds,
+            metrics.endpoints_tested,
+            metrics.endpoints_failed,
+            metrics.approvals_requested,
+            metrics.approvals_denied,
+            metrics.tool_calls,
+            metrics.tool_calls_denied
+        )
+    }
+}
+
+impl EventSubscriber for MetricsCollector {
+    fn on_event(&self, event: &AgentEvent) {
+        let mut metrics: MutexGuard<RunMetrics> = self.metrics.lock().unwrap();
+        match event {
+            AgentEvent::AgentStarted { .. } => metrics.agents_started += 1,
+            AgentEvent::StateChanged { agent, .. } => *metrics.state_changes.entry(agent.clone()).or_default() += 1,
+            AgentEvent::LlmRequest { prompt_tokens, .. } => {
+                metrics.llm_requests += 1;
+                metrics.llm_prompt_tokens += prompt_tokens;
+            }
+            AgentEvent::BuildFinished { success, .. } => {
+                metrics.builds += 1;
+                if !success {
+                    metrics.failed_builds += 1;
+                }
+            }
+            AgentEvent::EndpointTested { passed, .. } => {
+                metrics.endpoints_tested += 1;
+                if !passed {
+                    metrics.endpoints_failed += 1;
+                }
+            }
+            AgentEvent::ApprovalRequested { approved, .. } => {
+                metrics.approvals_requested += 1;
+                if !approved {
+                    metrics.approvals_denied += 1;
+                }
+            }
+            AgentEvent::ToolCalled { allowed, .. } => {
+                metrics.tool_calls += 1;
+                if !allowed {
+                    metrics.tool_calls_denied += 1;
+                }
+            }
+            AgentEvent::LlmResponse { .. } | AgentEvent::BuildStarted { .. } | AgentEvent::RunFinished { .. } => {}
+        }
+    }
+}
+
+// 事件記錄器：保存收到的事件，可以只記錄某個代理的事件（測試斷言事件順序時使用）
+#[cfg(test)]
+#[derive(Default)]
+pub struct EventRecorder {
+    agent: Option<String>,
+    events: Mutex<Vec<AgentEvent>>,
+}
+
+#[cfg(test)]
+impl EventRecorder {
+    pub fn for_agent(agent: &str) -> Self {
+        Self {
+            agent: Some(agent.to_string()),
+            events: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn events(&self) -> Vec<AgentEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl EventSubscriber for EventRecorder {
+    fn on_event(&self, event: &AgentEvent) {
+        if self.agent.is_none() || event.agent() == self.agent.as_deref() {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_subscribers_receive_events_in_order() {
+        let bus: EventBus = EventBus::new();
+        let recorder: Arc<EventRecorder> = Arc::new(EventRecorder::for_agent("Backend Developer"));
+        let metrics: Arc<MetricsCollector> = Arc::new(MetricsCollector::default());
+        let recorder_id: SubscriptionId = bus.subscribe(recorder.clone());
+        bus.subscribe(metrics.clone());
+
+        bus.publish(AgentEvent::AgentStarted { agent: "Backend Developer".to_string() });
+        bus.publish(AgentEvent::AgentStarted { agent: "Solutions Architect".to_string() });
+        bus.publish(AgentEvent::BuildFinished {
+            agent: "Backend Developer".to_string(),
+            success: false,
+            error_count: 3,
+        });
+        bus.unsubscribe(recorder_id);
+        bus.publish(AgentEvent::RunFinished { exit_code: 2 });
+
+        assert_eq!(
+            recorder.events(),
+            vec![
+                AgentEvent::AgentStarted { agent: "Backend Developer".to_string() },
+                AgentEvent::BuildFinished {
+                    agent: "Backend Developer".to_string(),
+                    success: false,
+                    error_count: 3
+                },
+            ]
+        );
+        let snapshot: RunMetrics = metrics.snapshot();
+        assert_eq!(snapshot.agents_started, 2);
+        assert_eq!((snapshot.builds, snapshot.failed_builds), (1, 1));
+    }
+
+    #[test]
+    fn tests_jsonl_log_writes_one_tagged_line_per_event() {
+        let path: std::path::PathBuf = std::env::temp_dir().join(format!("auto_gippity_events_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log: JsonlEventLog = JsonlEventLog::create(&path).unwrap();
+        log.on_event(&AgentEvent::EndpointTested {
+            agent: "Backend Developer".to_string(),
+            route: "/tasks".to_string(),
+            status: Some(200),
+            passed: true,
+        });
+        log.on_event(&AgentEvent::RunFinished { exit_code: 0 });
+
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "endpoint_tested");
+        assert_eq!(lines[0]["route"], "/tasks");
+        assert!(lines[0]["at_ms"].is_u64());
+        assert_eq!(lines[1]["exit_code"], 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
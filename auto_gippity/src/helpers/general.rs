@@ -1,30 +1,47 @@
-use crate::apis::call_request::call_gpt;
+use crate::apis::call_request::{call_gpt_with_model, LLM_MODEL};
 // 引入模块crate::apis::call_request::call_gpt，用于调用GPT的API请求。
 
+use crate::helpers::code_templates::{select_code_template, CodeTemplate};
+// 引入代码模板库，用于根据项目范围挑选代码模板。
+
 use crate::helpers::command_line::PrintCommand;
 // 引入模块crate::helpers::command_line::PrintCommand，用于打印命令行信息。
 
+use crate::models::agents::agent_traits::{ApiSample, ProjectScope};
+// 引入ProjectScope结构体，用于匹配代码模板；ApiSample用于记录外部API的回应样本。
+
+use crate::helpers::event_bus::{publish, AgentEvent};
+// 引入事件总线，发布LLM请求和响应事件。
+
+use crate::helpers::prompt_budget::{model_limits, ModelLimits, PromptBudgeter};
+// 引入提示预算，保证发送的提示不超过模型上下文。
+
+use crate::models::agent_basic::agent_memory::{estimate_messages_tokens, MemoryWindow};
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agent_basic::basic_traits::BasicTraits;
+// 引入BasicAgent，带记忆的请求会读取并更新代理的记忆。
+
 use crate::models::general::llm::Message;
 // 引入模块crate::models::general::llm::Message，用于定义消息结构。
 
+use crate::models::general::run_error::RunError;
+// 引入RunError，LLM调用失败或回应无法解码时返回给代理。
+
 use reqwest::Client;
 // 引入reqwest库中的Client，用于发送HTTP请求。
 
 use serde::de::DeserializeOwned;
 // 引入serde库中的DeserializeOwned特性，用于反序列化JSON数据。
 
+use serde_json::Value;
+// 引入serde_json中的Value，用于推断JSON回应的结构。
+
 use std::fs;
 // 引入标准库std::fs，用于文件系统操作。
 
-// 常量CODE_TEMPLATE_PATH，定义代码模板的文件路径。
-const CODE_TEMPLATE_PATH: &str = r"C:\Users\a0922\Desktop\rust\rust_autogpt/web_template/src/code_template.rs";
-
 // 常量WEB_SERVER_PROJECT_PATH，定义Web服务器项目的文件路径。
 pub const WEB_SERVER_PROJECT_PATH: &str = r"C:\Users\a0922\Desktop\rust\rust_autogpt\web_template\";
 
-// 常量EXEC_MAIN_PATH，定义可执行文件main.rs的文件路径。
-pub const EXEC_MAIN_PATH: &str = r"C:\Users\a0922\Desktop\rust\rust_autogpt\auto_gippity\src\main.rs";
-    
 
 // 常量API_SCHEMA_PATH，定义API模式文件的文件路径。
 const API_SCHEMA_PATH: &str = r"C:\Users\a0922\Desktop\rust\rust_autogpt/auto_gippity/schemas/api_schema.json";
@@ -32,10 +49,14 @@ const API_SCHEMA_PATH: &str = r"C:\Users\a0922\Desktop\rust\rust_autogpt/auto_gi
 
 
 
-// 扩展AI函数以鼓励特定输出
+// 扩展AI函数以鼓励特定输出（代理改用带记忆的请求后，只有测试还在使用）
+#[cfg_attr(not(test), allow(dead_code))]
 pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> Message {
-    let ai_function_str: &str = ai_func(func_input);
+    extend_ai_function_text(ai_func(func_input), func_input)
+}
 
+// 以文本形式的AI函数扩展消息
+pub fn extend_ai_function_text(ai_function_str: &str, func_input: &str) -> Message {
     // 扩展字符串以鼓励只打印输出
     let msg: String = format!(
         "FUNCTION: {}
@@ -49,47 +70,116 @@ pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -
     Message {
         role: "system".to_string(),
         content: msg,
+        ..Message::default()
     }
 }
 
-// 执行对GPT的调用请求
+// 执行对GPT的调用请求（代理改用带记忆的请求后，只有测试还在使用）
+#[cfg_attr(not(test), allow(dead_code))]
 pub async fn ai_task_request(
     msg_context: String,
     agent_position: &str,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> String {
-    // 扩展AI函数
+) -> Result<String, RunError> {
+    // 扩展AI函数，输入超出预算时先裁剪
+    let msg_context: String = PromptBudgeter::default().fit_text(&msg_context);
     let extended_msg: Message = extend_ai_function(function_pass, &msg_context);
 
     // 打印当前状态
     PrintCommand::AICall.print_agent_message(agent_position, agent_operation);
 
     // 获取LLM响应
+    call_gpt_with_retry(agent_position, agent_operation, LLM_MODEL, vec![extended_msg]).await
+}
+
+// 调用GPT，失败时再尝试一次，两次都失败时返回 LlmUnavailable；请求和响应都会发布到事件总线
+async fn call_gpt_with_retry(
+    agent_position: &str,
+    agent_operation: &str,
+    model: &str,
+    messages: Vec<Message>,
+) -> Result<String, RunError> {
+    publish(AgentEvent::LlmRequest {
+        agent: agent_position.to_string(),
+        operation: agent_operation.to_string(),
+        prompt_tokens: estimate_messages_tokens(&messages),
+    });
     let llm_response_res: Result<String, Box<dyn std::error::Error + Send>> =
-        call_gpt(vec![extended_msg.clone()]).await;
+        call_gpt_with_model(model, messages.clone()).await;
 
     // 返回成功或再次尝试
-    match llm_response_res {
+    let llm_response: String = match llm_response_res {
         Ok(llm_resp) => llm_resp,
-        Err(_) => call_gpt(vec![extended_msg.clone()])
+        Err(_) => call_gpt_with_model(model, messages)
             .await
-            .expect("Failed twice to call OpenAI"),
-    }
+            .map_err(|e| RunError::LlmUnavailable(format!("{} failed twice: {}", agent_operation, e)))?,
+    };
+    publish(AgentEvent::LlmResponse {
+        agent: agent_position.to_string(),
+        operation: agent_operation.to_string(),
+        response_chars: llm_response.chars().count(),
+    });
+    Ok(llm_response)
 }
 
-// 执行对GPT的调用请求 - 解码后的
-pub async fn ai_task_request_decoded<T: DeserializeOwned>(
+// 带记忆执行对GPT的调用请求：先发送代理记忆中最近的对话，再发送本次任务；
+// 本次的提示和回应会写回代理记忆
+pub async fn ai_task_request_with_memory(
     msg_context: String,
-    agent_position: &str,
+    agent: &mut BasicAgent,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> T {
+) -> Result<String, RunError> {
+    let ai_function_str: &str = function_pass(&msg_context);
+    ai_text_task_request_with_memory(msg_context, agent, agent_operation, ai_function_str).await
+}
+
+// 带记忆执行对GPT的调用请求，AI函数以文本给出（例如外部代理发来的函数描述）
+pub async fn ai_text_task_request_with_memory(
+    msg_context: String,
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    ai_function_str: &str,
+) -> Result<String, RunError> {
+    // 代理可以指定自己的模型，预算按该模型的上下文计算
+    let model: String = agent.model.clone().unwrap_or_else(|| LLM_MODEL.to_string());
+
+    // 扩展AI函数，输入超出预算时先裁剪
+    let msg_context: String = PromptBudgeter::for_model(&model).fit_text(&msg_context);
+    let extended_msg: Message = extend_ai_function_text(ai_function_str, &msg_context);
+
+    // 打印当前状态
+    PrintCommand::AICall.print_agent_message(agent.position.as_str(), agent_operation);
+
+    // 记忆在前，本次任务在后；记忆只使用提示中剩余的预算，较早的对话会被压缩
+    let limits: ModelLimits = model_limits(&model);
+    let memory_budget: usize = limits
+        .prompt_tokens()
+        .saturating_sub(estimate_messages_tokens(std::slice::from_ref(&extended_msg)));
+    let mut messages: Vec<Message> = MemoryWindow::default().compressed(&agent.memory, memory_budget);
+    messages.push(extended_msg);
+
     let llm_response: String =
-        ai_task_request(msg_context, agent_position, agent_operation, function_pass).await;
+        call_gpt_with_retry(agent.position.as_str(), agent_operation, &model, messages).await?;
+
+    let prompt: String = format!("{}\nINPUT: {}", agent_operation, msg_context);
+    agent.remember_turn(&prompt, &llm_response);
+    Ok(llm_response)
+}
+
+// 带记忆执行对GPT的调用请求 - 解码后的；回应无法解码时返回 MalformedReply
+pub async fn ai_task_request_decoded_with_memory<T: DeserializeOwned>(
+    msg_context: String,
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+) -> Result<T, RunError> {
+    let llm_response: String =
+        ai_task_request_with_memory(msg_context, agent, agent_operation, function_pass).await?;
     let decoded_response: T = serde_json::from_str(llm_response.as_str())
-        .expect("Failed to decode ai response from serde_json");
-    return decoded_response;
+        .map_err(|e| RunError::MalformedReply(format!("{}: {}", agent_operation, e)))?;
+    Ok(decoded_response)
 }
 
 // 检查请求URL是否合法
@@ -98,22 +188,76 @@ pub async fn check_status_code(client: &Client, url: &str) -> Result<u16, reqwes
     Ok(response.status().as_u16())
 }
 
-// 获取代码模板内容
-pub fn read_code_template_contents() -> String {
-    let path: String = String::from(CODE_TEMPLATE_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+// 外部API回应样本保留的最大字符数
+pub const API_SAMPLE_MAX_CHARS: usize = 1000;
+
+// 为推断JSON结构最多读取的回应字节数，避免下载过大的回应
+const API_SAMPLE_MAX_BODY_BYTES: usize = 256 * 1024;
+
+// 推断JSON值的结构：对象保留键，数组取第一个元素的结构，标量替换为类型名
+pub fn infer_json_shape(value: &Value) -> Value {
+    match value {
+        Value::Null => Value::String("null".to_string()),
+        Value::Bool(_) => Value::String("bool".to_string()),
+        Value::Number(_) => Value::String("number".to_string()),
+        Value::String(_) => Value::String("string".to_string()),
+        Value::Array(items) => match items.first() {
+            Some(first) => Value::Array(vec![infer_json_shape(first)]),
+            None => Value::Array(vec![]),
+        },
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| (key.clone(), infer_json_shape(item)))
+                .collect(),
+        ),
+    }
 }
 
-// 获取可执行文件main.rs的内容
-pub fn read_exec_main_contents() -> String {
-    let path: String = String::from(EXEC_MAIN_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+// 按字符边界截断字符串
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((idx, _)) => format!("{}...", &text[..idx]),
+        None => text.to_string(),
+    }
 }
 
-// 保存新的后端代码
-pub fn save_backend_code(contents: &String) {
-    let path: String = String::from(EXEC_MAIN_PATH);
-    fs::write(path, contents).expect("Failed to write main.rs file");
+// 请求外部URL并记录回应样本：状态码、内容类型、截断的回应体以及推断的JSON结构
+pub async fn sample_url_response(client: &Client, url: &str) -> Result<ApiSample, reqwest::Error> {
+    let mut response: reqwest::Response = client.get(url).send().await?;
+    let status_code: u16 = response.status().as_u16();
+    let content_type: Option<String> = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    // 分块读取回应体，超过上限即停止
+    let mut body: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= API_SAMPLE_MAX_BODY_BYTES {
+            break;
+        }
+    }
+
+    let body_str: String = String::from_utf8_lossy(&body).to_string();
+    let json_shape: Option<Value> = serde_json::from_str::<Value>(&body_str)
+        .ok()
+        .map(|value| infer_json_shape(&value));
+
+    Ok(ApiSample {
+        url: url.to_string(),
+        status_code,
+        content_type,
+        body_sample: truncate_chars(&body_str, API_SAMPLE_MAX_CHARS),
+        json_shape,
+    })
+}
+
+// 获取代码模板内容：按项目范围从模板库中挑选最匹配的模板
+pub fn read_code_template_contents(project_scope: Option<&ProjectScope>) -> String {
+    let template: &CodeTemplate = select_code_template(project_scope);
+    template.source.to_string()
 }
 
 // 保存JSON API端点模式
@@ -126,6 +270,8 @@ pub fn save_api_endpoints(api_endpoints: &String) {
 mod tests {
     use super::*;
     use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
+    use crate::helpers::stub_server::{spawn_stub_server, StubResponse};
+    use serde_json::json;
 
     #[test]
     // 测试扩展AI函数
@@ -148,10 +294,61 @@ mod tests {
             "Defining user requirements",
             convert_user_input_to_goal,
         )
-        .await;
+        .await
+        .unwrap();
 
         assert!(res.len() > 20);
     }
+
+    #[test]
+    // 测试JSON结构推断
+    fn tests_infer_json_shape() {
+        let value: Value = json!({
+            "symbol": "BTCUSDT",
+            "price": 42000.5,
+            "active": true,
+            "trades": [{"id": 1, "qty": "0.1"}, {"id": 2, "qty": "0.2"}],
+            "meta": null
+        });
+        let expected: Value = json!({
+            "symbol": "string",
+            "price": "number",
+            "active": "bool",
+            "trades": [{"id": "number", "qty": "string"}],
+            "meta": "null"
+        });
+        assert_eq!(infer_json_shape(&value), expected);
+    }
+
+    #[tokio::test]
+    // 测试对本地桩服务器的回应采样
+    async fn tests_sample_url_response() {
+        let long_text: String = "x".repeat(API_SAMPLE_MAX_CHARS * 2);
+        let base_url: String = spawn_stub_server(vec![
+            ("/prices", StubResponse::json(200, r#"[{"symbol":"ETH","price":3000}]"#)),
+            ("/text", StubResponse::new(200, "text/plain", &long_text)),
+        ])
+        .await;
+        let client: Client = Client::new();
+
+        let sample: ApiSample = sample_url_response(&client, &format!("{}/prices", base_url))
+            .await
+            .unwrap();
+        assert_eq!(sample.status_code, 200);
+        assert_eq!(sample.content_type.as_deref(), Some("application/json"));
+        assert_eq!(sample.json_shape, Some(json!([{"symbol": "string", "price": "number"}])));
+
+        let sample: ApiSample = sample_url_response(&client, &format!("{}/text", base_url))
+            .await
+            .unwrap();
+        assert!(sample.json_shape.is_none());
+        assert_eq!(sample.body_sample.chars().count(), API_SAMPLE_MAX_CHARS + 3);
+
+        let sample: ApiSample = sample_url_response(&client, &format!("{}/missing", base_url))
+            .await
+            .unwrap();
+        assert_eq!(sample.status_code, 404);
+    }
 }
 
 
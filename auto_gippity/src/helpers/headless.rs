@@ -0,0 +1,232 @@
+// 無人值守（CI）模式：所有提示都從預先提供的答案文件中回答，沒有答案時使用安全的預設值（項目請求必須有答案），
+// 每個回答都會打印出來並記錄，可選地追加寫入 JSONL 日誌文件。
+
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::config::load_toml_config;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+// 答案文件：問題文本 -> 答案；澄清問題由模型生成，文本無法預知，也可以按主題回答
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HeadlessAnswers {
+    pub answers: HashMap<String, String>,
+    pub clarifications: HashMap<String, String>,
+    pub log_file: Option<PathBuf>,
+}
+
+// 一次已回答的提示
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnsweredPrompt {
+    pub question: String,
+    pub answer: String,
+    pub source: String, // "answers_file"、"default" 或交互回答的 "user"
+}
+
+// 答案文件中沒有答案的自由文本提示使用的預設回答
+pub const NO_ANSWER_DEFAULT: &str = "No answer is available in unattended mode; proceed with your best judgement.";
+
+static HEADLESS: OnceLock<HeadlessAnswers> = OnceLock::new();
+static ANSWER_LOG: Mutex<Vec<AnsweredPrompt>> = Mutex::new(Vec::new());
+
+impl HeadlessAnswers {
+    // 讀取答案文件
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Err(format!("answers file {} not found", path.display()).into());
+        }
+        load_toml_config(path)
+    }
+
+    // 回答一個問題：優先使用答案文件，否則使用預設值
+    pub fn resolve(&self, question: &str, default: &str) -> AnsweredPrompt {
+        let question: &str = question.trim();
+        match self.answers.get(question) {
+            Some(answer) => AnsweredPrompt {
+                question: question.to_string(),
+                answer: answer.trim().to_string(),
+                source: "answers_file".to_string(),
+            },
+            None => AnsweredPrompt {
+                question: question.to_string(),
+                answer: default.to_string(),
+                source: "default".to_string(),
+            },
+        }
+    }
+
+    // 回答一個必須回答的問題：答案文件中沒有答案時返回錯誤，不使用預設值
+    pub fn resolve_required(&self, question: &str) -> Result<AnsweredPrompt, String> {
+        let answered: AnsweredPrompt = self.resolve(question, "");
+        if answered.source == "default" {
+            return Err(format!("the answers file has no answer for '{}'", answered.question));
+        }
+        Ok(answered)
+    }
+
+    // 回答一個澄清問題：問題文本沒有答案時按主題查找，都沒有時答案為空
+    pub fn resolve_clarification(&self, topic: &str, question: &str) -> AnsweredPrompt {
+        let answered: AnsweredPrompt = self.resolve(question, "");
+        if answered.source != "default" {
+            return answered;
+        }
+        match self.clarifications.get(topic.trim()) {
+            Some(answer) => AnsweredPrompt {
+                answer: answer.trim().to_string(),
+                source: "answers_file".to_string(),
+                ..answered
+            },
+            None => answered,
+        }
+    }
+}
+
+// 開啟無人值守模式；整個進程只能設置一次
+pub fn enable_headless(answers: HeadlessAnswers) {
+    HEADLESS
+        .set(answers)
+        .expect("Headless mode has already been enabled");
+}
+
+// 是否處於無人值守模式
+pub fn is_headless() -> bool {
+    HEADLESS.get().is_some()
+}
+
+// 在無人值守模式下回答問題並記錄；不在無人值守模式時返回 None
+pub fn headless_answer(question: &str, default: &str) -> Option<String> {
+    let answers: &HeadlessAnswers = HEADLESS.get()?;
+    let answered: AnsweredPrompt = record_answer(answers, answers.resolve(question, default));
+    Some(answered.answer)
+}
+
+// 在無人值守模式下回答必須回答的問題並記錄；沒有答案時返回錯誤，不在無人值守模式時返回 None
+pub fn headless_required_answer(question: &str) -> Option<Result<String, String>> {
+    let answers: &HeadlessAnswers = HEADLESS.get()?;
+    Some(
+        answers
+            .resolve_required(question)
+            .map(|answered| record_answer(answers, answered).answer),
+    )
+}
+
+// 在無人值守模式下按問題文本或主題回答澄清問題並記錄；不在無人值守模式時返回 None
+pub fn headless_clarification(topic: &str, question: &str) -> Option<AnsweredPrompt> {
+    let answers: &HeadlessAnswers = HEADLESS.get()?;
+    Some(record_answer(answers, answers.resolve_clarification(topic, question)))
+}
+
+// 打印回答，追加寫入日誌並記錄到本次運行的回答列表
+fn record_answer(answers: &HeadlessAnswers, answered: AnsweredPrompt) -> AnsweredPrompt {
+    let log_msg: String = format!(
+        "{} -> {:?} (from {})",
+        answered.question, answered.answer, answered.source
+    );
+    PrintCommand::UnitTest.print_agent_message("Headless", log_msg.as_str());
+
+    if let Some(log_file) = &answers.log_file {
+        let line: String = serde_json::to_string(&answered).expect("Failed to serialize answer");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .expect("Failed to open headless answer log");
+        writeln!(file, "{}", line).expect("Failed to write headless answer log");
+    }
+
+    ANSWER_LOG.lock().unwrap().push(answered.clone());
+    answered
+}
+
+// 本次運行中所有已回答的提示
+pub fn answered_prompts() -> Vec<AnsweredPrompt> {
+    ANSWER_LOG.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+
+    #[test]
+    fn tests_answers_resolve_with_defaults() {
+        let answers: HeadlessAnswers = parse_toml_config(
+            r#"
+            [answers]
+            "What webserver are we building today?" = "a todo api "
+            "Are you sure the code is safe to build?" = "y"
+            "#,
+        )
+        .unwrap();
+
+        let answered: AnsweredPrompt = answers.resolve("What webserver are we building today? ", "");
+        assert_eq!(answered.answer, "a todo api");
+        assert_eq!(answered.source, "answers_file");
+
+        let answered: AnsweredPrompt = answers.resolve("Are you sure the code is safe to execute?", "n");
+        assert_eq!(answered.answer, "n");
+        assert_eq!(answered.source, "default");
+
+        let answered: AnsweredPrompt = answers.resolve("Which database should be used?", NO_ANSWER_DEFAULT);
+        assert_eq!((answered.answer.as_str(), answered.source.as_str()), (NO_ANSWER_DEFAULT, "default"));
+    }
+
+    #[test]
+    fn tests_required_questions_need_an_answer() {
+        let answers: HeadlessAnswers = parse_toml_config(
+            r#"
+            [answers]
+            "What webserver are we building today?" = "a todo api"
+            "#,
+        )
+        .unwrap();
+
+        let answered: AnsweredPrompt = answers.resolve_required("What webserver are we building today?").unwrap();
+        assert_eq!(answered.answer, "a todo api");
+        let err: String = HeadlessAnswers::default()
+            .resolve_required("What webserver are we building today?")
+            .unwrap_err();
+        assert_eq!(err, "the answers file has no answer for 'What webserver are we building today?'");
+    }
+
+    #[test]
+    fn tests_example_answers_file_parses() {
+        let answers: HeadlessAnswers =
+            HeadlessAnswers::load(&crate::helpers::config::config_path("answers.example.toml")).unwrap();
+        assert_eq!(answers.resolve("Are you sure the code is safe to execute?", "n").answer, "y");
+        assert_eq!(
+            answers.resolve_clarification("authentication", "Should users be able to sign up?").source,
+            "answers_file"
+        );
+    }
+
+    #[test]
+    fn tests_clarifications_resolve_by_question_then_topic() {
+        let answers: HeadlessAnswers = parse_toml_config(
+            r#"
+            [answers]
+            "Which price API should be used?" = "CoinGecko"
+
+            [clarifications]
+            external_apis = "Binance"
+            "#,
+        )
+        .unwrap();
+
+        let answered: AnsweredPrompt = answers.resolve_clarification("external_apis", "Which price API should be used?");
+        assert_eq!(answered.answer, "CoinGecko");
+        let answered: AnsweredPrompt = answers.resolve_clarification("external_apis", "Which exchange?");
+        assert_eq!((answered.answer.as_str(), answered.source.as_str()), ("Binance", "answers_file"));
+        let answered: AnsweredPrompt = answers.resolve_clarification("data_retention", "How long is data kept?");
+        assert_eq!((answered.answer.as_str(), answered.source.as_str()), ("", "default"));
+    }
+
+    #[test]
+    fn tests_missing_answers_file_is_an_error() {
+        assert!(HeadlessAnswers::load(Path::new("/nonexistent/answers.toml")).is_err());
+    }
+}
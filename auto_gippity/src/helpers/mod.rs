@@ -1,2 +1,22 @@
+pub mod approvals;
+pub mod build_cache;
+pub mod candidates;
+pub mod clarifications;
+pub mod code_chunks;
+pub mod code_templates;
 pub mod command_line;
+pub mod config;
+pub mod critique;
+pub mod dependency_policy;
+pub mod event_bus;
 pub mod general;
+pub mod headless;
+pub mod patching;
+pub mod project_files;
+pub mod project_history;
+pub mod prompt_budget;
+pub mod run_budget;
+#[cfg(test)]
+pub mod stub_server;
+pub mod url_checker;
+pub mod url_policy;
@@ -0,0 +1,375 @@
+// 基於補丁的代碼修改：模型返回 SEARCH/REPLACE 塊或 unified diff，
+// 工具用模糊的上下文匹配應用補丁，無法應用的補丁會報告給模型。
+// 每次應用的修改都記錄在 FactSheet 的 patch_history 上，供檢查。
+
+use serde::{Deserialize, Serialize};
+
+// 模型返回的一處修改：把 search 替換成 replace；line_hint 來自 diff 頭部的行號
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchEdit {
+    pub search: String,
+    pub replace: String,
+    pub line_hint: Option<usize>,
+}
+
+// 已應用的修改：before 是文件中實際被替換的文本
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AppliedEdit {
+    pub start_line: usize,
+    pub before: String,
+    pub after: String,
+    pub fuzzy: bool,
+}
+
+// 無法應用的修改及原因
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RejectedEdit {
+    pub search: String,
+    pub reason: String,
+}
+
+// 一次應用補丁的結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchOutcome {
+    pub code: String,
+    pub applied: Vec<AppliedEdit>,
+    pub rejected: Vec<RejectedEdit>,
+}
+
+// 運行歷史中的一條補丁記錄
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PatchRecord {
+    pub step: String,
+    pub applied: Vec<AppliedEdit>,
+    pub rejected: Vec<RejectedEdit>,
+}
+
+impl PatchOutcome {
+    // 把整個文件替換成新代碼（模型沒有返回補丁時使用）
+    pub fn full_rewrite(code: &str, new_code: &str) -> Self {
+        Self {
+            code: new_code.to_string(),
+            applied: vec![AppliedEdit {
+                start_line: 1,
+                before: code.to_string(),
+                after: new_code.to_string(),
+                fuzzy: false,
+            }],
+            rejected: vec![],
+        }
+    }
+
+    // 整個回應被拒絕，代碼保持不變；回應本身作為被拒絕的內容發回給模型
+    pub fn rejected_reply(code: &str, reply: &str, reason: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            applied: vec![],
+            rejected: vec![RejectedEdit {
+                search: reply.to_string(),
+                reason: reason.to_string(),
+            }],
+        }
+    }
+
+    // 轉換成歷史記錄
+    pub fn record(&self, step: &str) -> PatchRecord {
+        PatchRecord {
+            step: step.to_string(),
+            applied: self.applied.clone(),
+            rejected: self.rejected.clone(),
+        }
+    }
+
+    // 發回給模型的拒絕報告；全部應用時返回 None
+    pub fn rejection_report(&self) -> Option<String> {
+        if self.rejected.is_empty() {
+            return None;
+        }
+        let report: Vec<String> = self
+            .rejected
+            .iter()
+            .map(|rejected| format!("{}:\n{}", rejected.reason, rejected.search))
+            .collect();
+        Some(report.join("\n\n"))
+    }
+}
+
+fn to_lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_string).collect()
+}
+
+fn join_lines(lines: Vec<String>, trailing_newline: bool) -> String {
+    let mut joined: String = lines.join("\n");
+    if trailing_newline && !joined.is_empty() {
+        joined.push('\n');
+    }
+    joined
+}
+
+// 忽略縮進和多餘空白的比較形式
+fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn find_exact(lines: &[String], search: &[String]) -> Vec<usize> {
+    if search.is_empty() || search.len() > lines.len() {
+        return vec![];
+    }
+    (0..=lines.len() - search.len())
+        .filter(|&pos| lines[pos..pos + search.len()] == *search)
+        .collect()
+}
+
+fn find_fuzzy(lines: &[String], search: &[String]) -> Vec<usize> {
+    let search: Vec<String> = search.iter().map(|line| normalize(line)).filter(|line| !line.is_empty()).collect();
+    if search.is_empty() {
+        return vec![];
+    }
+    let normalized: Vec<(usize, String)> = lines
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| (idx, normalize(line)))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    if search.len() > normalized.len() {
+        return vec![];
+    }
+    (0..=normalized.len() - search.len())
+        .filter(|&pos| normalized[pos..pos + search.len()].iter().map(|(_, line)| line).eq(search.iter()))
+        .map(|pos| normalized[pos].0)
+        .collect()
+}
+
+// 模糊匹配的長度：從起點開始覆蓋到最後一個非空的匹配行
+fn fuzzy_span(lines: &[String], start: usize, search: &[String]) -> usize {
+    let mut remaining: usize = search.iter().filter(|line| !line.trim().is_empty()).count();
+    let mut end: usize = start;
+    while remaining > 0 {
+        if !lines[end].trim().is_empty() {
+            remaining -= 1;
+        }
+        end += 1;
+    }
+    end - start
+}
+
+// 解析模型回應中的補丁：優先使用 SEARCH/REPLACE 塊，否則解析 unified diff
+pub fn parse_patches(text: &str) -> Vec<PatchEdit> {
+    let edits: Vec<PatchEdit> = parse_search_replace(text);
+    if edits.is_empty() {
+        parse_unified_diff(text)
+    } else {
+        edits
+    }
+}
+
+fn parse_search_replace(text: &str) -> Vec<PatchEdit> {
+    let mut edits: Vec<PatchEdit> = vec![];
+    let mut search: Option<Vec<&str>> = None;
+    let mut replace: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        let marker: &str = line.trim();
+        if marker.starts_with("<<<<<<<") && marker.ends_with("SEARCH") {
+            search = Some(vec![]);
+            replace = None;
+        } else if marker == "=======" && search.is_some() && replace.is_none() {
+            replace = Some(vec![]);
+        } else if marker.starts_with(">>>>>>>") && marker.ends_with("REPLACE") {
+            if let (Some(search_lines), Some(replace_lines)) = (search.take(), replace.take()) {
+                edits.push(PatchEdit {
+                    search: search_lines.join("\n"),
+                    replace: replace_lines.join("\n"),
+                    line_hint: None,
+                });
+            }
+        } else if let Some(replace_lines) = replace.as_mut() {
+            replace_lines.push(line);
+        } else if let Some(search_lines) = search.as_mut() {
+            search_lines.push(line);
+        }
+    }
+    edits
+}
+
+fn parse_unified_diff(text: &str) -> Vec<PatchEdit> {
+    let mut edits: Vec<PatchEdit> = vec![];
+    let mut hunk: Option<(Vec<&str>, Vec<&str>, Option<usize>)> = None;
+
+    let mut finish = |hunk: &mut Option<(Vec<&str>, Vec<&str>, Option<usize>)>| {
+        if let Some((old, new, line_hint)) = hunk.take() {
+            if !old.is_empty() {
+                edits.push(PatchEdit {
+                    search: old.join("\n"),
+                    replace: new.join("\n"),
+                    line_hint,
+                });
+            }
+        }
+    };
+
+    for line in text.lines() {
+        if let Some(header) = line.strip_prefix("@@") {
+            finish(&mut hunk);
+            // "@@ -12,7 +12,8 @@" 中的舊起始行號
+            let line_hint: Option<usize> = header
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('-'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|start| start.parse().ok());
+            hunk = Some((vec![], vec![], line_hint));
+            continue;
+        }
+        if line.starts_with("--- ") || line.starts_with("+++ ") || line.starts_with("diff ") || line.starts_with("```") {
+            finish(&mut hunk);
+            continue;
+        }
+        let Some((old, new, _)) = hunk.as_mut() else {
+            continue;
+        };
+        match line.chars().next() {
+            Some('-') => old.push(&line[1..]),
+            Some('+') => new.push(&line[1..]),
+            Some(' ') => {
+                old.push(&line[1..]);
+                new.push(&line[1..]);
+            }
+            Some('\\') => {}
+            None => {
+                old.push("");
+                new.push("");
+            }
+            Some(_) => finish(&mut hunk),
+        }
+    }
+    finish(&mut hunk);
+    edits
+}
+
+// 依次應用修改：先精確匹配，再忽略空白模糊匹配；多處匹配時取最接近行號提示的一處，無法確定時拒絕
+pub fn apply_patches(code: &str, edits: &[PatchEdit]) -> PatchOutcome {
+    let mut lines: Vec<String> = to_lines(code);
+    let mut applied: Vec<AppliedEdit> = vec![];
+    let mut rejected: Vec<RejectedEdit> = vec![];
+
+    for edit in edits {
+        let search: Vec<String> = to_lines(&edit.search);
+        let reject = |reason: &str| RejectedEdit {
+            search: edit.search.clone(),
+            reason: reason.to_string(),
+        };
+
+        if search.iter().all(|line| line.trim().is_empty()) {
+            rejected.push(reject("search text is empty"));
+            continue;
+        }
+
+        let (candidates, fuzzy): (Vec<usize>, bool) = match find_exact(&lines, &search) {
+            exact if !exact.is_empty() => (exact, false),
+            _ => (find_fuzzy(&lines, &search), true),
+        };
+
+        let pos: usize = match (candidates.len(), edit.line_hint) {
+            (0, _) => {
+                rejected.push(reject("search text not found"));
+                continue;
+            }
+            (1, _) => candidates[0],
+            (_, Some(hint)) => *candidates
+                .iter()
+                .min_by_key(|&&pos| (pos + 1).abs_diff(hint))
+                .unwrap(),
+            (_, None) => {
+                rejected.push(reject("search text matches more than one place"));
+                continue;
+            }
+        };
+
+        let span: usize = if fuzzy { fuzzy_span(&lines, pos, &search) } else { search.len() };
+        let before: Vec<String> = lines.splice(pos..pos + span, to_lines(&edit.replace)).collect();
+        applied.push(AppliedEdit {
+            start_line: pos + 1,
+            before: before.join("\n"),
+            after: edit.replace.clone(),
+            fuzzy,
+        });
+    }
+
+    PatchOutcome {
+        code: join_lines(lines, code.ends_with('\n')),
+        applied,
+        rejected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "async fn get_task() -> String {
+    let task = Task { id: 1 };
+    format!(\"{}\", tsk.id)
+}
+
+async fn delete_task() -> String {
+    let task = Task { id: 1 };
+    format!(\"{}\", task.id)
+}
+";
+
+    #[test]
+    fn tests_search_replace_with_fuzzy_match() {
+        let patches: &str = "<<<<<<< SEARCH
+  let task = Task { id: 1 };
+  format!(\"{}\",  tsk.id)
+=======
+    let task = Task { id: 2 };
+    format!(\"{}\", task.id)
+>>>>>>> REPLACE
+<<<<<<< SEARCH
+fn missing() {}
+=======
+fn added() {}
+>>>>>>> REPLACE";
+
+        let edits: Vec<PatchEdit> = parse_patches(patches);
+        assert_eq!(edits.len(), 2);
+
+        let outcome: PatchOutcome = apply_patches(CODE, &edits);
+        assert!(outcome.code.contains("Task { id: 2 }"));
+        assert!(!outcome.code.contains("tsk.id"));
+        assert_eq!(outcome.applied.len(), 1);
+        assert!(outcome.applied[0].fuzzy);
+        assert_eq!(outcome.rejected[0].reason, "search text not found");
+        assert!(outcome.rejection_report().unwrap().contains("fn missing() {}"));
+    }
+
+    #[test]
+    fn tests_unified_diff_uses_line_hint() {
+        let patches: &str = "--- a/src/main.rs
++++ b/src/main.rs
+@@ -7,2 +7,2 @@
+     let task = Task { id: 1 };
+-    format!(\"{}\", task.id)
++    format!(\"deleted {}\", task.id)
+";
+
+        let outcome: PatchOutcome = apply_patches(CODE, &parse_patches(patches));
+        assert!(outcome.rejected.is_empty());
+        assert_eq!(outcome.applied[0].start_line, 7);
+        assert!(outcome.code.contains("format!(\"deleted {}\", task.id)"));
+        assert!(outcome.code.contains("format!(\"{}\", tsk.id)"));
+    }
+
+    #[test]
+    fn tests_ambiguous_search_is_rejected() {
+        let edits: Vec<PatchEdit> = vec![PatchEdit {
+            search: "    let task = Task { id: 1 };".to_string(),
+            replace: String::new(),
+            line_hint: None,
+        }];
+        let outcome: PatchOutcome = apply_patches(CODE, &edits);
+        assert_eq!(outcome.code, CODE);
+        assert_eq!(outcome.rejected[0].reason, "search text matches more than one place");
+    }
+}
@@ -0,0 +1,360 @@
+// 多文件項目：後端代理輸出一份文件清單（models、handlers、persistence、config 等模塊以及需要的依賴），
+// 工具檢查清單後寫入運行工作區的 src/ 目錄；依賴經過 dependency_policy 檢查後再寫入 Cargo.toml。
+// 在提示、補丁和補丁歷史中，整個項目以「文件包」的形式出現：每個文件前有一行 FILE_MARKER。
+
+use crate::helpers::code_chunks::strip_code_fences;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// 文件包中每個文件的標記行：「// === FILE: src/main.rs ===」
+pub const FILE_MARKER: &str = "// === FILE: ";
+const FILE_MARKER_END: &str = " ===";
+
+// 項目的入口文件
+pub const MAIN_FILE: &str = "src/main.rs";
+
+// 清單中的一個文件，路徑相對於項目根目錄
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectFile {
+    pub path: String,
+    pub contents: String,
+}
+
+// 文件清單：所有源文件以及模型聲明需要的依賴（名稱 -> 版本）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FileManifest {
+    pub files: Vec<ProjectFile>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+}
+
+impl FileManifest {
+    // 只有 main.rs 的單文件項目
+    pub fn single_file(code: &str) -> Self {
+        Self {
+            files: vec![ProjectFile {
+                path: MAIN_FILE.to_string(),
+                contents: code.to_string(),
+            }],
+            dependencies: BTreeMap::new(),
+        }
+    }
+
+    // 解析模型輸出的 JSON 清單
+    pub fn parse(text: &str) -> Result<Self, String> {
+        serde_json::from_str(&strip_code_fences(text)).map_err(|e| format!("invalid file manifest: {}", e))
+    }
+
+    // 按路徑查找文件
+    pub fn file(&self, path: &str) -> Option<&ProjectFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    // 所有文件的路徑
+    pub fn paths(&self) -> Vec<String> {
+        self.files.iter().map(|file| file.path.clone()).collect()
+    }
+
+    // 項目自己的模塊名稱：每個模塊文件的名稱以及文件中聲明的 mod（包括內聯的 mod）
+    pub fn module_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for file in &self.files {
+            let module: &str = file.path.trim_end_matches(".rs").trim_end_matches("/mod");
+            if file.path != MAIN_FILE {
+                names.extend(module.rsplit('/').next().map(str::to_string));
+            }
+            if let Ok(parsed) = syn::parse_file(&file.contents) {
+                for item in parsed.items {
+                    if let syn::Item::Mod(item_mod) = item {
+                        names.push(item_mod.ident.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // 把所有文件合併成一個文件包
+    pub fn to_bundle(&self) -> String {
+        self.files
+            .iter()
+            .map(|file| format!("{}\n{}", file_header(&file.path), file.contents.trim_end()))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+            + "\n"
+    }
+
+    // 從文件包還原清單；沒有文件標記時整段代碼視為 main.rs
+    pub fn from_bundle(bundle: &str, dependencies: BTreeMap<String, String>) -> Self {
+        let mut files: Vec<ProjectFile> = vec![];
+        let mut current: Option<ProjectFile> = None;
+
+        for line in bundle.lines() {
+            let path: Option<&str> = line
+                .trim()
+                .strip_prefix(FILE_MARKER)
+                .map(|rest| rest.trim_end_matches(FILE_MARKER_END).trim());
+            match path {
+                Some(path) => {
+                    files.extend(current.take());
+                    current = Some(ProjectFile {
+                        path: path.to_string(),
+                        contents: String::new(),
+                    });
+                }
+                None => match current.as_mut() {
+                    Some(file) => {
+                        file.contents.push_str(line);
+                        file.contents.push('\n');
+                    }
+                    None if !line.trim().is_empty() || !files.is_empty() => {
+                        return Self {
+                            dependencies,
+                            ..Self::single_file(bundle)
+                        };
+                    }
+                    None => {}
+                },
+            }
+        }
+        files.extend(current);
+
+        for file in files.iter_mut() {
+            file.contents = format!("{}\n", file.contents.trim());
+        }
+        Self { files, dependencies }
+    }
+
+    // 讀取工作區 src/ 目錄下的所有 Rust 文件（不包括依賴）
+    pub fn load_from(project_dir: &Path) -> Result<Self, String> {
+        let mut files: Vec<ProjectFile> = vec![];
+        let mut pending: Vec<PathBuf> = vec![project_dir.join("src")];
+        while let Some(dir) = pending.pop() {
+            let entries: fs::ReadDir = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            for entry in entries.flatten() {
+                let path: PathBuf = entry.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "rs") {
+                    let relative: &Path = path.strip_prefix(project_dir).unwrap_or(&path);
+                    files.push(ProjectFile {
+                        path: relative.to_string_lossy().replace('\\', "/"),
+                        contents: fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?,
+                    });
+                }
+            }
+        }
+
+        // main.rs 排在最前，其餘按路徑排序
+        files.sort_by_key(|file| (file.path != MAIN_FILE, file.path.clone()));
+        Ok(Self {
+            files,
+            dependencies: BTreeMap::new(),
+        })
+    }
+
+    // 檢查路徑是否安全：只能是 src/ 下的相對 .rs 路徑，且不能重複
+    pub fn check_paths(&self) -> Result<(), String> {
+        let mut seen: Vec<&str> = vec![];
+        for file in &self.files {
+            if !is_safe_path(&file.path) {
+                return Err(format!("unsafe file path '{}': files must be .rs files under src/", file.path));
+            }
+            if seen.contains(&file.path.as_str()) {
+                return Err(format!("duplicate file path '{}'", file.path));
+            }
+            seen.push(file.path.as_str());
+        }
+        Ok(())
+    }
+
+    // 檢查清單：路徑安全、包含 main.rs、每個文件都能解析、聲明的模塊都有文件、每個文件都被聲明、依賴名稱合法
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = vec![];
+        if let Err(e) = self.check_paths() {
+            problems.push(e);
+        }
+        if self.file(MAIN_FILE).is_none() {
+            problems.push(format!("manifest has no {}", MAIN_FILE));
+        }
+
+        let mut declared: Vec<String> = vec![];
+        for file in &self.files {
+            match syn::parse_file(&file.contents) {
+                Ok(parsed) => {
+                    let dir: String = module_dir(&file.path);
+                    for item in parsed.items {
+                        if let syn::Item::Mod(item_mod) = item {
+                            if item_mod.content.is_none() {
+                                declared.push(format!("{}{}", dir, item_mod.ident));
+                            }
+                        }
+                    }
+                }
+                Err(e) => problems.push(format!("{} does not parse: {}", file.path, e)),
+            }
+        }
+
+        for module in &declared {
+            let flat: String = format!("{}.rs", module);
+            let nested: String = format!("{}/mod.rs", module);
+            if self.file(&flat).is_none() && self.file(&nested).is_none() {
+                problems.push(format!("module {} is declared but {} is missing", module, flat));
+            }
+        }
+        for file in &self.files {
+            let module: String = file.path.trim_end_matches(".rs").trim_end_matches("/mod").to_string();
+            if file.path != MAIN_FILE && !declared.contains(&module) {
+                problems.push(format!("{} is never declared with `mod`", file.path));
+            }
+        }
+
+        for (name, version) in &self.dependencies {
+            let valid_name: bool =
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid_name || version.trim().is_empty() {
+                problems.push(format!("invalid dependency {} = {:?}", name, version));
+            }
+        }
+        problems
+    }
+
+    // 把文件寫入項目目錄，並刪除上一份清單中有、這份清單中沒有的文件；清單不合法時什麼都不寫
+    pub fn write_to(&self, project_dir: &Path, previous: Option<&FileManifest>) -> Result<Vec<PathBuf>, String> {
+        let problems: Vec<String> = self.validate();
+        if !problems.is_empty() {
+            return Err(format!("invalid file manifest, no files written: {}", problems.join("; ")));
+        }
+
+        let mut written: Vec<PathBuf> = vec![];
+        for file in &self.files {
+            let path: PathBuf = project_dir.join(&file.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+            }
+            fs::write(&path, &file.contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+            written.push(path);
+        }
+
+        // 上一份清單的路徑在寫入時已經檢查過，這裡仍只刪除安全的路徑
+        let stale: Vec<&ProjectFile> = previous
+            .map(|previous| previous.files.iter().filter(|file| self.file(&file.path).is_none()).collect())
+            .unwrap_or_default();
+        for file in stale.into_iter().filter(|file| is_safe_path(&file.path)) {
+            let path: PathBuf = project_dir.join(&file.path);
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("{}: {}", path.display(), e)),
+            }
+        }
+        Ok(written)
+    }
+}
+
+// 路徑是否安全：只能是 src/ 下的相對 .rs 路徑
+fn is_safe_path(path: &str) -> bool {
+    let path: &Path = Path::new(path);
+    path.components().all(|component| matches!(component, Component::Normal(_)))
+        && path.starts_with("src")
+        && path.extension().is_some_and(|ext| ext == "rs")
+}
+
+// 文件包中某個文件的標記行
+pub fn file_header(path: &str) -> String {
+    format!("{}{}{}", FILE_MARKER, path, FILE_MARKER_END)
+}
+
+// 文件中的 `mod x;` 對應的模塊路徑前綴：src/main.rs -> src/，src/models/mod.rs -> src/models/
+fn module_dir(path: &str) -> String {
+    match path {
+        MAIN_FILE => "src/".to_string(),
+        _ if path.ends_with("/mod.rs") => path.trim_end_matches("mod.rs").to_string(),
+        _ => format!("{}/", path.trim_end_matches(".rs")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"```json
+{
+  "files": [
+    {"path": "src/main.rs", "contents": "mod handlers;\nmod models;\n\nfn main() {\n    handlers::run();\n}\n"},
+    {"path": "src/handlers.rs", "contents": "use crate::models::Task;\n\npub fn run() {\n    let _task = Task { id: 1 };\n}\n"},
+    {"path": "src/models/mod.rs", "contents": "pub struct Task {\n    pub id: u64,\n}\n"}
+  ],
+  "dependencies": {"uuid": "1.8.0"}
+}
+```"#;
+
+    #[test]
+    fn tests_manifest_round_trips_through_bundle() {
+        let manifest: FileManifest = FileManifest::parse(MANIFEST).unwrap();
+        assert!(manifest.validate().is_empty(), "{:?}", manifest.validate());
+
+        let bundle: String = manifest.to_bundle();
+        assert!(bundle.starts_with("// === FILE: src/main.rs ==="));
+        let restored: FileManifest = FileManifest::from_bundle(&bundle, manifest.dependencies.clone());
+        assert_eq!(restored, manifest);
+
+        assert_eq!(manifest.module_names(), vec!["handlers", "models"]);
+
+        let single: FileManifest = FileManifest::from_bundle("fn main() {}\n", BTreeMap::new());
+        assert_eq!(single.paths(), vec![MAIN_FILE.to_string()]);
+    }
+
+    #[test]
+    fn tests_validation_reports_problems() {
+        let mut manifest: FileManifest = FileManifest::parse(MANIFEST).unwrap();
+        manifest.files.remove(2);
+        manifest.files.push(ProjectFile {
+            path: "src/config.rs".to_string(),
+            contents: "pub const PORT: u16 = 8080;\n".to_string(),
+        });
+        manifest.dependencies.insert("bad name".to_string(), "1".to_string());
+
+        let problems: Vec<String> = manifest.validate();
+        assert!(problems.contains(&"module src/models is declared but src/models.rs is missing".to_string()));
+        assert!(problems.contains(&"src/config.rs is never declared with `mod`".to_string()));
+        assert!(problems.iter().any(|problem| problem.starts_with("invalid dependency bad name")));
+
+        manifest.files[0].path = "../Cargo.toml".to_string();
+        assert!(manifest.check_paths().is_err());
+    }
+
+    #[test]
+    fn tests_write_to_workspace() {
+        let project_dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_files_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_dir);
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let manifest: FileManifest = FileManifest::parse(MANIFEST).unwrap();
+        assert_eq!(manifest.write_to(&project_dir, None).unwrap().len(), 3);
+
+        let loaded: FileManifest = FileManifest::load_from(&project_dir).unwrap();
+        assert_eq!(loaded.paths(), vec!["src/main.rs", "src/handlers.rs", "src/models/mod.rs"]);
+
+        // 不合法的清單什麼都不寫
+        let mut invalid: FileManifest = manifest.clone();
+        invalid.files[1].contents = "pub fn run( {".to_string();
+        let err: String = invalid.write_to(&project_dir, Some(&manifest)).unwrap_err();
+        assert!(err.starts_with("invalid file manifest, no files written: src/handlers.rs does not parse"));
+        assert_eq!(FileManifest::load_from(&project_dir).unwrap(), loaded);
+
+        // 新清單中沒有的文件被刪除
+        let mut merged: FileManifest = manifest.clone();
+        merged.files.remove(2);
+        merged.files[0].contents = "mod handlers;\n\nfn main() {\n    handlers::run();\n}\n".to_string();
+        merged.files[1].contents = "pub fn run() {}\n".to_string();
+        merged.write_to(&project_dir, Some(&manifest)).unwrap();
+        assert_eq!(FileManifest::load_from(&project_dir).unwrap().paths(), vec!["src/main.rs", "src/handlers.rs"]);
+
+        fs::remove_dir_all(&project_dir).unwrap();
+    }
+}
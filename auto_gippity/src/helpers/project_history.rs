@@ -0,0 +1,182 @@
+// 生成項目的 git 歷史：倉庫放在項目目錄之外（單獨的 git 目錄，工作區指向項目目錄），每個代理步驟之後提交一次，
+// 提交信息寫明代理、狀態和原因。構建成功的版本打上 last-green 標籤，修復變得更糟時可以回滾到該版本。
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// 最後一次構建成功的版本的標籤
+pub const LAST_GREEN_TAG: &str = "last-green";
+
+// 歷史倉庫的預設位置，不放在項目目錄裡，避免在模板的 checkout 中嵌套倉庫
+pub const DEFAULT_HISTORY_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/.project_history");
+
+// 提交時使用的作者
+const COMMIT_AUTHOR: [&str; 4] = ["-c", "user.name=auto_gippity", "-c", "user.email=auto_gippity@localhost"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectHistory {
+    pub project_dir: PathBuf,
+    pub git_dir: PathBuf,
+}
+
+impl ProjectHistory {
+    // 打開 git_dir 中記錄 project_dir 的倉庫；不存在時初始化，提交模板並標記為最後一次構建成功的版本。
+    // target 目錄寫在倉庫自己的 info/exclude 中，不往項目目錄寫任何文件
+    pub fn open(project_dir: &Path, git_dir: &Path) -> Result<Self, String> {
+        let history: Self = Self {
+            project_dir: project_dir.to_path_buf(),
+            git_dir: git_dir.to_path_buf(),
+        };
+
+        if !git_dir.join("HEAD").exists() {
+            std::fs::create_dir_all(git_dir).map_err(|e| format!("{}: {}", git_dir.display(), e))?;
+            history.git(&["init", "--quiet"])?;
+            let exclude: PathBuf = git_dir.join("info").join("exclude");
+            std::fs::create_dir_all(git_dir.join("info")).map_err(|e| e.to_string())?;
+            std::fs::write(&exclude, "/target\n/.git\n").map_err(|e| e.to_string())?;
+
+            history.commit_step("Template", "Discovery", "web template before generation")?;
+            if history.head().is_some() {
+                history.mark_green()?;
+            }
+        }
+        Ok(history)
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, String> {
+        let git_dir: String = format!("--git-dir={}", self.git_dir.display());
+        let work_tree: String = format!("--work-tree={}", self.project_dir.display());
+        let output: Output = Command::new("git")
+            .args([git_dir.as_str(), work_tree.as_str()])
+            .args(args)
+            .current_dir(&self.project_dir)
+            .output()
+            .map_err(|e| format!("failed to run git: {}", e))?;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else {
+            Err(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    // 提交當前的所有修改；沒有修改時返回 None，否則返回提交的哈希
+    pub fn commit_step(&self, agent: &str, state: &str, reason: &str) -> Result<Option<String>, String> {
+        self.git(&["add", "-A"])?;
+        if self.git(&["status", "--porcelain"])?.is_empty() {
+            return Ok(None);
+        }
+
+        let message: String = format!("[{}] {}: {}", agent, state, reason);
+        let mut args: Vec<&str> = COMMIT_AUTHOR.to_vec();
+        args.extend(["commit", "--quiet", "-m", message.as_str()]);
+        self.git(&args)?;
+        self.git(&["rev-parse", "HEAD"]).map(Some)
+    }
+
+    // 把當前提交標記為最後一次構建成功的版本
+    pub fn mark_green(&self) -> Result<(), String> {
+        self.git(&["tag", "-f", LAST_GREEN_TAG]).map(|_| ())
+    }
+
+    fn head(&self) -> Option<String> {
+        self.git(&["rev-parse", "--verify", "--quiet", "HEAD"]).ok()
+    }
+
+    // 最後一次構建成功的提交
+    pub fn last_green(&self) -> Option<String> {
+        self.git(&["rev-parse", "--verify", "--quiet", LAST_GREEN_TAG]).ok()
+    }
+
+    // 把文件恢復到最後一次構建成功的版本，並作為新的提交記錄下來；返回新提交的哈希
+    pub fn rollback_to_last_green(&self, agent: &str, reason: &str) -> Result<Option<String>, String> {
+        let green: String = self.last_green().ok_or("no green build to roll back to")?;
+        // 只刪除 last-green 之後由歷史記錄新增的文件，不清理工作區中的其他內容
+        self.git(&["add", "-A"])?;
+        let added: String = self.git(&["diff", "--cached", "--name-only", "--diff-filter=A", green.as_str()])?;
+        for file in added.lines() {
+            let path: PathBuf = self.project_dir.join(file);
+            std::fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        let source: String = format!("--source={}", green);
+        self.git(&["restore", source.as_str(), "--staged", "--worktree", "--", "."])?;
+        let short_green: &str = &green[..green.len().min(8)];
+        self.commit_step(agent, "Rollback", format!("back to {} ({})", short_green, reason).as_str())
+    }
+
+    // 提交歷史，最新的在前，每行 "哈希 提交信息"
+    #[cfg(test)]
+    pub fn log(&self) -> Result<Vec<String>, String> {
+        let log: String = self.git(&["log", "--format=%h %s"])?;
+        Ok(log.lines().map(str::to_string).collect())
+    }
+}
+
+// 統計 cargo 輸出中的編譯錯誤數量，不包括 "could not compile" 等匯總行
+pub fn count_build_errors(build_output: &str) -> usize {
+    build_output
+        .lines()
+        .filter(|line| line.starts_with("error[") || (line.starts_with("error:") && !is_summary_line(line)))
+        .count()
+}
+
+fn is_summary_line(line: &str) -> bool {
+    line.starts_with("error: could not compile") || line.starts_with("error: aborting")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn tests_count_build_errors() {
+        let output: &str = "error[E0425]: cannot find value `tsk` in this scope
+error: expected `;`, found `}`
+error: could not compile `web_template` (bin \"web_template\") due to 2 previous errors";
+        assert_eq!(count_build_errors(output), 2);
+    }
+
+    #[test]
+    fn tests_commit_and_rollback_to_last_green() {
+        let root: PathBuf = std::env::temp_dir().join(format!("auto_gippity_history_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let project_dir: PathBuf = root.join("web_template");
+        fs::create_dir_all(project_dir.join("src")).unwrap();
+        fs::create_dir_all(project_dir.join("target")).unwrap();
+        fs::write(project_dir.join("target/build.log"), "cached\n").unwrap();
+        let main_rs: PathBuf = project_dir.join("src/main.rs");
+        fs::write(&main_rs, "fn main() {}\n").unwrap();
+
+        // 模板在初始化時提交並標記為構建成功，倉庫不在項目目錄中
+        let history: ProjectHistory = ProjectHistory::open(&project_dir, &root.join("history")).unwrap();
+        assert!(history.last_green().is_some());
+        assert!(!project_dir.join(".git").exists());
+        assert!(!project_dir.join(".gitignore").exists());
+        assert!(history.commit_step("Backend Developer", "Working", "nothing changed").unwrap().is_none());
+
+        fs::write(&main_rs, "fn main() { broken }\n").unwrap();
+        fs::write(project_dir.join("src/extra.rs"), "// new file\n").unwrap();
+        history.commit_step("Backend Developer", "Working", "fix").unwrap();
+
+        history.rollback_to_last_green("Backend Developer", "3 errors > 1").unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&main_rs).unwrap(), "fn main() {}\n");
+        assert!(!project_dir.join("src/extra.rs").exists());
+        assert!(project_dir.join("target/build.log").exists());
+
+        let log: Vec<String> = history.log().unwrap();
+        assert_eq!(log.len(), 3);
+        assert!(log[0].contains("[Backend Developer] Rollback: back to"));
+        assert!(log[2].ends_with("[Template] Discovery: web template before generation"));
+
+        // 再次打開時沿用已有的歷史
+        let reopened: ProjectHistory = ProjectHistory::open(&project_dir, &root.join("history")).unwrap();
+        assert_eq!(reopened.log().unwrap().len(), 3);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
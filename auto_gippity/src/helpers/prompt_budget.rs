@@ -0,0 +1,302 @@
+// 提示預算：按模型估算 token 數，把過長的提示段落裁剪或摘要到上下文之內。
+// 代碼段落只保留與當前任務相關的函數（其餘只留簽名），文本段落保留開頭和結尾，
+// 優先級低的段落先被裁剪，保證發給模型的提示永遠不超過上下文長度。
+// 縮減過的代碼不是完整的文件：只能發給回應以補丁或逐條目拼回的提示，模型原樣輸出整個文件的提示要用 Verbatim。
+
+use crate::apis::call_request::LLM_MODEL;
+use crate::helpers::code_chunks::{function_bodies, FunctionBody};
+
+// 模型的上下文限制
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelLimits {
+    pub context_tokens: usize,
+    pub response_tokens: usize,
+    pub chars_per_token: usize,
+}
+
+// 查詢模型的上下文限制；未知模型使用保守的預設值
+pub fn model_limits(model: &str) -> ModelLimits {
+    let (context_tokens, response_tokens): (usize, usize) = match model {
+        m if m.starts_with("gpt-4o") || m.starts_with("gpt-4-turbo") => (128_000, 4_096),
+        m if m.starts_with("gpt-4-32k") => (32_768, 4_096),
+        m if m.starts_with("gpt-4") => (8_192, 2_048),
+        m if m.starts_with("gpt-3.5-turbo") => (16_385, 4_096),
+        _ => (4_096, 1_024),
+    };
+    let chars_per_token: usize = if model.starts_with("gpt-") { 4 } else { 3 };
+
+    ModelLimits {
+        context_tokens,
+        response_tokens,
+        chars_per_token,
+    }
+}
+
+// 當前使用的模型的上下文限制
+pub fn current_model_limits() -> ModelLimits {
+    model_limits(LLM_MODEL)
+}
+
+impl ModelLimits {
+    // 粗略估算文本的 token 數
+    pub fn estimate_tokens(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(self.chars_per_token)
+    }
+
+    // 提示（包括記憶）最多可用的 token 數，其餘留給模型回應
+    pub fn prompt_tokens(&self) -> usize {
+        self.context_tokens - self.response_tokens
+    }
+}
+
+// 段落類型決定如何縮減：Code 可以聚焦和裁剪，Verbatim 永遠原樣發送
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SectionKind {
+    Text,
+    Code,
+    Verbatim,
+}
+
+// 組合好的提示；code_reduced 表示有代碼段落被聚焦或裁剪，模型看到的不是完整代碼
+#[derive(Debug, Clone, PartialEq)]
+pub struct FittedPrompt {
+    pub text: String,
+    pub code_reduced: bool,
+}
+
+// 提示中的一個段落；priority 越小越先被裁剪
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptSection {
+    pub label: String,
+    pub content: String,
+    pub kind: SectionKind,
+    pub priority: u8,
+}
+
+impl PromptSection {
+    pub fn new(label: &str, content: String, kind: SectionKind, priority: u8) -> Self {
+        Self {
+            label: label.to_string(),
+            content,
+            kind,
+            priority,
+        }
+    }
+
+    fn render(&self) -> String {
+        format!("{}: {} \n", self.label, self.content)
+    }
+}
+
+// 提示預算器
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PromptBudgeter {
+    pub limits: ModelLimits,
+    pub max_tokens: usize,
+}
+
+impl PromptBudgeter {
+    // 任務輸入最多佔用提示的 60%，其餘留給 AI 函數說明和代理記憶
+    pub fn for_model(model: &str) -> Self {
+        let limits: ModelLimits = model_limits(model);
+        Self {
+            limits,
+            max_tokens: limits.prompt_tokens() * 3 / 5,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    fn total_tokens(&self, sections: &[PromptSection]) -> usize {
+        sections.iter().map(|section| self.limits.estimate_tokens(&section.render())).sum()
+    }
+
+    // 把段落組合成提示
+    pub fn fit(&self, sections: Vec<PromptSection>, relevant_terms: &[String]) -> String {
+        self.fit_prompt(sections, relevant_terms).text
+    }
+
+    // 把段落組合成提示；超出預算時先聚焦代碼，再按優先級裁剪，Verbatim 段落不縮減
+    pub fn fit_prompt(&self, mut sections: Vec<PromptSection>, relevant_terms: &[String]) -> FittedPrompt {
+        let original: Vec<String> = sections.iter().map(|section| section.content.clone()).collect();
+        if self.total_tokens(&sections) > self.max_tokens {
+            for section in sections.iter_mut().filter(|section| section.kind == SectionKind::Code) {
+                section.content = focus_code(&section.content, relevant_terms);
+            }
+        }
+
+        let mut order: Vec<usize> = (0..sections.len())
+            .filter(|&idx| sections[idx].kind != SectionKind::Verbatim)
+            .collect();
+        order.sort_by_key(|&idx| sections[idx].priority);
+        for idx in order {
+            let total: usize = self.total_tokens(&sections);
+            if total <= self.max_tokens {
+                break;
+            }
+            let section_tokens: usize = self.limits.estimate_tokens(&sections[idx].content);
+            let keep_tokens: usize = section_tokens.saturating_sub(total - self.max_tokens);
+            sections[idx].content =
+                truncate_middle(&sections[idx].content, keep_tokens * self.limits.chars_per_token);
+        }
+
+        let code_reduced: bool = sections
+            .iter()
+            .zip(&original)
+            .any(|(section, content)| section.kind == SectionKind::Code && &section.content != content);
+        FittedPrompt {
+            text: sections.iter().map(PromptSection::render).collect(),
+            code_reduced,
+        }
+    }
+
+    // 把單段文本裁剪到預算之內
+    pub fn fit_text(&self, text: &str) -> String {
+        truncate_middle(text, self.max_tokens * self.limits.chars_per_token)
+    }
+}
+
+impl Default for PromptBudgeter {
+    fn default() -> Self {
+        Self::for_model(LLM_MODEL)
+    }
+}
+
+// 保留開頭和結尾、省略中間，使結果不超過 max_chars 個字符
+pub fn truncate_middle(text: &str, max_chars: usize) -> String {
+    let total_chars: usize = text.chars().count();
+    if total_chars <= max_chars {
+        return text.to_string();
+    }
+
+    let marker_len: usize = 40;
+    let keep: usize = max_chars.saturating_sub(marker_len);
+    let head: usize = keep * 2 / 3;
+    let tail: usize = keep - head;
+    let head_str: String = text.chars().take(head).collect();
+    let tail_str: String = text.chars().skip(total_chars - tail).collect();
+    format!("{}\n...[{} chars omitted]...\n{}", head_str, total_chars - keep, tail_str)
+}
+
+// 只保留與相關詞有關的函數，其餘函數體替換為佔位符；沒有相關詞或代碼無法解析時原樣返回
+pub fn focus_code(code: &str, relevant_terms: &[String]) -> String {
+    if relevant_terms.is_empty() {
+        return code.to_string();
+    }
+    let bodies: Vec<FunctionBody> = match function_bodies(code) {
+        Ok(bodies) => bodies,
+        Err(_) => return code.to_string(),
+    };
+
+    let mut focused: String = code.to_string();
+    for function in bodies.iter().rev() {
+        let source: &str = &code[function.item.clone()];
+        let is_relevant: bool = relevant_terms.iter().any(|term| source.contains(term.as_str()));
+        if !is_relevant {
+            focused.replace_range(function.body.clone(), "{ /* unchanged */ }");
+        }
+    }
+    focused
+}
+
+// 從編譯錯誤中提取相關詞：反引號中的標識符
+pub fn error_terms(errors: &str) -> Vec<String> {
+    let mut terms: Vec<String> = vec![];
+    for (idx, part) in errors.split('`').enumerate() {
+        let term: &str = part.trim();
+        let is_identifier: bool = !term.is_empty()
+            && term.len() <= 60
+            && term.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':');
+        if idx % 2 == 1 && is_identifier && !terms.iter().any(|t| t == term) {
+            terms.push(term.to_string());
+        }
+    }
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "use actix_web::web;
+
+#[derive(Debug)]
+struct Task {
+    id: u64,
+}
+
+async fn get_tasks() -> String {
+    let tasks = vec![1, 2, 3];
+    format!(\"{:?}\", tasks)
+}
+
+async fn create_task(task: Task) -> u64 {
+    task.id
+}
+";
+
+    #[test]
+    fn tests_model_limits() {
+        let limits: ModelLimits = model_limits("gpt-3.5-turbo");
+        assert_eq!(limits.context_tokens, 16_385);
+        assert_eq!(limits.estimate_tokens("abcdefgh"), 2);
+        assert!(model_limits("gpt-4o-mini").context_tokens > limits.context_tokens);
+        assert_eq!(model_limits("local-llama").context_tokens, 4_096);
+    }
+
+    #[test]
+    fn tests_focus_code_keeps_relevant_functions() {
+        let focused: String = focus_code(CODE, &["create_task".to_string()]);
+        assert!(focused.contains("async fn get_tasks() -> String { /* unchanged */ }"));
+        assert!(focused.contains("task.id"));
+        assert!(focused.contains("#[derive(Debug)]\nstruct Task"));
+        assert_eq!(focus_code("fn broken( {", &["broken".to_string()]), "fn broken( {");
+    }
+
+    #[test]
+    fn tests_verbatim_sections_are_never_reduced() {
+        let budgeter: PromptBudgeter = PromptBudgeter::for_model("gpt-3.5-turbo").with_max_tokens(100);
+        let template: String = CODE.repeat(3);
+        let fitted: FittedPrompt = budgeter.fit_prompt(
+            vec![
+                PromptSection::new("CODE TEMPLATE", template.clone(), SectionKind::Verbatim, 1),
+                PromptSection::new("NOTES", "note ".repeat(200), SectionKind::Text, 0),
+            ],
+            &["create_task".to_string()],
+        );
+        assert!(fitted.text.contains(&template));
+        assert!(!fitted.code_reduced);
+
+        let fitted: FittedPrompt = budgeter.fit_prompt(
+            vec![PromptSection::new("CODE TEMPLATE", template, SectionKind::Code, 1)],
+            &["create_task".to_string()],
+        );
+        assert!(fitted.code_reduced);
+    }
+
+    #[test]
+    fn tests_error_terms() {
+        let errors: &str = "error[E0425]: cannot find value `taskz` in this scope\n help: a local variable with a similar name exists: `tasks`\n note: see `rustc --explain E0425`";
+        assert_eq!(error_terms(errors), vec!["taskz".to_string(), "tasks".to_string()]);
+    }
+
+    #[test]
+    fn tests_fit_trims_low_priority_sections_first() {
+        let budgeter: PromptBudgeter = PromptBudgeter::for_model("gpt-3.5-turbo").with_max_tokens(300);
+        let sections: Vec<PromptSection> = vec![
+            PromptSection::new("CODE", CODE.repeat(5), SectionKind::Code, 2),
+            PromptSection::new("ERRORS", "E0425 ".repeat(400), SectionKind::Text, 1),
+            PromptSection::new("INSTRUCTION", "Only output code.".to_string(), SectionKind::Text, 9),
+        ];
+
+        let prompt: String = budgeter.fit(sections, &["create_task".to_string()]);
+        assert!(budgeter.limits.estimate_tokens(&prompt) <= 300);
+        assert!(prompt.contains("chars omitted"));
+        assert!(prompt.contains("task.id"));
+        assert!(prompt.contains("INSTRUCTION: Only output code."));
+    }
+}
@@ -0,0 +1,59 @@
+// 整次運行的 token 預算：記錄所有 LLM 調用消耗的 token，超過上限時代理停止並返回 BudgetExceeded。
+// 未設置上限時不限制。
+
+use crate::models::general::run_error::RunError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+static TOKENS_USED: AtomicU64 = AtomicU64::new(0);
+static TOKEN_LIMIT: OnceLock<u64> = OnceLock::new();
+
+// 設置本次運行的 token 上限；整個進程只能設置一次
+pub fn set_token_limit(limit: u64) {
+    TOKEN_LIMIT
+        .set(limit)
+        .expect("Token limit has already been set");
+}
+
+// 記錄一次 LLM 調用消耗的 token
+pub fn record_tokens(tokens: u64) {
+    TOKENS_USED.fetch_add(tokens, Ordering::SeqCst);
+}
+
+// 目前已消耗的 token
+pub fn tokens_used() -> u64 {
+    TOKENS_USED.load(Ordering::SeqCst)
+}
+
+// 剩餘可用的 token；未設置上限時返回 None
+pub fn remaining_tokens() -> Option<u64> {
+    TOKEN_LIMIT.get().map(|limit| limit.saturating_sub(tokens_used()))
+}
+
+// 檢查給定用量是否在上限之內
+pub fn check_usage(used: u64, limit: Option<u64>) -> Result<(), RunError> {
+    match limit {
+        Some(limit) if used > limit => Err(RunError::BudgetExceeded { used, limit }),
+        _ => Ok(()),
+    }
+}
+
+// 檢查本次運行是否超出預算
+pub fn check_run_budget() -> Result<(), RunError> {
+    check_usage(tokens_used(), TOKEN_LIMIT.get().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_budget_checks() {
+        assert!(check_usage(1_000_000, None).is_ok());
+        assert!(check_usage(100, Some(100)).is_ok());
+        assert_eq!(
+            check_usage(101, Some(100)),
+            Err(RunError::BudgetExceeded { used: 101, limit: 100 })
+        );
+    }
+}
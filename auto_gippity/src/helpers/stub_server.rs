@@ -0,0 +1,111 @@
+// 測試用的本地桩 HTTP 伺服器：按路徑返回預設的回應，未登記的路徑返回 404。
+// 路由可以寫成 "/path" 或 "HEAD /path"（只匹配該方法）；同一路由登記多次時按順序依次返回，
+// 最後一個回應會一直重複，用於模擬暫時性錯誤。
+// 只實現測試需要的最小 HTTP/1.1 子集，每個連接處理一個請求後關閉。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// 一個預設的回應
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn new(status: u16, content_type: &str, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn json(status: u16, body: &str) -> Self {
+        Self::new(status, "application/json", body)
+    }
+}
+
+// 一個路由的回應序列以及已經服務的次數
+#[derive(Debug, Default)]
+struct StubRoute {
+    responses: Vec<StubResponse>,
+    served: AtomicUsize,
+}
+
+impl StubRoute {
+    fn next_response(&self) -> StubResponse {
+        let idx: usize = self.served.fetch_add(1, Ordering::SeqCst);
+        self.responses[idx.min(self.responses.len() - 1)].clone()
+    }
+}
+
+// 啟動桩伺服器並返回其基礎 URL（例如 http://127.0.0.1:12345）
+pub async fn spawn_stub_server(routes: Vec<(&str, StubResponse)>) -> String {
+    let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url: String = format!("http://{}", listener.local_addr().unwrap());
+
+    let mut route_map: HashMap<String, StubRoute> = HashMap::new();
+    for (route, response) in routes {
+        route_map.entry(route.to_string()).or_default().responses.push(response);
+    }
+    let routes: Arc<HashMap<String, StubRoute>> = Arc::new(route_map);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let routes: Arc<HashMap<String, StubRoute>> = routes.clone();
+            tokio::spawn(async move {
+                let _ = handle_connection(stream, &routes).await;
+            });
+        }
+    });
+
+    base_url
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    routes: &HashMap<String, StubRoute>,
+) -> std::io::Result<()> {
+    // 讀取請求頭直到空行
+    let mut request: Vec<u8> = Vec::new();
+    let mut buf: [u8; 1024] = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n: usize = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request_str: String = String::from_utf8_lossy(&request).to_string();
+    let mut request_line = request_str.lines().next().unwrap_or("").split_whitespace();
+    let method: &str = request_line.next().unwrap_or("GET");
+    let path: &str = request_line.next().unwrap_or("/");
+
+    let response: StubResponse = routes
+        .get(&format!("{} {}", method, path))
+        .or_else(|| routes.get(path))
+        .map(|route| route.next_response())
+        .unwrap_or(StubResponse::new(404, "text/plain", "not found"));
+
+    let mut head: String = format!("HTTP/1.1 {} STUB\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" {
+        stream.write_all(response.body.as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
@@ -0,0 +1,323 @@
+// 並發的外部 URL 檢查器：在 URL 策略允許的前提下，以有限的並行度檢查每個 URL，
+// 對暫時性錯誤重試，先用 HEAD 請求、伺服器不支持 HEAD 時回退到 GET，
+// 並為每個 URL 產生一份類型化的 UrlCheckReport，而不是在出錯時只打印一行。
+
+use crate::helpers::general::sample_url_response;
+use crate::helpers::url_policy::UrlPolicy;
+use crate::models::agents::agent_traits::ApiSample;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// 檢查結果的分類
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UrlCheckOutcome {
+    Reachable,      // 2xx 或 3xx
+    HttpError,      // 4xx 或 5xx（重試後仍然失敗）
+    TransportError, // 連接失敗、超時等
+    Blocked,        // 被 URL 策略阻止，未發出任何請求
+}
+
+// 單個 URL 的檢查報告
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UrlCheckReport {
+    pub url: String,
+    pub outcome: UrlCheckOutcome,
+    pub status_code: Option<u16>,
+    pub content_type: Option<String>,
+    pub is_json: bool,
+    pub method: String,
+    pub attempts: u32,
+    pub error: Option<String>,
+    pub sample: Option<ApiSample>,
+}
+
+impl UrlCheckReport {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            outcome: UrlCheckOutcome::TransportError,
+            status_code: None,
+            content_type: None,
+            is_json: false,
+            method: "HEAD".to_string(),
+            attempts: 0,
+            error: None,
+            sample: None,
+        }
+    }
+
+    // URL 是否可以使用
+    pub fn is_usable(&self) -> bool {
+        self.outcome == UrlCheckOutcome::Reachable
+    }
+
+    // 不可用時的原因說明
+    pub fn failure_reason(&self) -> Option<String> {
+        match self.outcome {
+            UrlCheckOutcome::Reachable => None,
+            UrlCheckOutcome::HttpError => Some(format!(
+                "returned status code {} after {} attempt(s)",
+                self.status_code.unwrap_or(0),
+                self.attempts
+            )),
+            UrlCheckOutcome::TransportError => Some(format!(
+                "request failed after {} attempt(s): {}",
+                self.attempts,
+                self.error.clone().unwrap_or_default()
+            )),
+            UrlCheckOutcome::Blocked => self.error.clone(),
+        }
+    }
+}
+
+// 檢查器設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlCheckerConfig {
+    pub concurrency: usize,
+    pub max_retries: u32,
+    pub timeout: Duration,
+    pub retry_base_delay: Duration,
+    pub collect_samples: bool,
+}
+
+impl Default for UrlCheckerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            max_retries: 2,
+            timeout: Duration::from_secs(5),
+            retry_base_delay: Duration::from_millis(250),
+            collect_samples: true,
+        }
+    }
+}
+
+// 並發 URL 檢查器
+#[derive(Debug, Clone)]
+pub struct UrlChecker {
+    policy: UrlPolicy,
+    config: UrlCheckerConfig,
+    client: Client,
+}
+
+// 值得重試的狀態碼
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 502 | 503 | 504)
+}
+
+// 伺服器不支持 HEAD 時常見的狀態碼
+fn is_head_unsupported(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 405 | 501)
+}
+
+// 內容類型是否為 JSON（包括 application/vnd.api+json 之類）
+pub fn is_json_content_type(content_type: &str) -> bool {
+    let mime: String = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+    mime == "application/json" || mime.ends_with("+json")
+}
+
+impl UrlChecker {
+    pub fn new(policy: UrlPolicy, config: UrlCheckerConfig) -> Self {
+        let client: Client = policy.build_client(config.timeout);
+        Self {
+            policy,
+            config,
+            client,
+        }
+    }
+
+    // 第 attempt 次重試前等待的時間（指數退避）
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.config.retry_base_delay * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+
+    // 發送一個請求，對暫時性錯誤重試；返回最終結果以及嘗試次數
+    async fn send_with_retries(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> (Result<reqwest::Response, reqwest::Error>, u32) {
+        let mut attempts: u32 = 0;
+        loop {
+            attempts += 1;
+            let result: Result<reqwest::Response, reqwest::Error> =
+                self.client.request(method.clone(), url).send().await;
+
+            let transient: bool = match &result {
+                Ok(response) => is_transient_status(response.status()),
+                Err(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+            };
+            if !transient || attempts > self.config.max_retries {
+                return (result, attempts);
+            }
+            tokio::time::sleep(self.backoff(attempts)).await;
+        }
+    }
+
+    // 檢查單個 URL
+    pub async fn check(&self, url: &str) -> UrlCheckReport {
+        let mut report: UrlCheckReport = UrlCheckReport::new(url);
+
+        if let Err(reason) = self.policy.evaluate(url).await {
+            report.outcome = UrlCheckOutcome::Blocked;
+            report.error = Some(reason);
+            return report;
+        }
+
+        let (mut result, mut attempts) = self.send_with_retries(reqwest::Method::HEAD, url).await;
+        if matches!(&result, Ok(response) if is_head_unsupported(response.status())) {
+            report.method = "GET".to_string();
+            let (get_result, get_attempts) = self.send_with_retries(reqwest::Method::GET, url).await;
+            result = get_result;
+            attempts += get_attempts;
+        }
+        report.attempts = attempts;
+
+        match result {
+            Ok(response) => {
+                let status: StatusCode = response.status();
+                report.status_code = Some(status.as_u16());
+                report.content_type = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.to_string());
+                report.is_json = report.content_type.as_deref().is_some_and(is_json_content_type);
+                report.outcome = if status.is_success() || status.is_redirection() {
+                    UrlCheckOutcome::Reachable
+                } else {
+                    UrlCheckOutcome::HttpError
+                };
+            }
+            Err(e) => {
+                report.outcome = UrlCheckOutcome::TransportError;
+                report.error = Some(e.to_string());
+            }
+        }
+
+        // 可用的 URL 再用 GET 取一份回應樣本
+        if report.is_usable() && self.config.collect_samples {
+            match sample_url_response(&self.client, url).await {
+                Ok(sample) => report.sample = Some(sample),
+                Err(e) => report.error = Some(format!("failed to sample response: {}", e)),
+            }
+        }
+
+        report
+    }
+
+    // 以有限的並行度檢查所有 URL，報告順序與輸入順序一致
+    pub async fn check_all(&self, urls: &[String]) -> Vec<UrlCheckReport> {
+        let semaphore: Arc<Semaphore> = Arc::new(Semaphore::new(self.config.concurrency.max(1)));
+        let mut tasks: JoinSet<(usize, UrlCheckReport)> = JoinSet::new();
+
+        for (idx, url) in urls.iter().enumerate() {
+            let checker: UrlChecker = self.clone();
+            let semaphore: Arc<Semaphore> = semaphore.clone();
+            let url: String = url.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("URL checker semaphore closed");
+                (idx, checker.check(&url).await)
+            });
+        }
+
+        let mut reports: Vec<(usize, UrlCheckReport)> = Vec::with_capacity(urls.len());
+        while let Some(joined) = tasks.join_next().await {
+            reports.push(joined.expect("URL check task panicked"));
+        }
+        reports.sort_by_key(|(idx, _)| *idx);
+        reports.into_iter().map(|(_, report)| report).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::stub_server::{spawn_stub_server, StubResponse};
+
+    fn test_checker() -> UrlChecker {
+        let policy: UrlPolicy = UrlPolicy {
+            allow_loopback: true,
+            denied_hosts: vec!["blocked.test".to_string()],
+            ..UrlPolicy::default()
+        };
+        let config: UrlCheckerConfig = UrlCheckerConfig {
+            retry_base_delay: Duration::from_millis(5),
+            ..UrlCheckerConfig::default()
+        };
+        UrlChecker::new(policy, config)
+    }
+
+    #[test]
+    fn tests_json_content_types() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("application/json; charset=utf-8"));
+        assert!(is_json_content_type("application/vnd.api+json"));
+        assert!(!is_json_content_type("text/html"));
+    }
+
+    #[tokio::test]
+    async fn tests_check_all_reports() {
+        let base_url: String = spawn_stub_server(vec![
+            ("/ok", StubResponse::json(200, r#"{"ok":true}"#)),
+            ("/page", StubResponse::new(200, "text/html", "<html></html>")),
+            ("HEAD /nohead", StubResponse::new(405, "text/plain", "")),
+            ("/nohead", StubResponse::json(200, "[1,2]")),
+            ("/flaky", StubResponse::new(503, "text/plain", "busy")),
+            ("/flaky", StubResponse::json(200, "{}")),
+            ("/gone", StubResponse::new(410, "text/plain", "gone")),
+        ])
+        .await;
+        let urls: Vec<String> = ["/ok", "/page", "/nohead", "/flaky", "/gone"]
+            .iter()
+            .map(|path| format!("{}{}", base_url, path))
+            .chain(std::iter::once("http://blocked.test/".to_string()))
+            .collect();
+
+        let reports: Vec<UrlCheckReport> = test_checker().check_all(&urls).await;
+        assert_eq!(reports.len(), urls.len());
+
+        let ok: &UrlCheckReport = &reports[0];
+        assert_eq!(ok.outcome, UrlCheckOutcome::Reachable);
+        assert!(ok.is_json);
+        assert_eq!((ok.method.as_str(), ok.attempts), ("HEAD", 1));
+        assert!(ok.sample.is_some());
+
+        assert_eq!(reports[1].outcome, UrlCheckOutcome::Reachable);
+        assert!(!reports[1].is_json);
+
+        assert_eq!(reports[2].outcome, UrlCheckOutcome::Reachable);
+        assert_eq!(reports[2].method, "GET");
+
+        assert_eq!(reports[3].outcome, UrlCheckOutcome::Reachable);
+        assert_eq!(reports[3].attempts, 2);
+
+        assert_eq!(reports[4].outcome, UrlCheckOutcome::HttpError);
+        assert_eq!((reports[4].status_code, reports[4].attempts), (Some(410), 1));
+
+        assert_eq!(reports[5].outcome, UrlCheckOutcome::Blocked);
+        assert!(reports[5].failure_reason().unwrap().contains("denied"));
+    }
+
+    #[tokio::test]
+    async fn tests_transport_errors_are_retried() {
+        // 綁定後立即釋放端口，使連接被拒絕
+        let listener: std::net::TcpListener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: String = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let report: UrlCheckReport = test_checker().check(&url).await;
+        assert_eq!(report.outcome, UrlCheckOutcome::TransportError);
+        assert_eq!(report.attempts, 3);
+        assert!(report.failure_reason().unwrap().contains("3 attempt"));
+    }
+}
@@ -0,0 +1,396 @@
+// URL 策略層：在架構師請求模型給出的外部 URL 之前，先檢查協議、主機、端口，
+// 並解析主機名，阻止訪問回環、私有、鏈路本地以及雲端元數據地址（除非配置中明確允許），
+// 以防止伺服器端請求偽造（SSRF）。客戶端自己的 DNS 解析也經過同樣的檢查，
+// 檢查之後才改變解析結果的主機（DNS rebinding）和重定向到的主機都無法連到受限地址。
+
+use crate::helpers::config::{config_path, load_toml_config};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+// URL 策略配置文件名
+pub const URL_POLICY_FILE: &str = "url_policy.toml";
+
+// 定義 UrlPolicy 結構體，所有欄位都可以在 url_policy.toml 中覆蓋
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UrlPolicy {
+    pub allowed_schemes: Vec<String>,
+    pub allowed_hosts: Vec<String>, // 為空表示允許任何主機；支持 "*.example.com"
+    pub denied_hosts: Vec<String>,
+    pub allowed_ports: Vec<u16>, // 為空表示允許任何端口
+    pub denied_ports: Vec<u16>,
+    pub allow_loopback: bool,
+    pub allow_private: bool,
+    pub allow_link_local: bool,
+    pub allow_metadata: bool,
+    pub max_redirects: usize,
+}
+
+impl Default for UrlPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_schemes: vec!["http".to_string(), "https".to_string()],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            allowed_ports: vec![],
+            denied_ports: vec![],
+            allow_loopback: false,
+            allow_private: false,
+            allow_link_local: false,
+            allow_metadata: false,
+            max_redirects: 5,
+        }
+    }
+}
+
+// 被阻止的 IP 地址類別
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpCategory {
+    Loopback,
+    Private,
+    LinkLocal,
+    Metadata,
+}
+
+// 雲端元數據服務地址（AWS/GCP/Azure、阿里雲、AWS IPv6）
+const METADATA_V4: [Ipv4Addr; 2] = [Ipv4Addr::new(169, 254, 169, 254), Ipv4Addr::new(100, 100, 100, 200)];
+const METADATA_V6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0x0ec2, 0, 0, 0, 0, 0, 0x0254);
+
+// 判斷 IP 地址屬於哪個受限類別；公網地址返回 None
+pub fn classify_ip(ip: IpAddr) -> Option<IpCategory> {
+    match ip {
+        IpAddr::V4(v4) => classify_ipv4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => classify_ipv4(v4),
+            None => classify_ipv6(v6),
+        },
+    }
+}
+
+fn classify_ipv4(ip: Ipv4Addr) -> Option<IpCategory> {
+    let octets: [u8; 4] = ip.octets();
+    if METADATA_V4.contains(&ip) {
+        Some(IpCategory::Metadata)
+    } else if ip.is_loopback() || ip.is_unspecified() {
+        Some(IpCategory::Loopback)
+    } else if ip.is_link_local() {
+        Some(IpCategory::LinkLocal)
+    } else if ip.is_private() || (octets[0] == 100 && (64..128).contains(&octets[1])) {
+        // 100.64.0.0/10 為運營商級 NAT 地址
+        Some(IpCategory::Private)
+    } else {
+        None
+    }
+}
+
+fn classify_ipv6(ip: Ipv6Addr) -> Option<IpCategory> {
+    let segments: [u16; 8] = ip.segments();
+    let first_segment: u16 = segments[0];
+    if ip == METADATA_V6 {
+        Some(IpCategory::Metadata)
+    } else if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        // 64:ff9b::/96 為 NAT64，按內嵌的 IPv4 地址判斷
+        classify_ipv4(Ipv4Addr::new(
+            (segments[6] >> 8) as u8,
+            segments[6] as u8,
+            (segments[7] >> 8) as u8,
+            segments[7] as u8,
+        ))
+    } else if first_segment == 0x2002 {
+        // 2002::/16 為 6to4，按內嵌的 IPv4 地址判斷
+        classify_ipv4(Ipv4Addr::new(
+            (segments[1] >> 8) as u8,
+            segments[1] as u8,
+            (segments[2] >> 8) as u8,
+            segments[2] as u8,
+        ))
+    } else if ip.is_loopback() || ip.is_unspecified() {
+        Some(IpCategory::Loopback)
+    } else if first_segment & 0xffc0 == 0xfe80 {
+        Some(IpCategory::LinkLocal)
+    } else if first_segment & 0xfe00 == 0xfc00 {
+        // fc00::/7 為唯一本地地址
+        Some(IpCategory::Private)
+    } else {
+        None
+    }
+}
+
+// 主機名是否匹配規則；"*.example.com" 匹配 example.com 的任何子域名
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let host: String = host.to_lowercase();
+    let pattern: String = pattern.to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host.ends_with(&format!(".{}", suffix)),
+        None => host == pattern,
+    }
+}
+
+impl UrlPolicy {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(URL_POLICY_FILE)
+    }
+
+    // 從預設路徑讀取策略，文件不存在時使用預設策略
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse URL policy config")
+    }
+
+    // 該類別的地址是否被允許
+    fn allows_category(&self, category: IpCategory) -> bool {
+        match category {
+            IpCategory::Loopback => self.allow_loopback,
+            IpCategory::Private => self.allow_private,
+            IpCategory::LinkLocal => self.allow_link_local,
+            IpCategory::Metadata => self.allow_metadata,
+        }
+    }
+
+    // 檢查單個 IP 地址
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), String> {
+        match classify_ip(ip) {
+            Some(category) if !self.allows_category(category) => {
+                Err(format!("address {} is in a blocked {:?} range", ip, category))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // 不需要 DNS 的靜態檢查：協議、主機允許/拒絕列表、端口、IP 字面量
+    pub fn check_static(&self, url: &Url) -> Result<(), String> {
+        if !self.allowed_schemes.iter().any(|scheme| scheme == url.scheme()) {
+            return Err(format!("scheme '{}' is not allowed", url.scheme()));
+        }
+
+        let host: &str = url.host_str().ok_or("URL has no host")?;
+        let bare_host: &str = host.trim_start_matches('[').trim_end_matches(']');
+
+        if self.denied_hosts.iter().any(|pattern| host_matches(bare_host, pattern)) {
+            return Err(format!("host '{}' is denied", bare_host));
+        }
+        if !self.allowed_hosts.is_empty()
+            && !self.allowed_hosts.iter().any(|pattern| host_matches(bare_host, pattern))
+        {
+            return Err(format!("host '{}' is not in the allowlist", bare_host));
+        }
+
+        let port: u16 = url.port_or_known_default().ok_or("URL has no port")?;
+        if self.denied_ports.contains(&port) {
+            return Err(format!("port {} is denied", port));
+        }
+        if !self.allowed_ports.is_empty() && !self.allowed_ports.contains(&port) {
+            return Err(format!("port {} is not in the allowlist", port));
+        }
+
+        if let Ok(ip) = bare_host.parse::<IpAddr>() {
+            self.check_ip(ip)?;
+        }
+        Ok(())
+    }
+
+    // 完整檢查：靜態檢查後解析主機名，任何一個解析結果落在受限範圍內都拒絕
+    pub async fn evaluate(&self, url_str: &str) -> Result<Url, String> {
+        let url: Url = Url::parse(url_str).map_err(|e| format!("invalid URL: {}", e))?;
+        self.check_static(&url)?;
+
+        let host: &str = url.host_str().ok_or("URL has no host")?;
+        let bare_host: &str = host.trim_start_matches('[').trim_end_matches(']');
+        let port: u16 = url.port_or_known_default().ok_or("URL has no port")?;
+
+        let addrs: Vec<std::net::SocketAddr> = tokio::net::lookup_host((bare_host, port))
+            .await
+            .map_err(|e| format!("failed to resolve host '{}': {}", bare_host, e))?
+            .collect();
+        if addrs.is_empty() {
+            return Err(format!("host '{}' did not resolve", bare_host));
+        }
+        for addr in addrs {
+            self.check_ip(addr.ip())?;
+        }
+
+        Ok(url)
+    }
+
+    // 建立受策略約束的 HTTP 客戶端：限制重定向次數，對每一跳重新做靜態檢查，
+    // 所有主機名（包括重定向到的主機）都通過策略解析器解析，只連接允許的地址
+    pub fn build_client(&self, timeout: Duration) -> Client {
+        let policy: UrlPolicy = self.clone();
+        let redirect_policy: Policy = Policy::custom(move |attempt| {
+            // previous() 包含最初請求的 URL，它的長度就是這是第幾次重定向
+            let redirect_number: usize = attempt.previous().len();
+            if redirect_number > policy.max_redirects {
+                let reason: String = format!("more than {} redirects", policy.max_redirects);
+                attempt.error(reason)
+            } else if let Err(reason) = policy.check_static(attempt.url()) {
+                attempt.error(format!("redirect blocked: {}", reason))
+            } else {
+                attempt.follow()
+            }
+        });
+
+        Client::builder()
+            .timeout(timeout)
+            .redirect(redirect_policy)
+            .dns_resolver(Arc::new(PolicyResolver { policy: self.clone() }))
+            .build()
+            .expect("Failed to build URL policy client")
+    }
+}
+
+// 客戶端的 DNS 解析器：解析結果中受限的地址被過濾掉，沒有剩下允許的地址時解析失敗
+#[derive(Debug, Clone)]
+pub struct PolicyResolver {
+    policy: UrlPolicy,
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy: UrlPolicy = self.policy.clone();
+        let host: String = name.as_str().to_string();
+        Box::pin(async move {
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let mut blocked: Vec<String> = vec![];
+            let mut allowed: Vec<SocketAddr> = vec![];
+            for addr in resolved {
+                match policy.check_ip(addr.ip()) {
+                    Ok(()) => allowed.push(addr),
+                    Err(reason) => blocked.push(reason),
+                }
+            }
+            if allowed.is_empty() {
+                let reason: String = match blocked.first() {
+                    Some(reason) => format!("host '{}' resolved only to blocked addresses: {}", host, reason),
+                    None => format!("host '{}' did not resolve", host),
+                };
+                return Err(reason.into());
+            }
+            let addrs: Addrs = Box::new(allowed.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+    use crate::helpers::stub_server::{spawn_stub_server, StubResponse};
+
+    #[test]
+    fn tests_classify_ip_ranges() {
+        let cases: [(&str, Option<IpCategory>); 13] = [
+            ("127.0.0.1", Some(IpCategory::Loopback)),
+            ("0.0.0.0", Some(IpCategory::Loopback)),
+            ("10.1.2.3", Some(IpCategory::Private)),
+            ("100.64.0.1", Some(IpCategory::Private)),
+            ("169.254.169.254", Some(IpCategory::Metadata)),
+            ("169.254.1.1", Some(IpCategory::LinkLocal)),
+            ("::ffff:192.168.0.1", Some(IpCategory::Private)),
+            ("fe80::1", Some(IpCategory::LinkLocal)),
+            ("93.184.216.34", None),
+            ("64:ff9b::a9fe:a9fe", Some(IpCategory::Metadata)),
+            ("64:ff9b::7f00:1", Some(IpCategory::Loopback)),
+            ("2002:0a00:0001::1", Some(IpCategory::Private)),
+            ("2002:5db8:d822::1", None),
+        ];
+        for (ip, expected) in cases {
+            assert_eq!(classify_ip(ip.parse().unwrap()), expected, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn tests_static_checks() {
+        let policy: UrlPolicy = parse_toml_config(
+            r#"
+            denied_hosts = ["*.evil.test"]
+            denied_ports = [22]
+            "#,
+        )
+        .unwrap();
+
+        assert!(policy.check_static(&Url::parse("https://api.example.com/x").unwrap()).is_ok());
+        assert!(policy.check_static(&Url::parse("ftp://api.example.com/x").unwrap()).is_err());
+        assert!(policy.check_static(&Url::parse("http://a.evil.test/").unwrap()).is_err());
+        assert!(policy.check_static(&Url::parse("http://example.com:22/").unwrap()).is_err());
+        assert!(policy.check_static(&Url::parse("http://169.254.169.254/latest").unwrap()).is_err());
+        assert!(policy.check_static(&Url::parse("http://[::1]:8080/").unwrap()).is_err());
+
+        let allowlisted: UrlPolicy = UrlPolicy {
+            allowed_hosts: vec!["api.example.com".to_string()],
+            allowed_ports: vec![443],
+            ..UrlPolicy::default()
+        };
+        assert!(allowlisted.check_static(&Url::parse("https://api.example.com/").unwrap()).is_ok());
+        assert!(allowlisted.check_static(&Url::parse("https://other.example.com/").unwrap()).is_err());
+        assert!(allowlisted.check_static(&Url::parse("http://api.example.com/").unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn tests_evaluate_resolves_hosts() {
+        let policy: UrlPolicy = UrlPolicy::default();
+        let reason: String = policy.evaluate("http://localhost:8080/").await.unwrap_err();
+        assert!(reason.contains("Loopback"), "{}", reason);
+
+        let permissive: UrlPolicy = UrlPolicy {
+            allow_loopback: true,
+            ..UrlPolicy::default()
+        };
+        assert!(permissive.evaluate("http://localhost:8080/").await.is_ok());
+    }
+
+    // 客戶端自己解析主機名時同樣過濾受限地址，不依賴之前的 evaluate
+    #[tokio::test]
+    async fn tests_client_resolver_blocks_restricted_addresses() {
+        let base_url: String = spawn_stub_server(vec![("/", StubResponse::json(200, "{}"))]).await;
+        let port: u16 = Url::parse(&base_url).unwrap().port().unwrap();
+        let url: String = format!("http://localhost:{}/", port);
+
+        let strict: Client = UrlPolicy::default().build_client(Duration::from_secs(5));
+        assert!(strict.get(url.as_str()).send().await.is_err());
+
+        let resolver: PolicyResolver = PolicyResolver { policy: UrlPolicy::default() };
+        let reason: String = resolver.resolve("localhost".parse().unwrap()).await.err().unwrap().to_string();
+        assert!(reason.contains("blocked"), "{}", reason);
+
+        let permissive: UrlPolicy = UrlPolicy {
+            allow_loopback: true,
+            ..UrlPolicy::default()
+        };
+        let client: Client = permissive.build_client(Duration::from_secs(5));
+        assert_eq!(client.get(url.as_str()).send().await.unwrap().status().as_u16(), 200);
+    }
+
+    // max_redirects 是允許跟隨的重定向次數上限
+    #[tokio::test]
+    async fn tests_redirect_limit_is_exact() {
+        let redirect = |location: &str| StubResponse {
+            status: 302,
+            headers: vec![("Location".to_string(), location.to_string())],
+            body: String::new(),
+        };
+        let base_url: String = spawn_stub_server(vec![
+            ("/one", redirect("/done")),
+            ("/two", redirect("/one")),
+            ("/done", StubResponse::json(200, "{}")),
+        ])
+        .await;
+        let policy: UrlPolicy = UrlPolicy {
+            allow_loopback: true,
+            max_redirects: 1,
+            ..UrlPolicy::default()
+        };
+        let client: Client = policy.build_client(Duration::from_secs(5));
+
+        let one_hop: reqwest::Response = client.get(format!("{}/one", base_url)).send().await.unwrap();
+        assert_eq!(one_hop.status().as_u16(), 200);
+        let two_hops: String = format!("{:?}", client.get(format!("{}/two", base_url)).send().await.unwrap_err());
+        assert!(two_hops.contains("more than 1 redirects"), "{}", two_hops);
+    }
+}
@@ -20,13 +20,255 @@ mod helpers;
 mod models;
 
 
-use helpers::command_line::PrintCommand;
+use helpers::build_cache::BuildCache;
+use helpers::config::load_toml_config;
+use helpers::command_line::{get_required_user_response, PrintCommand};
+use helpers::headless::{answered_prompts, enable_headless, is_headless, HeadlessAnswers};
+use helpers::dependency_policy::DependencyPolicy;
+use helpers::event_bus::{event_bus, publish, AgentEvent, JsonlEventLog, MetricsCollector, TerminalPrinter};
+use helpers::general::WEB_SERVER_PROJECT_PATH;
+use helpers::run_budget::set_token_limit;
+use apis::call_request::LLM_MODEL;
+use models::agents_manager::agent_registry::{AgentRegistry, PipelineConfig};
+use models::agents_manager::goal_loop::{
+    load_step_log, GoalLoopConfig, GoalLoopReport, LlmPlanner, LogEntry, LoopOutcome, Planner, ReplayPlanner,
+};
+use models::agents_manager::managing_agent::ManagingAgent;
+use models::tools::tool_loop::OpenAiChat;
+use models::general::run_error::{exit_code_for, EXIT_OTHER_ERROR, EXIT_SUCCESS, EXIT_USAGE};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 
-fn main() {
+// 用法；參數錯誤時打印用法並以 EXIT_USAGE 退出
+const USAGE: &str = "usage:
+  auto_gippity [--headless] [--answers <file>] [--max-tokens <n>] [--trace-events] [--event-log <file>] [--pipeline <file>]
+               [--goal-loop] [--max-steps <n>] [--step-log <file>] [--replay <step log>]
+  auto_gippity vendor-setup                                  vendor all crates generated projects may use
+  auto_gippity <AICall|UnitTest|Issue> <agent> <message>   print an agent message";
+
+// 打印參數錯誤和用法，然後退出
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n{}", message, USAGE);
+    std::process::exit(EXIT_USAGE);
+}
+
+// 取出參數後面的值
+fn flag_value<'a>(arg_iter: &mut impl Iterator<Item = &'a String>, flag: &str, expected: &str) -> &'a String {
+    arg_iter
+        .next()
+        .unwrap_or_else(|| usage_error(format!("{} requires {}", flag, expected).as_str()))
+}
+
+// 取出參數後面的數字
+fn flag_number<'a, T: std::str::FromStr>(arg_iter: &mut impl Iterator<Item = &'a String>, flag: &str) -> T {
+    let value: &String = flag_value(arg_iter, flag, "a number");
+    value
+        .parse()
+        .unwrap_or_else(|_| usage_error(format!("{} must be a number, got '{}'", flag, value).as_str()))
+}
+
+#[tokio::main]
+async fn main() {
     let args: Vec<String> = std::env::args().collect();
+
+    if let Some(command) = args.get(1) {
+        if matches!(command.as_str(), "AICall" | "UnitTest" | "Issue") {
+            print_message(&args);
+            return;
+        }
+        if command == "vendor-setup" {
+            std::process::exit(vendor_setup());
+        }
+    }
+
+    let mut headless: bool = false;
+    let mut answers_path: Option<PathBuf> = None;
+    let mut max_tokens: Option<u64> = None;
+    let mut trace_events: bool = false;
+    let mut event_log: Option<PathBuf> = None;
+    let mut pipeline_path: Option<PathBuf> = None;
+    let mut goal_loop: bool = false;
+    let mut max_steps: Option<usize> = None;
+    let mut step_log: Option<PathBuf> = None;
+    let mut replay_path: Option<PathBuf> = None;
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "--answers" => {
+                answers_path = Some(PathBuf::from(flag_value(&mut arg_iter, arg, "a file path")));
+                headless = true;
+            }
+            "--max-tokens" => max_tokens = Some(flag_number(&mut arg_iter, arg)),
+            "--trace-events" => trace_events = true,
+            "--event-log" => event_log = Some(PathBuf::from(flag_value(&mut arg_iter, arg, "a file path"))),
+            "--pipeline" => pipeline_path = Some(PathBuf::from(flag_value(&mut arg_iter, arg, "a file path"))),
+            "--goal-loop" => goal_loop = true,
+            "--max-steps" => max_steps = Some(flag_number(&mut arg_iter, arg)),
+            "--step-log" => step_log = Some(PathBuf::from(flag_value(&mut arg_iter, arg, "a file path"))),
+            "--replay" => {
+                replay_path = Some(PathBuf::from(flag_value(&mut arg_iter, arg, "a step log")));
+                goal_loop = true;
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(EXIT_SUCCESS);
+            }
+            _ => usage_error(format!("unknown argument '{}'", arg).as_str()),
+        }
+    }
+
+    // 無人值守模式：所有提示從答案文件回答，沒有答案時使用安全預設值
+    if headless {
+        let answers: HeadlessAnswers = match &answers_path {
+            Some(path) => HeadlessAnswers::load(path).unwrap_or_else(|e| {
+                PrintCommand::Issue.print_agent_message("Headless", e.to_string().as_str());
+                std::process::exit(EXIT_OTHER_ERROR);
+            }),
+            None => HeadlessAnswers::default(),
+        };
+        enable_headless(answers);
+    }
+
+    if let Some(limit) = max_tokens {
+        set_token_limit(limit);
+    }
+
+    // 事件訂閱者：指標匯總總是開啟，終端跟蹤和 JSONL 日誌按參數開啟
+    let metrics: Arc<MetricsCollector> = Arc::new(MetricsCollector::default());
+    event_bus().subscribe(metrics.clone());
+    if trace_events {
+        event_bus().subscribe(Arc::new(TerminalPrinter));
+    }
+    if let Some(path) = &event_log {
+        match JsonlEventLog::create(path) {
+            Ok(log) => {
+                event_bus().subscribe(Arc::new(log));
+            }
+            Err(e) => {
+                PrintCommand::Issue.print_agent_message("Events", e.as_str());
+                std::process::exit(EXIT_OTHER_ERROR);
+            }
+        }
+    }
+
+    // 流水線在提問之前檢查，配置錯誤時不消耗任何 token
+    let pipeline: PipelineConfig = match &pipeline_path {
+        Some(path) if !path.exists() => {
+            let err_msg: String = format!("pipeline file {} not found", path.display());
+            PrintCommand::Issue.print_agent_message("Pipeline", err_msg.as_str());
+            std::process::exit(EXIT_OTHER_ERROR);
+        }
+        Some(path) => load_toml_config(path).unwrap_or_else(|e| {
+            PrintCommand::Issue.print_agent_message("Pipeline", e.to_string().as_str());
+            std::process::exit(EXIT_OTHER_ERROR);
+        }),
+        None => PipelineConfig::load_default(),
+    };
+    if let Err(e) = pipeline.validate(&AgentRegistry::with_builtin_agents()) {
+        PrintCommand::Issue.print_agent_message("Pipeline", e.as_str());
+        std::process::exit(EXIT_OTHER_ERROR);
+    }
+
+    // 目標循環配置；回放時新的步驟寫入 <日誌>.replay.jsonl，不覆蓋被回放的日誌
+    let mut loop_config: GoalLoopConfig = GoalLoopConfig::load_default();
+    if let Some(steps) = max_steps {
+        loop_config.max_steps = steps;
+    }
+    loop_config.step_log = step_log
+        .or_else(|| replay_path.as_ref().map(|path| path.with_extension("replay.jsonl")))
+        .or(loop_config.step_log);
+
+    let result: Result<(), Box<dyn std::error::Error>> = match &replay_path {
+        Some(path) => {
+            let entries: Vec<LogEntry> = load_step_log(path).unwrap_or_else(|e| {
+                PrintCommand::Issue.print_agent_message("Replay", e.as_str());
+                std::process::exit(EXIT_OTHER_ERROR);
+            });
+            let mut manager: ManagingAgent = ManagingAgent::from_step_log(&entries).unwrap_or_else(|e| {
+                PrintCommand::Issue.print_agent_message("Replay", e.as_str());
+                std::process::exit(EXIT_OTHER_ERROR);
+            });
+            run_goal_loop(&mut manager, &ReplayPlanner::new(&entries), loop_config).await
+        }
+        None => {
+            let usr_req: String = get_required_user_response("What webserver are we building today?").unwrap_or_else(|e| {
+                PrintCommand::Issue.print_agent_message("Headless", e.as_str());
+                std::process::exit(EXIT_OTHER_ERROR);
+            });
+            match ManagingAgent::new(usr_req).await {
+                Ok(mut manager) if goal_loop => {
+                    let planner: LlmPlanner = LlmPlanner::new(Box::new(OpenAiChat { model: LLM_MODEL.to_string() }));
+                    run_goal_loop(&mut manager, &planner, loop_config).await
+                }
+                Ok(manager) => manager.with_pipeline(pipeline).execute_project().await,
+                Err(e) => Err(e),
+            }
+        }
+    };
+
+    let exit_code: i32 = match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(e) => {
+            PrintCommand::Issue.print_agent_message("Project Manager", e.to_string().as_str());
+            exit_code_for(e.as_ref())
+        }
+    };
+
+    // 無人值守模式下匯總所有自動回答的提示
+    if is_headless() {
+        for answered in answered_prompts() {
+            let summary: String = format!("{} -> {:?} (from {})", answered.question, answered.answer, answered.source);
+            PrintCommand::UnitTest.print_agent_message("Headless", summary.as_str());
+        }
+    }
+    PrintCommand::UnitTest.print_agent_message("Metrics", metrics.summary().as_str());
+    publish(AgentEvent::RunFinished { exit_code });
+    std::process::exit(exit_code);
+}
+
+
+// 運行目標循環；達到步數上限而目標沒有完成時視為失敗
+async fn run_goal_loop(
+    manager: &mut ManagingAgent,
+    planner: &dyn Planner,
+    config: GoalLoopConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let step_log: PathBuf = config.step_log();
+    let report: GoalLoopReport = manager.execute_goal_loop(planner, config).await?;
+    PrintCommand::UnitTest.print_agent_message(
+        "Goal Loop",
+        format!("{} step(s) recorded in {}", report.steps.len(), step_log.display()).as_str(),
+    );
+    match report.outcome {
+        LoopOutcome::StepCapReached => Err("goal loop stopped at the step cap before completing its goals".into()),
+        LoopOutcome::Finished(_) | LoopOutcome::GoalsCompleted => Ok(()),
+    }
+}
+
+
+// vendor 模板和依賴允許列表中的所有 crate，讓生成項目可以離線構建
+fn vendor_setup() -> i32 {
+    let cache: BuildCache = BuildCache::load_default();
+    match cache.setup(&DependencyPolicy::load_default(), Path::new(WEB_SERVER_PROJECT_PATH)) {
+        Ok(vendor_dir) => {
+            let message: String = format!("Vendored crates into {}", vendor_dir.display());
+            PrintCommand::UnitTest.print_agent_message("Vendor Setup", message.as_str());
+            EXIT_SUCCESS
+        }
+        Err(e) => {
+            PrintCommand::Issue.print_agent_message("Vendor Setup", e.as_str());
+            EXIT_OTHER_ERROR
+        }
+    }
+}
+
+
+// 打印一條代理消息
+fn print_message(args: &[String]) {
     if args.len() < 4 {
-        panic!("Not enough arguments.");
+        usage_error(format!("{} takes an agent and a message", args[1]).as_str());
     }
     let command = &args[1];
     let agent = &args[2];
@@ -37,8 +279,6 @@ fn main() {
         "AICall" => PrintCommand::AICall.print_agent_message(agent, message),
         "UnitTest" => PrintCommand::UnitTest.print_agent_message(agent, message),
         "Issue" => PrintCommand::Issue.print_agent_message(agent, message),
-        _ => panic!("Unknown command."),
+        _ => usage_error(format!("unknown command '{}'", command).as_str()),
     }
 }
-
-
@@ -0,0 +1,134 @@
+// 代理記憶的窗口策略：每輪對話（提示與回應）都寫入 BasicAgent.memory，
+// 之後的調用會帶上最近的若干輪。記憶總量按估算的 token 數限制在模型上下文之內，超出時丟棄最舊的輪次；
+// 發送前還可以壓縮較早的輪次，為當前提示騰出空間。
+
+use crate::helpers::prompt_budget::{current_model_limits, truncate_middle, ModelLimits};
+use crate::models::general::llm::Message;
+
+// 每條消息的固定開銷（角色、分隔符）
+const TOKENS_PER_MESSAGE: usize = 4;
+
+// 壓縮時較早的消息最多保留的字符數
+const COMPRESSED_MESSAGE_CHARS: usize = 600;
+
+// 估算一組消息的 token 數
+pub fn estimate_messages_tokens(messages: &[Message]) -> usize {
+    let limits: ModelLimits = current_model_limits();
+    messages
+        .iter()
+        .map(|message| limits.estimate_tokens(&message.content) + TOKENS_PER_MESSAGE)
+        .sum()
+}
+
+// 丟棄最舊的消息直到不超過 max_tokens；不會留下沒有對應提示的回應
+fn drop_oldest(memory: &mut Vec<Message>, max_tokens: usize) {
+    while !memory.is_empty() && estimate_messages_tokens(memory) > max_tokens {
+        memory.remove(0);
+        while memory.first().is_some_and(|message| message.role == "assistant") {
+            memory.remove(0);
+        }
+    }
+}
+
+// 記憶窗口：記憶最多佔用的 token 數，以及單條記憶最多保留的字符數
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryWindow {
+    pub max_tokens: usize,
+    pub max_message_chars: usize,
+}
+
+impl Default for MemoryWindow {
+    // 記憶最多佔用提示的一半，其餘留給當前任務
+    fn default() -> Self {
+        Self {
+            max_tokens: current_model_limits().prompt_tokens() / 2,
+            max_message_chars: 6_000,
+        }
+    }
+}
+
+impl MemoryWindow {
+    // 截斷過長的單條記憶，保留開頭部分
+    pub fn clip(&self, content: &str) -> String {
+        match content.char_indices().nth(self.max_message_chars) {
+            Some((idx, _)) => format!("{}\n...[truncated]", &content[..idx]),
+            None => content.to_string(),
+        }
+    }
+
+    // 丟棄最舊的消息直到記憶不超過窗口
+    pub fn apply(&self, memory: &mut Vec<Message>) {
+        drop_oldest(memory, self.max_tokens);
+    }
+
+    // 記錄一輪對話：提示作為 user 消息，回應作為 assistant 消息
+    pub fn record_turn(&self, memory: &mut Vec<Message>, prompt: &str, response: &str) {
+        memory.push(Message {
+            role: "user".to_string(),
+            content: self.clip(prompt),
+            ..Message::default()
+        });
+        memory.push(Message {
+            role: "assistant".to_string(),
+            content: self.clip(response),
+            ..Message::default()
+        });
+        self.apply(memory);
+    }
+
+    // 準備發送的記憶：最近一輪保持原樣，較早的輪次壓縮為摘要，仍然超出 max_tokens 時丟棄最舊的輪次
+    pub fn compressed(&self, memory: &[Message], max_tokens: usize) -> Vec<Message> {
+        let mut sent: Vec<Message> = memory.to_vec();
+        if estimate_messages_tokens(&sent) <= max_tokens {
+            return sent;
+        }
+
+        let recent_start: usize = sent.len().saturating_sub(2);
+        for message in sent.iter_mut().take(recent_start) {
+            message.content = truncate_middle(&message.content, COMPRESSED_MESSAGE_CHARS);
+        }
+        drop_oldest(&mut sent, max_tokens);
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_memory_window_drops_oldest_turns() {
+        let window: MemoryWindow = MemoryWindow {
+            max_tokens: 60,
+            max_message_chars: 80,
+        };
+        let mut memory: Vec<Message> = vec![];
+
+        for turn in 0..5 {
+            let prompt: String = format!("fix attempt {} {}", turn, "x".repeat(100));
+            window.record_turn(&mut memory, &prompt, "code");
+        }
+
+        assert!(estimate_messages_tokens(&memory) <= window.max_tokens);
+        assert_eq!(memory.first().unwrap().role, "user");
+        assert!(memory.first().unwrap().content.starts_with("fix attempt 4"));
+        assert!(memory.first().unwrap().content.ends_with("...[truncated]"));
+        assert_eq!(memory.last().unwrap().content, "code");
+    }
+
+    #[test]
+    fn tests_compressed_memory_keeps_latest_turn() {
+        let window: MemoryWindow = MemoryWindow::default();
+        let mut memory: Vec<Message> = vec![];
+        for turn in 0..3 {
+            let prompt: String = format!("attempt {} {}", turn, "y".repeat(5_000));
+            window.record_turn(&mut memory, &prompt, "code");
+        }
+
+        let sent: Vec<Message> = window.compressed(&memory, 2_000);
+        assert_eq!(sent.len(), 6);
+        assert!(estimate_messages_tokens(&sent) <= 2_000);
+        assert!(sent[0].content.contains("chars omitted"));
+        assert_eq!(sent[4].content, memory[4].content);
+    }
+}
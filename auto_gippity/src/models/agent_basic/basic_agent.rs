@@ -2,23 +2,105 @@
 use crate::models::general::llm::Message;
 // 引入BasicTraits特征，定义了代理基本行为。
 use crate::models::agent_basic::basic_traits::BasicTraits;
+// 引入MemoryWindow，限制记忆不超过模型上下文。
+use crate::models::agent_basic::agent_memory::MemoryWindow;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::event_bus::{publish, AgentEvent};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // 定义AgentState枚举，表示代理的不同状态。
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum AgentState{
   Discovery,  // 发现状态
   Working,    // 工作状态
   UnitTesting,// 单元测试状态
-  Finished    // 完成状态
+  Reviewing,  // 审查状态：测试已通过，确认结果
+  Blocked(String), // 阻塞状态：等待审批，附带原因
+  Failed(String),  // 失败状态（终止），附带原因
+  Finished    // 完成状态（终止）
 }
 
+impl AgentState {
+    // Finished 和 Failed 是终止状态，不能再离开
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, AgentState::Finished | AgentState::Failed(_))
+    }
+
+    // 状态转换表：任何未终止的状态都可以进入 Blocked 或 Failed。
+    // Blocked 只能回到进入 Blocked 之前的状态（由 BasicAgent::update_state 按转换历史检查）或 Failed
+    pub fn can_transition_to(&self, next: &AgentState) -> bool {
+        use AgentState::*;
+        match (self, next) {
+            (Finished | Failed(_), _) => false,
+            (_, Failed(_)) => true,
+            (Blocked(_), _) => false,
+            (_, Blocked(_)) => true,
+            // 在发现阶段就完成的代理：架构师不需要检查外部 URL 时、单步的代理
+            (Discovery, Working | UnitTesting | Reviewing | Finished) => true,
+            // 没有测试阶段的代理：工具代理、外部代理、管理代理
+            (Working, UnitTesting | Reviewing | Finished) => true,
+            (UnitTesting, Working | Reviewing | Finished) => true,
+            (Reviewing, Working | Finished) => true,
+            _ => false,
+        }
+    }
+}
+
+// 一次状态转换及其发生时间（Unix 毫秒）
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateTransition {
+    pub from: AgentState,
+    pub to: AgentState,
+    pub at_ms: u128,
+}
+
+// 转换表不允许的状态转换
+#[derive(Debug, Clone, PartialEq)]
+pub struct IllegalTransition {
+    pub position: String,
+    pub from: AgentState,
+    pub to: AgentState,
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: illegal state transition {:?} -> {:?}", self.position, self.from, self.to)
+    }
+}
+
+impl std::error::Error for IllegalTransition {}
+
 // 定义BasicAgent结构体，代表基本的代理。
 #[derive(Debug)]
 pub struct BasicAgent{
     pub objective: String, // 代理的目标
     pub position: String,  // 代理的位置或角色
     pub state: AgentState, // 代理当前的状态
-    pub memory: Vec<Message> // 代理的记忆，存放消息
+    pub memory: Vec<Message>, // 代理的记忆，存放消息
+    pub transitions: Vec<StateTransition>, // 代理的状态转换历史
+    pub model: Option<String> // 代理使用的模型，None时使用预设模型
+}
+
+impl BasicAgent {
+    // 停止运行：打印原因，代理进入 Failed 状态，错误原样返回（RunError 保持类型以便得到正确的退出码）
+    pub fn halt<E>(&mut self, error: E) -> Box<dyn std::error::Error>
+    where
+        E: fmt::Display + Into<Box<dyn std::error::Error>>,
+    {
+        let halt_msg: String = format!("Stopping: {}", error);
+        PrintCommand::Issue.print_agent_message(self.position.as_str(), halt_msg.as_str());
+        self.fail(error.to_string());
+        error.into()
+    }
+
+    // 阻塞的代理在进入 Blocked 之前的状态，审批结束后只能回到这个状态
+    pub fn state_before_block(&self) -> Option<&AgentState> {
+        match self.state {
+            AgentState::Blocked(_) => self.transitions.last().map(|transition| &transition.from),
+            _ => None,
+        }
+    }
 }
 
 // 为BasicAgent实现BasicTraits特征。
@@ -29,13 +111,44 @@ impl BasicTraits for BasicAgent{
             objective, 
             position, 
             state: AgentState::Discovery, // 初始化状态为Discovery
-            memory: Vec::from([]) // 初始化记忆为空
+            memory: Vec::from([]), // 初始化记忆为空
+            transitions: vec![], // 初始化转换历史为空
+            model: None // 使用预设模型
         }
     }
 
-    // 更新代理的状态。
-    fn update_state(&mut self, new_state: AgentState) {
-        self.state = new_state;
+    // 按转换表更新代理的状态并记录转换；状态不变时不记录，不允许的转换返回错误。
+    fn update_state(&mut self, new_state: AgentState) -> Result<(), IllegalTransition> {
+        if self.state == new_state {
+            return Ok(());
+        }
+        let resumes: bool = self.state_before_block() == Some(&new_state);
+        if !resumes && !self.state.can_transition_to(&new_state) {
+            return Err(IllegalTransition {
+                position: self.position.clone(),
+                from: self.state.clone(),
+                to: new_state,
+            });
+        }
+
+        let at_ms: u128 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        let from: AgentState = std::mem::replace(&mut self.state, new_state.clone());
+        publish(AgentEvent::StateChanged {
+            agent: self.position.clone(),
+            from: format!("{:?}", from),
+            to: format!("{:?}", new_state),
+        });
+        self.transitions.push(StateTransition { from, to: new_state, at_ms });
+        Ok(())
+    }
+    // 让代理失败；已经终止的代理保持原来的状态。
+    fn fail(&mut self, reason: String) {
+        if !self.state.is_terminal() {
+            self.update_state(AgentState::Failed(reason)).expect("Any running agent can fail");
+        }
     }
     // 获取代理的目标。
     fn get_objective(&self) -> &String {
@@ -53,5 +166,80 @@ impl BasicTraits for BasicAgent{
     fn get_memory(&self) -> &Vec<Message> {
         &self.memory
     }
+    // 获取代理的状态转换历史。
+    fn get_transitions(&self) -> &Vec<StateTransition> {
+        &self.transitions
+    }
+    // 记录一轮对话，记忆超出窗口时丢弃最旧的对话。
+    fn remember_turn(&mut self, prompt: &str, response: &str) {
+        MemoryWindow::default().record_turn(&mut self.memory, prompt, response);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::general::run_error::RunError;
+
+    #[test]
+    fn tests_state_transitions_are_validated_and_recorded() {
+        let mut agent: BasicAgent = BasicAgent::new("build".to_string(), "Backend Developer".to_string());
+        agent.update_state(AgentState::Working).unwrap();
+        agent.update_state(AgentState::Blocked("awaiting approval: BeforeBuild".to_string())).unwrap();
+        assert_eq!(agent.state_before_block(), Some(&AgentState::Working));
+        assert!(agent.update_state(AgentState::Finished).is_err());
+        assert!(agent.update_state(AgentState::UnitTesting).is_err());
+        agent.update_state(AgentState::Working).unwrap();
+        agent.update_state(AgentState::UnitTesting).unwrap();
+        agent.update_state(AgentState::UnitTesting).unwrap();
+
+        let illegal: IllegalTransition = agent.update_state(AgentState::Discovery).unwrap_err();
+        assert_eq!(illegal.from, AgentState::UnitTesting);
+        assert_eq!(agent.state, AgentState::UnitTesting);
+
+        agent.update_state(AgentState::Reviewing).unwrap();
+        agent.update_state(AgentState::Finished).unwrap();
+        assert!(agent.update_state(AgentState::Working).is_err());
+        agent.fail("too late".to_string());
+        assert_eq!(agent.state, AgentState::Finished);
 
-}
\ No newline at end of file
+        let path: Vec<AgentState> = agent.get_transitions().iter().map(|transition| transition.to.clone()).collect();
+        assert_eq!(path.len(), 6);
+        assert_eq!(path[5], AgentState::Finished);
+        assert!(agent.transitions.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+    }
+
+    #[test]
+    fn tests_failed_is_terminal() {
+        let mut agent: BasicAgent = BasicAgent::new("plan".to_string(), "Solutions Architect".to_string());
+        agent.fail("approval denied".to_string());
+        assert_eq!(agent.state, AgentState::Failed("approval denied".to_string()));
+        assert!(agent.state.is_terminal());
+        assert!(agent.update_state(AgentState::Finished).is_err());
+    }
+
+    #[test]
+    fn tests_halt_fails_the_agent_and_keeps_the_error_type() {
+        let mut agent: BasicAgent = BasicAgent::new("build".to_string(), "Backend Developer".to_string());
+        let error: Box<dyn std::error::Error> = agent.halt(RunError::BuildFailed("E0308".to_string()));
+        assert_eq!(error.downcast_ref::<RunError>(), Some(&RunError::BuildFailed("E0308".to_string())));
+        assert_eq!(agent.state, AgentState::Failed("build failed: E0308".to_string()));
+
+        let mut other: BasicAgent = BasicAgent::new("tools".to_string(), "Tool User".to_string());
+        assert_eq!(other.halt("no answer".to_string()).to_string(), "no answer");
+        assert_eq!(other.state, AgentState::Failed("no answer".to_string()));
+    }
+
+    #[test]
+    fn tests_blocked_agent_can_only_resume_or_fail() {
+        let mut agent: BasicAgent = BasicAgent::new("plan".to_string(), "Solutions Architect".to_string());
+        agent.update_state(AgentState::UnitTesting).unwrap();
+        agent.update_state(AgentState::Blocked("awaiting approval: BeforeExternalUrls".to_string())).unwrap();
+        for next in [AgentState::Discovery, AgentState::Working, AgentState::Reviewing, AgentState::Finished] {
+            assert!(agent.update_state(next).is_err());
+        }
+        agent.fail("approval denied".to_string());
+        assert_eq!(agent.state, AgentState::Failed("approval denied".to_string()));
+    }
+}
@@ -1,15 +1,19 @@
 // 引入AgentState枚举，定义了代理可能处于的状态。
-use crate::models::agent_basic::basic_agent::AgentState;
+use crate::models::agent_basic::basic_agent::{AgentState, IllegalTransition, StateTransition};
 // 引入Message结构体，代表代理可能存储或处理的消息。
 use crate::models::general::llm::Message;
 
-// 定义一个名为BasicTraits的trait。
+// 定义一个名为BasicTraits的trait。读取属性的方法是代理的公共接口，目前的代码直接访问字段。
+#[allow(dead_code)]
 pub trait BasicTraits {
     // 定义一个关联函数（类似于其他编程语言中的静态方法），用于创建并返回trait实现者的实例。
     fn new(objective: String, position: String) -> Self;
 
-    // 定义一个方法，用于更新实现此trait的实例的状态。
-    fn update_state(&mut self, new_state: AgentState);
+    // 定义一个方法，按状态转换表更新实现此trait的实例的状态，不允许的转换返回错误。
+    fn update_state(&mut self, new_state: AgentState) -> Result<(), IllegalTransition>;
+
+    // 定义一个方法，让实例进入Failed状态并记录原因。
+    fn fail(&mut self, reason: String);
 
     // 定义一个方法，返回实现此trait的实例的目标。
     fn get_objective(&self) -> &String;
@@ -22,4 +26,10 @@ pub trait BasicTraits {
 
     // 定义一个方法，返回与实现此trait的实例相关联的消息列表的引用。
     fn get_memory(&self) -> &Vec<Message>;
-}
\ No newline at end of file
+
+    // 定义一个方法，返回实现此trait的实例的状态转换历史。
+    fn get_transitions(&self) -> &Vec<StateTransition>;
+
+    // 定义一个方法，把一轮对话（提示和回应）写入记忆，并按窗口策略丢弃过旧的记忆。
+    fn remember_turn(&mut self, prompt: &str, response: &str);
+}
@@ -4,21 +4,30 @@
 
 // 引入所需的crate庫和模組
 use crate::ai_functions::aifunc_architect::{print_project_scope, print_site_urls};
+use crate::helpers::approvals::{ApprovalGate, Approvals};
 use crate::helpers::command_line::PrintCommand;
-use crate::helpers::general::{ai_task_request_decoded, check_status_code};
+use crate::helpers::general::ai_task_request_decoded_with_memory;
+use crate::helpers::url_checker::{UrlCheckReport, UrlChecker, UrlCheckerConfig};
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
 use crate::models::agent_basic::basic_traits::BasicTraits;
-use crate::models::agents::agent_traits::{FactSheet, ProjectScope, SpecialFunctions};
+use crate::helpers::url_policy::UrlPolicy;
+use crate::helpers::run_budget::check_run_budget;
+use crate::models::general::run_error::RunError;
+use crate::models::agents::agent_traits::{
+    AgentOptions, ApiSample, FactField, FactSheet, ProjectScope, SpecialFunctions, UrlExclusion,
+};
 
 use async_trait::async_trait;
-use reqwest::Client;
-use std::time::Duration;
 
+// 流水線配置中架構師接受的選項
+pub const ARCHITECT_OPTIONS: [&str; 1] = ["model"];
 
 // Solutions Architect  定義解決方案架構師結構體
 #[derive(Debug)]
 pub struct AgentSolutionArchitect {
     attributes: BasicAgent,
+    url_policy: UrlPolicy,
+    approvals: Approvals,
 }
 
 impl AgentSolutionArchitect {
@@ -29,40 +38,52 @@ impl AgentSolutionArchitect {
             position: "Solutions Architect".to_string(),
             state: AgentState::Discovery,
             memory: vec![],
+            transitions: vec![],
+            model: None,
         };
 
-        Self { attributes }
+        Self {
+            attributes,
+            url_policy: UrlPolicy::load_default(),
+            approvals: Approvals::load_default(),
+        }
+    }
+
+    // 按流水線配置建立：可以指定模型
+    pub fn with_options(options: &AgentOptions) -> Self {
+        let mut agent: Self = Self::new();
+        agent.attributes.model = options.model.clone();
+        agent
     }
 
     // Retrieve Project Scope 獲取项目范围的异步方法
-    async fn call_project_scope(&mut self, factsheet: &mut FactSheet) -> ProjectScope {
-        let msg_context: String = format!("{}", factsheet.project_description);
+    async fn call_project_scope(&mut self, factsheet: &mut FactSheet) -> Result<ProjectScope, RunError> {
+        let msg_context: String = factsheet.project_description.clone();
 
-        let ai_response: ProjectScope = ai_task_request_decoded::<ProjectScope>(
+        let ai_response: ProjectScope = ai_task_request_decoded_with_memory::<ProjectScope>(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_project_scope),
             print_project_scope,
         )
-        .await;
+        .await?;
 
-        factsheet.project_scope = Some(ai_response.clone());
-        self.attributes.update_state(AgentState::Finished);
-        return ai_response;
+        factsheet.project_scope = Some(ai_response);
+        Ok(ai_response)
     }
 
     // 检索项目中的外部URL的异步方法
-    async fn call_determine_external_urls(&mut self,factsheet: &mut FactSheet,msg_context: String,) {
-        let ai_response: Vec<String> = ai_task_request_decoded::<Vec<String>>(
+    async fn call_determine_external_urls(&mut self,factsheet: &mut FactSheet,msg_context: String,) -> Result<(), RunError> {
+        let ai_response: Vec<String> = ai_task_request_decoded_with_memory::<Vec<String>>(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_site_urls),
             print_site_urls,
         )
-        .await;
+        .await?;
 
         factsheet.external_urls = Some(ai_response);
-        self.attributes.state = AgentState::UnitTesting;
+        Ok(())
     }
 }
 
@@ -78,71 +99,121 @@ impl SpecialFunctions for AgentSolutionArchitect {
         &self.attributes
     }
 
+    // 读取项目描述，写入项目范围和外部URL的检查结果，并标记完成的集成任务
+    fn reads(&self) -> Vec<FactField> {
+        vec![FactField::ProjectDescription, FactField::ProjectTasks]
+    }
+
+    fn writes(&self) -> Vec<FactField> {
+        vec![
+            FactField::ProjectScope,
+            FactField::ExternalUrls,
+            FactField::ApiSamples,
+            FactField::ExcludedUrls,
+            FactField::UrlCheckReports,
+            FactField::ProjectTasks,
+        ]
+    }
+
     // 执行函数定义
     async fn execute(&mut self,factsheet: &mut FactSheet,) -> Result<(), Box<dyn std::error::Error>> {
 
         // !!! WARNING - BE CAREFUL OF INFINITATE LOOPS !!!
-        while self.attributes.state != AgentState::Finished {
+        while !self.attributes.state.is_terminal() {
+            check_run_budget().map_err(|exceeded| self.attributes.halt(exceeded))?;
+
             match self.attributes.state {
                 AgentState::Discovery => {
-                    let project_scope: ProjectScope = self.call_project_scope(factsheet).await;
+                    let project_scope: ProjectScope =
+                        self.call_project_scope(factsheet).await.map_err(|error| self.attributes.halt(error))?;
 
-                    // Confirm if external urls  判断是否需要外部URLs
+                    // Confirm if external urls  判断是否需要外部URLs，不需要时直接完成
                     if project_scope.is_external_urls_required {
-                        self.call_determine_external_urls(factsheet, factsheet.project_description.clone(),).await;
-                        self.attributes.state = AgentState::UnitTesting;
+                        self.call_determine_external_urls(factsheet, factsheet.project_description.clone(),)
+                            .await
+                            .map_err(|error| self.attributes.halt(error))?;
+                        self.attributes.update_state(AgentState::UnitTesting)?;
+                    } else {
+                        self.attributes.update_state(AgentState::Finished)?;
                     }
                 }
 
                 AgentState::UnitTesting => {
-                    let mut exclude_urls: Vec<String> = vec![];
-
-                    let client: Client = Client::builder()
-                        .timeout(Duration::from_secs(5))
-                        .build()
-                        .unwrap();
-
                     // Defining urls to check  定义要检查的urls
-                    let urls: &Vec<String> = factsheet
-                        .external_urls.as_ref().expect("No URL object on factsheet");
-
-                    // Find faulty urls  查找故障的urls
-                    for url in urls {
-                        let endpoint_str: String = format!("Testing URL Endpoint: {}", url);
-                        PrintCommand::UnitTest.print_agent_message(
-                            self.attributes.position.as_str(),endpoint_str.as_str(),);
-
-                        // Perform URL Test  执行URL测试
-                        match check_status_code(&client, url).await {
-                            Ok(status_code) => {
-                                if status_code != 200 {
-                                    exclude_urls.push(url.clone())
+                    let urls: Vec<String> = factsheet
+                        .external_urls.clone().expect("No URL object on factsheet");
+
+                    // Guard:: 訪問外部 URL 前必須通過審批關卡
+                    self.approvals
+                        .request_for(&mut self.attributes, ApprovalGate::BeforeExternalUrls, urls.join(", ").as_str(), factsheet)
+                        .map_err(|denied| self.attributes.halt(RunError::from(denied)))?;
+
+                    let checking_str: String = format!("Testing {} URL Endpoint(s) concurrently", urls.len());
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(), checking_str.as_str());
+
+                    // Check urls  在 URL 策略约束下并发检查所有URL，被阻止的URL不会发出任何请求
+                    let checker: UrlChecker = UrlChecker::new(self.url_policy.clone(), UrlCheckerConfig::default());
+                    let reports: Vec<UrlCheckReport> = checker.check_all(&urls).await;
+
+                    // Find faulty urls  查找故障的urls，并保留可用URL的回应样本
+                    let mut excluded_urls: Vec<UrlExclusion> = vec![];
+                    let mut api_samples: Vec<ApiSample> = vec![];
+                    for report in &reports {
+                        match report.failure_reason() {
+                            Some(reason) => {
+                                let issue_str: String = format!("Excluding URL {}: {}", report.url, reason);
+                                PrintCommand::Issue.print_agent_message(
+                                    self.attributes.position.as_str(), issue_str.as_str());
+                                excluded_urls.push(UrlExclusion { url: report.url.clone(), reason });
+                            }
+                            None => {
+                                if !report.is_json {
+                                    let warn_str: String = format!(
+                                        "URL {} is reachable but does not return JSON ({})",
+                                        report.url,
+                                        report.content_type.as_deref().unwrap_or("no content type")
+                                    );
+                                    PrintCommand::Issue.print_agent_message(
+                                        self.attributes.position.as_str(), warn_str.as_str());
+                                }
+                                if let Some(sample) = &report.sample {
+                                    api_samples.push(sample.clone());
                                 }
                             }
-                            Err(e) => println!("Error checking {}: {}", url, e),
                         }
                     }
+                    factsheet.url_check_reports = Some(reports);
+
+                    // Store response samples  保存回应样本，供后端代理编写反序列化结构体
+                    factsheet.api_samples = Some(api_samples);
 
-                    // Exclude any faulty urls 排除任何故障的urls
-                    if exclude_urls.len() > 0 {
+                    // Exclude any faulty urls 排除任何故障的urls，并记录排除原因
+                    if !excluded_urls.is_empty() {
                         let new_urls: Vec<String> = factsheet
                             .external_urls
                             .as_ref()
                             .unwrap()
                             .iter()
-                            .filter(|url| !exclude_urls.contains(&url))
+                            .filter(|url| !excluded_urls.iter().any(|excluded| &excluded.url == *url))
                             .cloned()
                             .collect();
                         factsheet.external_urls = Some(new_urls);
                     }
+                    factsheet.excluded_urls = Some(excluded_urls);
+
+                    // 可用的外部URL覆盖的集成任务标记为完成
+                    let urls: Vec<String> = factsheet.external_urls.clone().unwrap_or_default();
+                    let position: String = self.attributes.position.clone();
+                    factsheet.complete_tasks(&position, |task| task.integrated_by(&urls));
 
                     // Confirm done 确认任务完成
-                    self.attributes.state = AgentState::Finished;
+                    self.attributes.update_state(AgentState::Finished)?;
                 }
 
                 // Default to Finished state 默认设置状态为完成
                 _ => {
-                    self.attributes.state = AgentState::Finished;
+                    self.attributes.update_state(AgentState::Finished)?;
                 }
             }
         }
@@ -154,6 +225,8 @@ impl SpecialFunctions for AgentSolutionArchitect {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::approvals::{ApprovalPolicy, GateDecision};
+    use crate::helpers::stub_server::{spawn_stub_server, StubResponse};
 
     // 异步测试Solutions Architect的功能
     #[tokio::test]
@@ -166,14 +239,103 @@ mod tests {
       external_urls: None,
       backend_code: None,
       api_endpoint_schema: None,
+      ..FactSheet::default()
     };
 
         agent.execute(&mut factsheet).await.expect("Unable to execute Solutions Architect Agent");
-        assert!(factsheet.project_scope != None);
+        assert!(factsheet.project_scope.is_some());
         assert!(factsheet.external_urls.is_some());
 
         dbg!(factsheet);
     }
+
+    // 預設策略下，回環和元數據地址在發出請求前就被排除並記錄原因
+    #[tokio::test]
+    async fn tests_solution_architect_blocks_internal_urls() {
+        let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new();
+        agent.attributes.state = AgentState::UnitTesting;
+        agent.url_policy = UrlPolicy::default();
+        agent.approvals = Approvals::new(ApprovalPolicy::uniform(GateDecision::AutoApprove));
+        let mut factsheet: FactSheet = FactSheet {
+            project_description: "Read cloud metadata".to_string(),
+            project_scope: None,
+            external_urls: Some(vec![
+                "http://127.0.0.1:9/admin".to_string(),
+                "http://169.254.169.254/latest/meta-data".to_string(),
+                "file:///etc/passwd".to_string(),
+            ]),
+            ..FactSheet::default()
+        };
+
+        agent.execute(&mut factsheet).await.unwrap();
+
+        assert_eq!(factsheet.external_urls, Some(vec![]));
+        let excluded: Vec<UrlExclusion> = factsheet.excluded_urls.unwrap();
+        assert_eq!(excluded.len(), 3);
+        assert!(excluded[0].reason.contains("Loopback"));
+        assert!(excluded[1].reason.contains("Metadata"));
+        assert!(excluded[2].reason.contains("scheme"));
+    }
+
+    // 在 UnitTesting 階段對本地桩伺服器採樣，不需要調用 LLM
+    #[tokio::test]
+    async fn tests_solution_architect_samples_external_urls() {
+        let base_url: String = spawn_stub_server(vec![(
+            "/api/rates",
+            StubResponse::json(200, r#"{"base":"USD","rates":{"EUR":0.9}}"#),
+        )])
+        .await;
+        let good_url: String = format!("{}/api/rates", base_url);
+        let bad_url: String = format!("{}/api/missing", base_url);
+
+        let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new();
+        agent.attributes.state = AgentState::UnitTesting;
+        agent.url_policy.allow_loopback = true;
+        agent.approvals = Approvals::new(ApprovalPolicy::uniform(GateDecision::AutoApprove));
+        let mut factsheet: FactSheet = FactSheet {
+            project_description: "Show currency exchange rates".to_string(),
+            project_scope: None,
+            external_urls: Some(vec![good_url.clone(), bad_url.clone()]),
+            ..FactSheet::default()
+        };
+
+        agent.execute(&mut factsheet).await.unwrap();
+
+        assert_eq!(factsheet.external_urls, Some(vec![good_url.clone()]));
+        let excluded: Vec<UrlExclusion> = factsheet.excluded_urls.unwrap();
+        assert_eq!(excluded.len(), 1);
+        assert_eq!(excluded[0].url, bad_url);
+        assert!(excluded[0].reason.contains("404"));
+        let samples: Vec<ApiSample> = factsheet.api_samples.unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].url, good_url);
+        assert_eq!(
+            samples[0].json_shape,
+            Some(serde_json::json!({"base": "string", "rates": {"EUR": "number"}}))
+        );
+    }
+
+    // 外部 URL 關卡被拒絕時，代理以 Failed 狀態結束並記錄原因，不發出任何請求
+    #[tokio::test]
+    async fn tests_solution_architect_stops_when_urls_denied() {
+        let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new();
+        agent.attributes.state = AgentState::UnitTesting;
+        agent.approvals = Approvals::new(ApprovalPolicy::uniform(GateDecision::AutoDeny));
+        let mut factsheet: FactSheet = FactSheet {
+            project_description: "Show weather".to_string(),
+            external_urls: Some(vec!["https://api.example.com/weather".to_string()]),
+            ..FactSheet::default()
+        };
+
+        let err: Box<dyn std::error::Error> = agent.execute(&mut factsheet).await.unwrap_err();
+        match err.downcast_ref::<RunError>() {
+            Some(RunError::PolicyDenied(denied)) => assert_eq!(denied.gate, ApprovalGate::BeforeExternalUrls),
+            other => panic!("expected a policy denial, got {:?}", other),
+        }
+        assert!(matches!(agent.attributes.state, AgentState::Failed(ref reason) if reason.contains("denied")));
+        assert!(factsheet.url_check_reports.is_none());
+        assert!(!factsheet.approvals.unwrap()[0].approved);
+    }
 }
 
 
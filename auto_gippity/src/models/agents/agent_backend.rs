@@ -1,37 +1,231 @@
 
 // 引入模組中的幾個函數，用來處理 AI 任務的請求
 use crate::ai_functions::aifunc_backend::{
-    print_backend_webserver_code, print_fixed_code, print_improved_webserver_code,
-    print_rest_api_endpoints,
+    print_backend_project_manifest, print_code_critique, print_critique_revision_patches, print_fixed_code_patches,
+    print_fixed_items, print_improved_webserver_patches, print_rest_api_endpoints,
 };
 use crate::helpers::general::{
-    check_status_code, read_code_template_contents, read_exec_main_contents, save_api_endpoints,
-    save_backend_code, WEB_SERVER_PROJECT_PATH,
+    check_status_code, read_code_template_contents, save_api_endpoints, WEB_SERVER_PROJECT_PATH,
 };
+use crate::helpers::approvals::{ApprovalDenied, ApprovalGate, Approvals};
+use crate::helpers::code_chunks::{
+    error_lines, items_for_lines, split_code_items, splice_items, strip_code_fences, CodeItem,
+};
+use std::collections::BTreeMap;
+use crate::helpers::build_cache::BuildCache;
+use crate::helpers::candidates::{prepare_workspace, race_candidates, CandidateConfig};
+use crate::helpers::event_bus::{publish, AgentEvent};
+use crate::helpers::dependency_policy::{missing_crates, patch_cargo_toml, DependencyPolicy, DependencyResolution};
+use crate::helpers::code_templates::{select_code_template, CodeTemplate};
+use crate::helpers::patching::{apply_patches, parse_patches, PatchEdit, PatchOutcome, RejectedEdit};
+use crate::helpers::project_files::{file_header, FileManifest, ProjectFile, FILE_MARKER};
+use crate::helpers::project_history::{count_build_errors, ProjectHistory, DEFAULT_HISTORY_DIR};
+use crate::helpers::prompt_budget::{error_terms, FittedPrompt, PromptBudgeter, PromptSection, SectionKind};
+use crate::helpers::run_budget::{check_run_budget, remaining_tokens};
+use crate::models::general::llm::Message;
+use crate::models::general::run_error::RunError;
 
 // 引入輔助函式,用於檢查HTTP狀態碼、讀取程式碼模板內容、讀取主要執行程式的內容、儲存 API 端點和後端程式碼,以及定義網頁伺服器專案的路徑。
-use crate::helpers::command_line::{confirm_safe_code, PrintCommand};
-use crate::helpers::general::ai_task_request;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::critique::{Critique, CritiqueConfig, CritiqueRecord};
+use crate::helpers::general::{ai_task_request_decoded_with_memory, ai_task_request_with_memory};
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
-use crate::models::agents::agent_traits::{FactSheet, RouteObject, SpecialFunctions};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_traits::{AgentOptions, FactField, FactSheet, RouteObject, SpecialFunctions};
 
 
 // 引入一些外部的 crate 和標準函式庫模組,包括用於非同步程式設計的 async_trait,
 // 用於發送 HTTP 請求的 reqwest,用於執行命令列程式的 process 模組,以及用於處理時間的 Duration 和 tokio 的 time 模組。
 use async_trait::async_trait;
 use reqwest::Client;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::time::Duration;
 use tokio::time;
 
 
 
+// 一個候選實現的生成任務：帶著代理記憶的副本生成完整項目，在自己的工作區構建、運行並測試端點
+#[derive(Debug, Clone)]
+struct CandidateJob {
+    msg_context: String,
+    objective: String,
+    position: String,
+    memory: Vec<Message>,
+    model: Option<String>,
+    dependency_policy: DependencyPolicy,
+    vendor_dir: PathBuf,
+    workspace: PathBuf,
+    target_dir: PathBuf,
+    port: u16,
+}
+
+// 通過構建和端點測試的候選
+#[derive(Debug)]
+struct CandidateWinner {
+    manifest: FileManifest,
+    api_endpoints_str: String,
+    api_endpoints: Vec<RouteObject>,
+    check_endpoints: Vec<RouteObject>,
+    memory: Vec<Message>,
+}
+
+impl CandidateJob {
+    async fn run(self) -> Result<CandidateWinner, String> {
+        check_run_budget().map_err(|e| e.to_string())?;
+        let mut attributes: BasicAgent = BasicAgent {
+            objective: self.objective.clone(),
+            position: self.position.clone(),
+            state: AgentState::Working,
+            memory: self.memory.clone(),
+            transitions: vec![],
+            model: self.model.clone(),
+        };
+        let ai_response: String = ai_task_request_with_memory(
+            self.msg_context.clone(),
+            &mut attributes,
+            get_function_string!(print_backend_project_manifest),
+            print_backend_project_manifest,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // 清單不合法或依賴不可用的候選直接淘汰
+        let manifest: FileManifest = FileManifest::parse(&ai_response)?;
+        let problems: Vec<String> = manifest.validate();
+        if !problems.is_empty() {
+            return Err(problems.join("; "));
+        }
+        let resolution: DependencyResolution = self.dependency_policy.resolve(&manifest.dependencies);
+        if !resolution.rejected.is_empty() {
+            return Err(resolution.rejected.join("; "));
+        }
+        prepare_workspace(Path::new(WEB_SERVER_PROJECT_PATH), &self.workspace)?;
+        manifest.write_to(&self.workspace, None)?;
+        patch_cargo_toml(&self.workspace.join("Cargo.toml"), &resolution.accepted)?;
+
+        // 子進程在候選被取消時隨之結束
+        publish(AgentEvent::BuildStarted {
+            agent: self.position.clone(),
+            project_dir: self.workspace.display().to_string(),
+        });
+        let build: std::process::Output = tokio::process::Command::new("cargo")
+            .args(self.dependency_policy.build_args(&self.vendor_dir))
+            .env("CARGO_TARGET_DIR", &self.target_dir)
+            .current_dir(&self.workspace)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("failed to run cargo build: {}", e))?;
+        let build_errors: String = String::from_utf8_lossy(&build.stderr).to_string();
+        let error_count: usize = if build.status.success() { 0 } else { count_build_errors(&build_errors) };
+        publish(AgentEvent::BuildFinished {
+            agent: self.position.clone(),
+            success: build.status.success(),
+            error_count,
+        });
+        if !build.status.success() {
+            return Err(format!("build failed with {} error(s)", error_count));
+        }
+
+        check_run_budget().map_err(|e| e.to_string())?;
+        let msg_context: String = PromptBudgeter::default()
+            .fit(vec![PromptSection::new("CODE_INPUT", manifest.to_bundle(), SectionKind::Code, 1)], &[]);
+        let api_endpoints_str: String = ai_task_request_with_memory(
+            msg_context,
+            &mut attributes,
+            get_function_string!(print_rest_api_endpoints),
+            print_rest_api_endpoints,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let api_endpoints: Vec<RouteObject> = serde_json::from_str(api_endpoints_str.as_str())
+            .map_err(|e| format!("failed to decode API endpoints: {}", e))?;
+        let check_endpoints: Vec<RouteObject> = api_endpoints
+            .iter()
+            .filter(|route_object| route_object.method == "get" && route_object.is_route_dynamic == "false")
+            .cloned()
+            .collect();
+
+        let mut server: tokio::process::Child = tokio::process::Command::new("cargo")
+            .args(self.dependency_policy.run_args(&self.vendor_dir))
+            .env("CARGO_TARGET_DIR", &self.target_dir)
+            .env("PORT", self.port.to_string())
+            .current_dir(&self.workspace)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to run server: {}", e))?;
+        time::sleep(Duration::from_secs(5)).await;
+
+        let client: Client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let mut failed_endpoints: Vec<String> = vec![];
+        for endpoint in &check_endpoints {
+            let url: String = format!("http://localhost:{}{}", self.port, endpoint.route);
+            let status: Option<u16> = match check_status_code(&client, &url).await {
+                Ok(200) => Some(200),
+                Ok(status_code) => {
+                    failed_endpoints.push(format!("{} ({})", endpoint.route, status_code));
+                    Some(status_code)
+                }
+                Err(e) => {
+                    failed_endpoints.push(format!("{} ({})", endpoint.route, e));
+                    None
+                }
+            };
+            publish(AgentEvent::EndpointTested {
+                agent: self.position.clone(),
+                route: endpoint.route.clone(),
+                status,
+                passed: status == Some(200),
+            });
+        }
+        let _ = server.kill().await;
+        if !failed_endpoints.is_empty() {
+            return Err(format!("endpoint tests failed: {}", failed_endpoints.join(", ")));
+        }
+
+        Ok(CandidateWinner {
+            manifest,
+            api_endpoints_str,
+            api_endpoints,
+            check_endpoints,
+            memory: attributes.memory,
+        })
+    }
+}
+
+
+// 流水線配置中後端開發接受的選項
+pub const BACKEND_OPTIONS: [&str; 2] = ["model", "max_fix_rounds"];
+
+// 構建失敗後最多修復幾輪，超過時返回 BuildFailed
+const DEFAULT_MAX_FIX_ROUNDS: u8 = 2;
+
+// 除修復輪次外允許的額外構建次數（補上缺少的依賴、回滾後重新構建），超過總次數時返回 BuildFailed
+const EXTRA_BUILD_ATTEMPTS: u16 = 4;
+
 // 定義 AgentBackendDeveloper 的結構體。包含 agent 的基本屬性、程式錯誤訊息和錯誤計數。
 #[derive(Debug)]
 pub struct AgentBackendDeveloper {
     attributes: BasicAgent,
     bug_errors: Option<String>,
     bug_count: u8,
+    build_attempts: u16,
+    approvals: Approvals,
+    patch_feedback: Option<String>,
+    history: Option<ProjectHistory>,
+    last_error_count: Option<usize>,
+    dependency_policy: DependencyPolicy,
+    build_cache: BuildCache,
+    candidates: CandidateConfig,
+    max_fix_rounds: u8,
+    api_endpoints: Vec<RouteObject>,
+    critique: CritiqueConfig,
 }
 
 
@@ -44,91 +238,707 @@ impl AgentBackendDeveloper {
             position: "Backend Developer".to_string(),
             state: AgentState::Discovery,
             memory: vec![],
+            transitions: vec![],
+            model: None,
         };
 
         Self {
             attributes,
             bug_errors: None,
             bug_count: 0,
+            build_attempts: 0,
+            approvals: Approvals::load_default(),
+            patch_feedback: None,
+            history: None,
+            last_error_count: None,
+            dependency_policy: DependencyPolicy::load_default(),
+            build_cache: BuildCache::load_default(),
+            candidates: CandidateConfig::load_default(),
+            max_fix_rounds: DEFAULT_MAX_FIX_ROUNDS,
+            api_endpoints: vec![],
+            critique: CritiqueConfig::load_default(),
+        }
+    }
+
+    // 按流水線配置建立：可以指定模型和最多修復幾輪
+    pub fn with_options(options: &AgentOptions) -> Self {
+        let mut agent: Self = Self::new();
+        agent.attributes.model = options.model.clone();
+        agent.max_fix_rounds = options.max_fix_rounds.unwrap_or(DEFAULT_MAX_FIX_ROUNDS);
+        agent
+    }
+
+    // 按允許列表檢查聲明的依賴並寫入工作區的 Cargo.toml；返回發回給模型的問題
+    fn apply_dependencies(&self, requested: &BTreeMap<String, String>) -> Vec<String> {
+        let resolution: DependencyResolution = self.dependency_policy.resolve(requested);
+        let mut problems: Vec<String> = resolution.rejected.clone();
+
+        let cargo_toml: PathBuf = Path::new(WEB_SERVER_PROJECT_PATH).join("Cargo.toml");
+        match patch_cargo_toml(&cargo_toml, &resolution.accepted) {
+            Ok(added) if !added.is_empty() => {
+                let added_msg: String = format!("Added dependencies: {}", added.join(", "));
+                PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), added_msg.as_str());
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(e),
+        }
+
+        if !resolution.rejected.is_empty() {
+            problems.push(format!("ONLY THESE CRATES ARE AVAILABLE: {}", self.dependency_policy.describe()));
+        }
+        problems
+    }
+
+    // 構建因缺少 crate 失敗時，把允許列表中的 crate 加入 Cargo.toml；有新增依賴時返回 true
+    fn add_missing_crates(&mut self, factsheet: &mut FactSheet, build_errors: &str) -> bool {
+        let local_modules: Vec<String> = factsheet
+            .project_files
+            .as_ref()
+            .map(FileManifest::module_names)
+            .unwrap_or_default();
+        let missing: Vec<String> = missing_crates(build_errors, &local_modules);
+        if missing.is_empty() {
+            return false;
+        }
+
+        let requested: BTreeMap<String, String> = missing
+            .iter()
+            .map(|name| (name.clone(), "*".to_string()))
+            .collect();
+        let resolution: DependencyResolution = self.dependency_policy.resolve(&requested);
+        let cargo_toml: PathBuf = Path::new(WEB_SERVER_PROJECT_PATH).join("Cargo.toml");
+        let added: Vec<String> = patch_cargo_toml(&cargo_toml, &resolution.accepted).unwrap_or_else(|e| {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+            vec![]
+        });
+
+        if !resolution.rejected.is_empty() {
+            let unavailable: String = format!(
+                "{}\nONLY THESE CRATES ARE AVAILABLE: {}",
+                resolution.rejected.join("\n"),
+                self.dependency_policy.describe()
+            );
+            self.patch_feedback = Some(match self.patch_feedback.take() {
+                Some(feedback) => format!("{}\n\n{}", feedback, unavailable),
+                None => unavailable,
+            });
+        }
+        if added.is_empty() {
+            return false;
+        }
+
+        let added_msg: String = format!("Build was missing crates, added: {}", added.join(", "));
+        PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), added_msg.as_str());
+        if let Some(manifest) = factsheet.project_files.as_mut() {
+            for name in &added {
+                manifest.dependencies.insert(name.clone(), self.dependency_policy.crates[name].version.clone());
+            }
+        }
+        self.record_step(format!("add missing crates {}", added.join(", ")).as_str());
+        true
+    }
+
+    // 打開生成項目的 git 歷史；git 不可用時只打印警告，不影響生成
+    fn open_history(&mut self) {
+        match ProjectHistory::open(Path::new(WEB_SERVER_PROJECT_PATH), Path::new(DEFAULT_HISTORY_DIR)) {
+            Ok(history) => self.history = Some(history),
+            Err(e) => {
+                let err_msg: String = format!("Project history disabled: {}", e);
+                PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), err_msg.as_str());
+            }
+        }
+    }
+
+    // 在生成項目的 git 歷史中提交一個步驟
+    fn record_step(&self, reason: &str) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let state: String = format!("{:?}", self.attributes.state);
+        if let Err(e) = history.commit_step(self.attributes.position.as_str(), state.as_str(), reason) {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+        }
+    }
+
+    // 通過測試的項目提供的端點和數據實體：對應的任務標記為完成
+    fn complete_project_tasks(&self, factsheet: &mut FactSheet) {
+        let code: String = factsheet.backend_code.clone().unwrap_or_default();
+        let completed: Vec<usize> = factsheet.complete_tasks(&self.attributes.position, |task| {
+            self.api_endpoints.iter().any(|route| task.matches_route(route)) || task.defined_in(&code)
+        });
+        if !completed.is_empty() {
+            let ids: Vec<String> = completed.iter().map(|id| id.to_string()).collect();
+            let done_msg: String = format!("Completed task(s) {}", ids.join(", "));
+            PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), done_msg.as_str());
+        }
+    }
+
+    // 從工作區重新讀取項目文件（回滾之後）
+    fn reload_project_files(&self, factsheet: &mut FactSheet) {
+        match FileManifest::load_from(Path::new(WEB_SERVER_PROJECT_PATH)) {
+            Ok(mut manifest) => {
+                if let Some(previous) = &factsheet.project_files {
+                    manifest.dependencies = previous.dependencies.clone();
+                }
+                factsheet.backend_code = Some(manifest.to_bundle());
+                factsheet.project_files = Some(manifest);
+            }
+            Err(e) => PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str()),
+        }
+    }
+
+    // 整個構建-修復過程最多構建幾次：每輪修復之後可能還有一次回滾後的重新構建
+    fn max_build_attempts(&self) -> u16 {
+        2 * (u16::from(self.max_fix_rounds) + 1) + EXTRA_BUILD_ATTEMPTS
+    }
+
+    // 修復後錯誤比同一輪修復中上一次更多時回滾到最後一次構建成功的版本；成功回滾時返回 true
+    fn rollback_if_worse(&mut self, factsheet: &mut FactSheet, error_count: usize) -> bool {
+        let previous: Option<usize> = self.last_error_count.replace(error_count);
+        let Some(previous) = previous.filter(|&previous| error_count > previous) else {
+            return false;
+        };
+        let Some(history) = self.history.as_ref().filter(|history| history.last_green().is_some()) else {
+            return false;
+        };
+
+        let reason: String = format!("{} build errors after fix, {} before", error_count, previous);
+        match history.rollback_to_last_green(self.attributes.position.as_str(), reason.as_str()) {
+            Ok(_) => {
+                let rollback_msg: String = format!("Rolled back to last green build: {}", reason);
+                PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), rollback_msg.as_str());
+                self.reload_project_files(factsheet);
+                self.bug_errors = None;
+                self.last_error_count = None;
+                true
+            }
+            Err(e) => {
+                PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+                false
+            }
+        }
+    }
+
+    // 保存一次代碼修改：檢查文件清單，通過覆蓋文件的審批後寫入工作區，記錄到補丁歷史；
+    // 被拒絕的補丁和清單問題留給下一次提示
+    fn save_code_change(
+        &mut self,
+        factsheet: &mut FactSheet,
+        step: &str,
+        outcome: PatchOutcome,
+    ) -> Result<(), ApprovalDenied> {
+        let dependencies: BTreeMap<String, String> = factsheet
+            .project_files
+            .as_ref()
+            .map(|manifest| manifest.dependencies.clone())
+            .unwrap_or_default();
+        let manifest: FileManifest = FileManifest::from_bundle(&outcome.code, dependencies);
+        self.save_manifest(factsheet, step, manifest, outcome)
+    }
+
+    fn save_manifest(
+        &mut self,
+        factsheet: &mut FactSheet,
+        step: &str,
+        manifest: FileManifest,
+        outcome: PatchOutcome,
+    ) -> Result<(), ApprovalDenied> {
+        // 清單不合法時整個修改被拒絕：不寫入任何文件，代碼保持不變，問題發回給模型
+        let problems: Vec<String> = manifest.validate();
+        if !problems.is_empty() {
+            for problem in &problems {
+                PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), problem.as_str());
+            }
+            let previous_code: String = factsheet.backend_code.clone().unwrap_or_default();
+            let reason: String = format!("FILE MANIFEST PROBLEMS, no files were written:\n{}", problems.join("\n"));
+            let rejected: PatchOutcome = PatchOutcome::rejected_reply(&previous_code, &outcome.code, reason.as_str());
+            self.patch_feedback = rejected.rejection_report();
+            factsheet.patch_history.get_or_insert_with(Vec::new).push(rejected.record(step));
+            return Ok(());
+        }
+
+        let file_list: String = format!("{} in {}", manifest.paths().join(", "), WEB_SERVER_PROJECT_PATH);
+        self.approvals
+            .request_for(&mut self.attributes, ApprovalGate::BeforeOverwriteFiles, file_list.as_str(), factsheet)?;
+
+        if !outcome.rejected.is_empty() {
+            let reject_msg: String = format!(
+                "{} patch(es) applied, {} rejected",
+                outcome.applied.len(),
+                outcome.rejected.len()
+            );
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), reject_msg.as_str());
+        }
+        let mut feedback: Vec<String> = outcome.rejection_report().into_iter().collect();
+
+        if let Err(e) = manifest.write_to(Path::new(WEB_SERVER_PROJECT_PATH), factsheet.project_files.as_ref()) {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+            feedback.push(format!("FILES NOT WRITTEN: {}", e));
+        }
+        let dependency_problems: Vec<String> = self.apply_dependencies(&manifest.dependencies);
+        for problem in &dependency_problems {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), problem.as_str());
+        }
+        if !dependency_problems.is_empty() {
+            feedback.push(format!("DEPENDENCY PROBLEMS:\n{}", dependency_problems.join("\n")));
+        }
+        self.patch_feedback = if feedback.is_empty() { None } else { Some(feedback.join("\n\n")) };
+
+        factsheet.patch_history.get_or_insert_with(Vec::new).push(outcome.record(step));
+        factsheet.backend_code = Some(outcome.code);
+        factsheet.project_files = Some(manifest);
+        Ok(())
+    }
+
+    // 模型返回的補丁應用到當前代碼；沒有補丁時只有提示中的代碼是完整的，才把回應當作完整文件，
+    // 否則模型看到的是聚焦或裁剪過的代碼，整個回應被拒絕
+    fn patch_outcome(code: &str, ai_response: &str, code_reduced: bool) -> PatchOutcome {
+        let edits: Vec<PatchEdit> = parse_patches(ai_response);
+        if !edits.is_empty() {
+            return apply_patches(code, &edits);
+        }
+        if code_reduced {
+            return PatchOutcome::rejected_reply(
+                code,
+                ai_response,
+                "no SEARCH/REPLACE blocks; only part of the code was shown, so the reply cannot replace the files",
+            );
+        }
+
+        // 回應中的每個文件都能解析為 Rust 時才當作完整的新代碼，否則作為被拒絕的補丁發回給模型
+        let new_code: String = strip_code_fences(ai_response);
+        let rewrite: FileManifest = FileManifest::from_bundle(&new_code, BTreeMap::new());
+        let unparsable: Vec<String> = rewrite
+            .files
+            .iter()
+            .filter_map(|file| syn::parse_file(&file.contents).err().map(|e| format!("{}: {}", file.path, e)))
+            .collect();
+        if new_code.trim().is_empty() {
+            return PatchOutcome::rejected_reply(code, ai_response, "empty reply without SEARCH/REPLACE blocks");
+        }
+        if !unparsable.is_empty() {
+            let reason: String = format!(
+                "no SEARCH/REPLACE blocks, and the reply is not complete Rust code ({})",
+                unparsable.join("; ")
+            );
+            return PatchOutcome::rejected_reply(code, ai_response, reason.as_str());
         }
+        PatchOutcome::full_rewrite(code, &new_code)
+    }
+
+    // 上一次被拒絕的補丁，作為提示的一部分發回給模型
+    fn take_patch_feedback(&mut self) -> Option<PromptSection> {
+        self.patch_feedback
+            .take()
+            .map(|report| PromptSection::new("REJECTED_PATCHES", report, SectionKind::Text, 2))
     }
 
 
 // 這裡定義了一些 AgentBackendDeveloper 的非同步方法,用於生成初始後端程式碼、改進後端程式碼、修復程式碼錯誤,
-// 以及提取 REST API 端點。這些方法使用 ai_task_request 函式向 AI 發出任務請求,並根據回應進行相應的操作。
-    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) {
-        let code_template_str: String = read_code_template_contents();
+// 以及提取 REST API 端點。這些方法使用 ai_task_request_with_memory 函式帶著代理記憶向 AI 發出任務請求,並根據回應進行相應的操作。
+    // 生成初始代碼的提示：模板、項目描述、外部 API 樣本和可用的 crate
+    fn initial_prompt_sections(&self, factsheet: &FactSheet) -> Vec<PromptSection> {
+        // 按架構師給出的項目範圍挑選模板
+        let template: &CodeTemplate = select_code_template(factsheet.project_scope.as_ref());
+        let template_msg: String = format!("Using code template '{}': {}", template.name, template.description);
+        PrintCommand::AICall.print_agent_message(self.attributes.position.as_str(), template_msg.as_str());
+
+        let code_template_str: String = read_code_template_contents(factsheet.project_scope.as_ref());
 
         // Concatenate Instruction
-        let msg_context: String = format!(
-            "CODE TEMPLATE: {} \n PROJECT_DESCRIPTION: {} \n",
-            code_template_str, factsheet.project_description
+        let mut sections: Vec<PromptSection> = vec![
+            PromptSection::new("CODE TEMPLATE", code_template_str, SectionKind::Verbatim, 1),
+            PromptSection::new("PROJECT_DESCRIPTION", factsheet.project_description.clone(), SectionKind::Text, 3),
+        ];
+
+        // 附上外部 API 的回應樣本，讓模型寫出正確的反序列化結構體
+        if let Some(api_samples) = &factsheet.api_samples {
+            let samples_str: String =
+                serde_json::to_string(api_samples).expect("Failed to serialize API samples");
+            sections.push(PromptSection::new("EXTERNAL_API_SAMPLES", samples_str, SectionKind::Text, 0));
+        }
+        sections.push(PromptSection::new("ALLOWED_CRATES", self.dependency_policy.describe(), SectionKind::Text, 2));
+        sections
+    }
+
+    // 並行生成多個候選實現，採用第一個構建成功並通過端點測試的候選；
+    // 沒有候選勝出時返回 false，由順序流程接手
+    async fn call_candidate_backend_code(&mut self, factsheet: &mut FactSheet) -> Result<bool, RunError> {
+        let count: usize = self.candidates.candidate_count(remaining_tokens());
+        if count == 0 {
+            PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                "Token budget too low for candidate generation, building sequentially",
+            );
+            return Ok(false);
+        }
+
+        let workspace_detail: String = format!("{} candidate(s) in {}", count, self.candidates.workspace(0).display());
+        self.approvals
+            .request_for(&mut self.attributes, ApprovalGate::BeforeBuild, workspace_detail.as_str(), factsheet)?;
+        self.approvals
+            .request_for(&mut self.attributes, ApprovalGate::BeforeRun, workspace_detail.as_str(), factsheet)?;
+
+        let mut sections: Vec<PromptSection> = self.initial_prompt_sections(factsheet);
+        sections.push(PromptSection::new(
+            "INSTRUCTION",
+            "THE SERVER MUST LISTEN ON THE PORT GIVEN BY THE PORT ENVIRONMENT VARIABLE, DEFAULTING TO 8080.".to_string(),
+            SectionKind::Text,
+            9,
+        ));
+        let job: CandidateJob = CandidateJob {
+            msg_context: PromptBudgeter::default().fit(sections, &[]),
+            objective: self.attributes.objective.clone(),
+            position: self.attributes.position.clone(),
+            memory: self.attributes.memory.clone(),
+            model: self.attributes.model.clone(),
+            dependency_policy: self.dependency_policy.clone(),
+            vendor_dir: self.build_cache.vendor_dir(),
+            workspace: PathBuf::new(),
+            target_dir: PathBuf::new(),
+            port: 0,
+        };
+
+        let race_msg: String = format!(
+            "Generating {} candidates, {} at a time...",
+            count,
+            self.candidates.concurrency
         );
+        PrintCommand::AICall.print_agent_message(self.attributes.position.as_str(), race_msg.as_str());
+
+        // 每個候選使用自己的工作區、target 目錄和端口，互不阻塞
+        let candidates: &CandidateConfig = &self.candidates;
+        let target_root: PathBuf = self.build_cache.target_dir();
+        let result: Result<(usize, CandidateWinner), Vec<String>> = race_candidates(count, candidates.concurrency, |index| {
+            let candidate: CandidateJob = CandidateJob {
+                workspace: candidates.workspace(index),
+                target_dir: target_root.join(format!("candidate-{}", index + 1)),
+                port: candidates.port(index),
+                ..job.clone()
+            };
+            candidate.run()
+        })
+        .await;
 
-        let ai_response: String = ai_task_request(
+        let (index, winner) = match result {
+            Ok(won) => won,
+            Err(failures) => {
+                for failure in &failures {
+                    PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), failure.as_str());
+                }
+                PrintCommand::Issue.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "No candidate passed, building sequentially",
+                );
+                return Ok(false);
+            }
+        };
+
+        let winner_msg: String = format!("Candidate {} built and passed endpoint tests", index + 1);
+        PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), winner_msg.as_str());
+        self.attributes.memory = winner.memory;
+
+        let previous_code: String = factsheet.backend_code.clone().unwrap_or_default();
+        let outcome: PatchOutcome = PatchOutcome::full_rewrite(&previous_code, &winner.manifest.to_bundle());
+        let step: String = format!("candidate {}", index + 1);
+        self.save_manifest(factsheet, step.as_str(), winner.manifest, outcome)?;
+        if let Some(history) = &self.history {
+            if let Err(e) = history.mark_green() {
+                PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+            }
+        }
+
+        factsheet.api_endpoint_schema = Some(winner.check_endpoints);
+        self.api_endpoints = winner.api_endpoints;
+        save_api_endpoints(&winner.api_endpoints_str);
+        Ok(true)
+    }
+
+    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) -> Result<(), RunError> {
+        let sections: Vec<PromptSection> = self.initial_prompt_sections(factsheet);
+        let msg_context: String = PromptBudgeter::default().fit(sections, &[]);
+
+        let ai_response: String = ai_task_request_with_memory(
             msg_context,
-            &self.attributes.position,
-            get_function_string!(print_backend_webserver_code),
-            print_backend_webserver_code,
+            &mut self.attributes,
+            get_function_string!(print_backend_project_manifest),
+            print_backend_project_manifest,
         )
-        .await;
+        .await?;
+
+        // 模型沒有返回合法的清單時，把回應當作單文件的 main.rs
+        let manifest: FileManifest = FileManifest::parse(&ai_response).unwrap_or_else(|e| {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+            FileManifest::single_file(&ai_response)
+        });
+        let previous_code: String = factsheet.backend_code.clone().unwrap_or_default();
+        let outcome: PatchOutcome = PatchOutcome::full_rewrite(&previous_code, &manifest.to_bundle());
+        Ok(self.save_manifest(factsheet, "initial", manifest, outcome)?)
+    }
+
+    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) -> Result<(), RunError> {
+        let backend_code: String = factsheet.backend_code.clone().unwrap_or_default();
+        let mut sections: Vec<PromptSection> = vec![
+            PromptSection::new("CODE TEMPLATE", backend_code.clone(), SectionKind::Code, 1),
+            PromptSection::new("PROJECT_DESCRIPTION", factsheet.to_prompt_json(), SectionKind::Text, 0),
+        ];
+        sections.extend(self.take_patch_feedback());
+        let prompt: FittedPrompt = PromptBudgeter::default().fit_prompt(sections, &[]);
+
+        let ai_response: String = ai_task_request_with_memory(
+            prompt.text,
+            &mut self.attributes,
+            get_function_string!(print_improved_webserver_patches),
+            print_improved_webserver_patches,
+        )
+        .await?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        let outcome: PatchOutcome = Self::patch_outcome(&backend_code, &ai_response, prompt.code_reduced);
+        self.save_code_change(factsheet, "improve", outcome)?;
+
+        if self.critique.is_active() {
+            self.call_critique_rounds(factsheet).await?;
+        }
+        Ok(())
     }
 
-    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) {
-        let msg_context: String = format!(
-            "CODE TEMPLATE: {:?} \n PROJECT_DESCRIPTION: {:?} \n",
-            factsheet.backend_code, factsheet
-        );
+    // 自我評審：模型對照需求和 API 端點評審代碼，分數低於門檻時按缺口修改，最多配置的輪數；
+    // 每輪評審記錄在 FactSheet 上，修改以同名的步驟記錄在補丁歷史中
+    async fn call_critique_rounds(&mut self, factsheet: &mut FactSheet) -> Result<(), RunError> {
+        let threshold: u8 = self.critique.threshold;
+        for round in 1..=self.critique.max_rounds {
+            if check_run_budget().is_err() {
+                return Ok(());
+            }
+            let step: String = format!("critique round {}", round);
+            let backend_code: String = factsheet.backend_code.clone().unwrap_or_default();
+            let api_schema: String = match &factsheet.api_endpoint_schema {
+                Some(routes) => serde_json::to_string(routes).expect("Failed to serialize API endpoints"),
+                None => "Not extracted yet, use the routes declared in the code".to_string(),
+            };
+            let sections: Vec<PromptSection> = vec![
+                PromptSection::new("CODE_TEMPLATE", backend_code.clone(), SectionKind::Code, 1),
+                PromptSection::new("PROJECT_DESCRIPTION", factsheet.to_prompt_json(), SectionKind::Text, 0),
+                PromptSection::new("API_SCHEMA", api_schema, SectionKind::Text, 2),
+            ];
+            let msg_context: String = PromptBudgeter::default().fit(sections, &[]);
+
+            // 無法解析的評審視為沒有評審，停止評審輪次
+            let critique: Critique = match ai_task_request_decoded_with_memory::<Critique>(
+                msg_context,
+                &mut self.attributes,
+                get_function_string!(print_code_critique),
+                print_code_critique,
+            )
+            .await
+            {
+                Ok(critique) => critique,
+                Err(RunError::MalformedReply(reason)) => {
+                    let skip_msg: String = format!("{}: could not read the critique, stopping: {}", step, reason);
+                    PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), skip_msg.as_str());
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            let critique_msg: String = format!(
+                "{}: score {}/10 (threshold {}), {} gap(s)",
+                step,
+                critique.score,
+                threshold,
+                critique.gaps.len()
+            );
+            PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), critique_msg.as_str());
+
+            let accepted: bool = critique.is_accepted(threshold);
+            let critiques: &mut Vec<CritiqueRecord> = factsheet.critiques.get_or_insert_with(Vec::new);
+            critiques.push(critique.record(&step, threshold, false));
+            let record_index: usize = critiques.len() - 1;
+            if accepted {
+                return Ok(());
+            }
+
+            let mut sections: Vec<PromptSection> = vec![
+                PromptSection::new("CODE_TEMPLATE", backend_code.clone(), SectionKind::Code, 1),
+                PromptSection::new("PROJECT_DESCRIPTION", factsheet.to_prompt_json(), SectionKind::Text, 0),
+                PromptSection::new("CRITIQUE", critique.to_prompt(), SectionKind::Text, 0),
+            ];
+            sections.extend(self.take_patch_feedback());
+            let prompt: FittedPrompt = PromptBudgeter::default().fit_prompt(sections, &[]);
+
+            let ai_response: String = ai_task_request_with_memory(
+                prompt.text,
+                &mut self.attributes,
+                get_function_string!(print_critique_revision_patches),
+                print_critique_revision_patches,
+            )
+            .await?;
+            let outcome: PatchOutcome = Self::patch_outcome(&backend_code, &ai_response, prompt.code_reduced);
+            self.save_code_change(factsheet, step.as_str(), outcome)?;
+            // 只有代碼真的被修改並保存時才算修改過；補丁全部被拒絕或清單不合法時代碼保持不變
+            let revised: bool = factsheet.backend_code.as_deref().unwrap_or_default() != backend_code;
+            if let Some(record) = factsheet.critiques.as_mut().and_then(|critiques| critiques.get_mut(record_index)) {
+                record.revised = revised;
+            }
+            self.record_step(step.as_str());
+        }
+        Ok(())
+    }
 
-        let ai_response: String = ai_task_request(
+    // 只讓模型重寫編譯錯誤所在的條目，再拼回各自的文件；改動不屬於項目的文件作為被拒絕的補丁報告。
+    // 錯誤無法定位到條目或拼接失敗時返回 None
+    async fn call_fix_code_items(&mut self, code: &str, bug_errors: &str) -> Result<Option<PatchOutcome>, RunError> {
+        let mut manifest: FileManifest = FileManifest::from_bundle(code, BTreeMap::new());
+        let mut broken_files: Vec<(String, Vec<CodeItem>)> = vec![];
+        let mut item_count: usize = 0;
+        for file in &manifest.files {
+            let Ok(items) = split_code_items(&file.contents) else {
+                return Ok(None);
+            };
+            item_count += items.len();
+            let affected: Vec<CodeItem> = items_for_lines(&items, &error_lines(bug_errors, &file.path));
+            if !affected.is_empty() {
+                broken_files.push((file.path.clone(), affected));
+            }
+        }
+        if broken_files.is_empty() {
+            return Ok(None);
+        }
+
+        let keys: Vec<String> = broken_files
+            .iter()
+            .flat_map(|(path, items)| items.iter().map(move |item| format!("{} in {}", item.key, path)))
+            .collect();
+        let fix_msg: String = format!("Rewriting {} of {} items: {}", keys.len(), item_count, keys.join(", "));
+        PrintCommand::AICall.print_agent_message(self.attributes.position.as_str(), fix_msg.as_str());
+
+        let broken_items: String = broken_files
+            .iter()
+            .map(|(path, items)| {
+                let sources: Vec<&str> = items.iter().map(|item| item.source.as_str()).collect();
+                format!("{}\n{}", file_header(path), sources.join("\n\n"))
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        let sections: Vec<PromptSection> = vec![
+            PromptSection::new("BROKEN_ITEMS", broken_items, SectionKind::Code, 1),
+            PromptSection::new("ERROR_BUGS", bug_errors.to_string(), SectionKind::Text, 0),
+        ];
+        let msg_context: String = PromptBudgeter::default().fit(sections, &[]);
+
+        let ai_response: String = ai_task_request_with_memory(
             msg_context,
-            &self.attributes.position,
-            get_function_string!(print_improved_webserver_code),
-            print_improved_webserver_code,
+            &mut self.attributes,
+            get_function_string!(print_fixed_items),
+            print_fixed_items,
         )
-        .await;
+        .await?;
+
+        // 回應沒有文件標記時，只有一個文件出錯才能確定改的是哪個文件
+        let ai_response: String = strip_code_fences(&ai_response);
+        let edited: Vec<ProjectFile> = if ai_response.contains(FILE_MARKER) {
+            FileManifest::from_bundle(&ai_response, BTreeMap::new()).files
+        } else if broken_files.len() == 1 {
+            vec![ProjectFile {
+                path: broken_files[0].0.clone(),
+                contents: ai_response,
+            }]
+        } else {
+            return Ok(None);
+        };
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        let project_paths: Vec<String> = manifest.paths();
+        let mut rejected: Vec<RejectedEdit> = vec![];
+        for edited_file in edited {
+            let Some(file) = manifest.files.iter_mut().find(|file| file.path == edited_file.path) else {
+                rejected.push(RejectedEdit {
+                    search: edited_file.contents,
+                    reason: format!(
+                        "{} is not a file of the project ({}), only the broken items may be edited",
+                        edited_file.path,
+                        project_paths.join(", ")
+                    ),
+                });
+                continue;
+            };
+            let targets: &[CodeItem] = broken_files
+                .iter()
+                .find(|(path, _)| *path == file.path)
+                .map(|(_, items)| items.as_slice())
+                .unwrap_or(&[]);
+            match splice_items(&file.contents, targets, &edited_file.contents) {
+                Ok(fixed_contents) => file.contents = fixed_contents,
+                Err(e) => {
+                    let err_msg: String =
+                        format!("Could not splice fixed items into {} ({}), resending the whole project", file.path, e);
+                    PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), err_msg.as_str());
+                    return Ok(None);
+                }
+            }
+        }
+        let fixed_code: String = manifest.to_bundle();
+        let mut outcome: PatchOutcome = if fixed_code == code {
+            PatchOutcome {
+                code: code.to_string(),
+                applied: vec![],
+                rejected: vec![],
+            }
+        } else {
+            PatchOutcome::full_rewrite(code, &fixed_code)
+        };
+        outcome.rejected = rejected;
+        Ok(Some(outcome))
     }
 
-    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) {
-        let msg_context: String = format!(
-            "BROKEN_CODE: {:?} \n ERROR_BUGS: {:?} \n
-      THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
-            factsheet.backend_code, self.bug_errors
-        );
+    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) -> Result<(), RunError> {
+        let bug_errors: String = self.bug_errors.clone().unwrap_or_default();
+        let backend_code: String = factsheet.backend_code.clone().unwrap_or_default();
 
-        let ai_response: String = ai_task_request(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_fixed_code),
-            print_fixed_code,
+        if let Some(outcome) = self.call_fix_code_items(&backend_code, &bug_errors).await? {
+            return Ok(self.save_code_change(factsheet, "fix_items", outcome)?);
+        }
+
+        // 只發送與編譯錯誤相關的函數，錯誤輸出過長時保留開頭和結尾
+        let relevant_terms: Vec<String> = error_terms(&bug_errors);
+        let mut sections: Vec<PromptSection> = vec![
+            PromptSection::new("BROKEN_CODE", backend_code.clone(), SectionKind::Code, 1),
+            PromptSection::new("ERROR_BUGS", bug_errors, SectionKind::Text, 0),
+            PromptSection::new(
+                "INSTRUCTION",
+                "THIS FUNCTION ONLY OUTPUTS SEARCH/REPLACE EDIT BLOCKS. JUST OUTPUT THE EDITS.".to_string(),
+                SectionKind::Text,
+                9,
+            ),
+        ];
+        sections.extend(self.take_patch_feedback());
+        let prompt: FittedPrompt = PromptBudgeter::default().fit_prompt(sections, &relevant_terms);
+
+        let ai_response: String = ai_task_request_with_memory(
+            prompt.text,
+            &mut self.attributes,
+            get_function_string!(print_fixed_code_patches),
+            print_fixed_code_patches,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        let outcome: PatchOutcome = Self::patch_outcome(&backend_code, &ai_response, prompt.code_reduced);
+        Ok(self.save_code_change(factsheet, "fix", outcome)?)
     }
 
-    async fn call_extract_rest_api_endpoints(&self) -> String {
-        let backend_code: String = read_exec_main_contents();
+    // 從項目的所有文件中提取 REST API 端點
+    async fn call_extract_rest_api_endpoints(&mut self, factsheet: &FactSheet) -> Result<String, RunError> {
+        let backend_code: String = factsheet.backend_code.clone().unwrap_or_default();
 
         // Structure message context
-        let msg_context: String = format!("CODE_INPUT: {}", backend_code);
+        let msg_context: String = PromptBudgeter::default()
+            .fit(vec![PromptSection::new("CODE_INPUT", backend_code, SectionKind::Code, 1)], &[]);
 
-        let ai_response: String = ai_task_request(
+        ai_task_request_with_memory(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_rest_api_endpoints),
             print_rest_api_endpoints,
         )
-        .await;
-
-        ai_response
+        .await
     }
 }
 
@@ -141,7 +951,7 @@ impl AgentBackendDeveloper {
 // 定義了 get_attributes_from_agent 方法來獲取 agent 的基本屬性,以及 execute 方法來執行 agent 的主要任務。
 
 // 在 execute 方法中,while迴圈會根據 agent 的狀態執行不同的操作,包括:
-// Discovery: 呼叫 call_initial_backend_code 生成初始後端程式碼。
+// Discovery: 啟用候選生成時先並行生成多個候選,有候選通過構建和端點測試即完成;否則呼叫 call_initial_backend_code 生成初始後端程式碼。
 // Working: 根據錯誤計數呼叫 call_improved_backend_code 或 call_fix_code_bugs 來改進程式碼或修復錯誤。
 // UnitTesting: 執行單元測試,包括確認程式碼安全性,構建專案,提取 API 端點,並測試端點的可訪問性。
 
@@ -152,42 +962,86 @@ impl SpecialFunctions for AgentBackendDeveloper {
         &self.attributes
     }
 
+    // 讀取架構師的結果,寫入項目代碼、端點、補丁歷史和評審記錄,並標記完成的任務
+    fn reads(&self) -> Vec<FactField> {
+        vec![
+            FactField::ProjectDescription,
+            FactField::ProjectScope,
+            FactField::ExternalUrls,
+            FactField::ApiSamples,
+            FactField::ExcludedUrls,
+            FactField::ProjectTasks,
+        ]
+    }
+
+    fn writes(&self) -> Vec<FactField> {
+        vec![
+            FactField::BackendCode,
+            FactField::ApiEndpointSchema,
+            FactField::PatchHistory,
+            FactField::ProjectFiles,
+            FactField::ProjectTasks,
+            FactField::Critiques,
+        ]
+    }
+
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        while self.attributes.state != AgentState::Finished {
+        while !self.attributes.state.is_terminal() {
+            check_run_budget().map_err(|exceeded| self.attributes.halt(exceeded))?;
+
             match &self.attributes.state {
                 AgentState::Discovery => {
-                    self.call_initial_backend_code(factsheet).await;
-                    self.attributes.state = AgentState::Working;
+                    self.open_history();
+                    let vendor_dir: PathBuf = self.build_cache.vendor_dir();
+                    if self.dependency_policy.offline && !self.dependency_policy.builds_offline(&vendor_dir) {
+                        let online_msg: String = format!(
+                            "Offline builds need {}, run `auto_gippity vendor-setup`; building online",
+                            vendor_dir.display()
+                        );
+                        PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), online_msg.as_str());
+                    }
+                    if self.candidates.enabled {
+                        let won: bool =
+                            self.call_candidate_backend_code(factsheet).await.map_err(|error| self.attributes.halt(error))?;
+                        if won {
+                            self.attributes.update_state(AgentState::Reviewing)?;
+                            continue;
+                        }
+                    }
+                    self.call_initial_backend_code(factsheet).await.map_err(|error| self.attributes.halt(error))?;
+                    self.record_step("initial code from template");
+                    self.attributes.update_state(AgentState::Working)?;
                     continue;
                 }
 
                 AgentState::Working => {
-                    if self.bug_count == 0 {
-                        self.call_improved_backend_code(factsheet).await;
+                    let result: Result<(), RunError> = if self.bug_count == 0 {
+                        self.call_improved_backend_code(factsheet).await
                     } else {
-                        self.call_fix_code_bugs(factsheet).await;
-                    }
-                    self.attributes.state = AgentState::UnitTesting;
+                        self.call_fix_code_bugs(factsheet).await
+                    };
+                    result.map_err(|error| self.attributes.halt(error))?;
+                    let reason: String = if self.bug_count == 0 {
+                        "improve code".to_string()
+                    } else {
+                        format!("fix attempt {} for build errors", self.bug_count)
+                    };
+                    self.record_step(reason.as_str());
+                    self.attributes.update_state(AgentState::UnitTesting)?;
                     continue;
                 }
 
 
-               // 如果在單元測試過程中發現太多錯誤,程式會 panic 並退出。如果一切正常,agent 的狀態會變為 Finished,並返回 Ok(())。
+               // 如果在單元測試過程中發現太多錯誤,返回 BuildFailed;端點測試失敗時返回 TestFailed。如果一切正常,agent 的狀態會變為 Reviewing,記錄結果後變為 Finished,並返回 Ok(())。
                 AgentState::UnitTesting => {
-                    // Guard:: ENSURE AI SAFETY
-                    PrintCommand::UnitTest.print_agent_message(
-                        self.attributes.position.as_str(),
-                        "Backend Code Unit Testing: Requesting user input",
-                    );
-
-                    let is_safe_code: bool = confirm_safe_code();
-
-                    if !is_safe_code {
-                        panic!("Better go work on some AI alignment instead...")
-                    }
+                    // Guard:: ENSURE AI SAFETY  構建前必須通過審批關卡
+                    let build_detail: String = format!("cargo build in {}", WEB_SERVER_PROJECT_PATH);
+                    self.approvals
+                        .request_for(&mut self.attributes, ApprovalGate::BeforeBuild, build_detail.as_str(), factsheet)
+                        .map_err(|denied| self.attributes.halt(RunError::from(denied)))?;
 
                     // Build and Test Code
                     PrintCommand::UnitTest.print_agent_message(
@@ -195,52 +1049,93 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         "Backend Code Unit Testing: building project...",
                     );
 
+                    // 回滾和補依賴都會重新構建，總次數有上限，避免來回循環
+                    self.build_attempts += 1;
+                    if self.build_attempts > self.max_build_attempts() {
+                        let build_errors: String = format!(
+                            "stopped after {} build attempts\n{}",
+                            self.max_build_attempts(),
+                            self.bug_errors.clone().unwrap_or_default()
+                        );
+                        return Err(self.attributes.halt(RunError::BuildFailed(build_errors)));
+                    }
+
                     // Build Code
+                    publish(AgentEvent::BuildStarted {
+                        agent: self.attributes.position.clone(),
+                        project_dir: WEB_SERVER_PROJECT_PATH.to_string(),
+                    });
                     let build_backend_server: std::process::Output = Command::new("cargo")
-                        .arg("build")
+                        .args(self.dependency_policy.build_args(&self.build_cache.vendor_dir()))
+                        .env("CARGO_TARGET_DIR", self.build_cache.target_dir())
                         .current_dir(WEB_SERVER_PROJECT_PATH)
                         .stdout(Stdio::piped())
                         .stderr(Stdio::piped())
                         .output()
-                        .expect("Failed to build backend application");
+                        .map_err(|e| self.attributes.halt(RunError::BuildFailed(format!("could not run cargo: {}", e))))?;
+
+                    let build_errors: String = String::from_utf8_lossy(&build_backend_server.stderr).to_string();
+                    publish(AgentEvent::BuildFinished {
+                        agent: self.attributes.position.clone(),
+                        success: build_backend_server.status.success(),
+                        error_count: count_build_errors(&build_errors),
+                    });
 
                     // Determine if build errors
                     if build_backend_server.status.success() {
+                        // 構建成功結束了這一輪修復，下一輪的錯誤數不與之比較
                         self.bug_count = 0;
+                        self.last_error_count = None;
+                        if let Some(history) = &self.history {
+                            if let Err(e) = history.mark_green() {
+                                PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), e.as_str());
+                            }
+                        }
                         PrintCommand::UnitTest.print_agent_message(
                             self.attributes.position.as_str(),
                             "Backend Code Unit Testing: Test server build successful...",
                         );
                     } else {
-                        let error_arr: Vec<u8> = build_backend_server.stderr;
-                        let error_str: String = String::from_utf8(error_arr).unwrap();
+                        let error_str: String = build_errors;
+
+                        // 缺少的是允許列表中的 crate 時直接補上依賴並重新構建，不進入修復循環
+                        if self.add_missing_crates(factsheet, &error_str) {
+                            continue;
+                        }
 
                         // Update error stats
+                        let error_count: usize = count_build_errors(&error_str);
                         self.bug_count += 1;
                         self.bug_errors = Some(error_str);
 
                         // Exit if too many bugs
-                        if self.bug_count > 2 {
+                        if self.bug_count > self.max_fix_rounds {
                             PrintCommand::Issue.print_agent_message(
                                 self.attributes.position.as_str(),
                                 "Backend Code Unit Testing: Too many bugs found in code",
                             );
-                            panic!("Error: Too many bugs")
+                            let build_errors: String = self.bug_errors.clone().unwrap_or_default();
+                            return Err(self.attributes.halt(RunError::BuildFailed(build_errors)));
+                        }
+
+                        // 修復讓情況變得更糟時，回滾到最後一次構建成功的版本並重新測試
+                        if self.rollback_if_worse(factsheet, error_count) {
+                            continue;
                         }
 
                         // Pass back for rework
-                        self.attributes.state = AgentState::Working;
+                        self.attributes.update_state(AgentState::Working)?;
                         continue;
                     }
 
 
                     // Extract API Endpoints
-                    let api_endpoints_str: String = self.call_extract_rest_api_endpoints().await;
+                    let api_endpoints_str: String =
+                        self.call_extract_rest_api_endpoints(factsheet).await.map_err(|error| self.attributes.halt(error))?;
 
                     // Convert API Endpoints into Values
-                    let api_endpoints: Vec<RouteObject> =
-                        serde_json::from_str(api_endpoints_str.as_str())
-                            .expect("Failed to decode API Endpoints");
+                    let api_endpoints: Vec<RouteObject> = serde_json::from_str(api_endpoints_str.as_str())
+                        .map_err(|e| self.attributes.halt(RunError::MalformedReply(format!("API endpoints: {}", e))))?;
 
                     // Define endpoints to check
                     let check_endpoints: Vec<RouteObject> = api_endpoints
@@ -253,6 +1148,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
 
                     // Store API Endpoints
                     factsheet.api_endpoint_schema = Some(check_endpoints.clone());
+                    self.api_endpoints = api_endpoints;
 
                     // Run backend application
                     PrintCommand::UnitTest.print_agent_message(
@@ -260,14 +1156,27 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         "Backend Code Unit Testing: Starting web server...",
                     );
 
+                    // Guard:: 運行生成的伺服器前必須通過審批關卡
+                    let run_detail: String = format!("cargo run in {}", WEB_SERVER_PROJECT_PATH);
+                    self.approvals
+                        .request_for(&mut self.attributes, ApprovalGate::BeforeRun, run_detail.as_str(), factsheet)
+                        .map_err(|denied| self.attributes.halt(RunError::from(denied)))?;
+
+                    // Create client with timout
+                    let client: Client = Client::builder()
+                        .timeout(Duration::from_secs(5))
+                        .build()
+                        .map_err(|e| self.attributes.halt(RunError::TestFailed(format!("could not create HTTP client: {}", e))))?;
+
                     // Execute running server
                     let mut run_backend_server: std::process::Child = Command::new("cargo")
-                        .arg("run")
+                        .args(self.dependency_policy.run_args(&self.build_cache.vendor_dir()))
+                        .env("CARGO_TARGET_DIR", self.build_cache.target_dir())
                         .current_dir(WEB_SERVER_PROJECT_PATH)
                         .stdout(Stdio::piped())
                         .stderr(Stdio::piped())
                         .spawn()
-                        .expect("Failed to run backend application");
+                        .map_err(|e| self.attributes.halt(RunError::TestFailed(format!("could not start the web server: {}", e))))?;
 
                     // Let user know testing on server will take place soon
                     PrintCommand::UnitTest.print_agent_message(
@@ -279,6 +1188,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                     time::sleep(seconds_sleep).await;
 
                     // Check status code
+                    let mut failed_endpoints: Vec<String> = vec![];
                     for endpoint in check_endpoints {
                         // Confirm url testing
                         let testing_msg: String =
@@ -288,17 +1198,19 @@ impl SpecialFunctions for AgentBackendDeveloper {
                             testing_msg.as_str(),
                         );
 
-                        // Create client with timout
-                        let client: Client = Client::builder()
-                            .timeout(Duration::from_secs(5))
-                            .build()
-                            .unwrap();
-
                         // Test url
                         let url: String = format!("http://localhost:8080{}", endpoint.route);
-                        match check_status_code(&client, &url).await {
+                        let status_result: Result<u16, reqwest::Error> = check_status_code(&client, &url).await;
+                        publish(AgentEvent::EndpointTested {
+                            agent: self.attributes.position.clone(),
+                            route: endpoint.route.clone(),
+                            status: status_result.as_ref().ok().copied(),
+                            passed: matches!(status_result, Ok(200)),
+                        });
+                        match status_result {
                             Ok(status_code) => {
                                 if status_code != 200 {
+                                    failed_endpoints.push(format!("{} ({})", endpoint.route, status_code));
                                     let err_msg: String = format!(
                                         "WARNING: Failed to call backend url endpoint {}",
                                         endpoint.route
@@ -310,10 +1222,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                                 }
                             }
                             Err(e) => {
-                                // kill $(lsof -t -i:8080)
-                                run_backend_server
-                                    .kill()
-                                    .expect("Failed to kill backend web server");
+                                failed_endpoints.push(format!("{} ({})", endpoint.route, e));
                                 let err_msg: String = format!("Error checking backend {}", e);
                                 PrintCommand::Issue.print_agent_message(
                                     self.attributes.position.as_str(),
@@ -330,14 +1239,31 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         "Backend testing complete...",
                     );
 
-                    run_backend_server
-                        .kill()
-                        .expect("Failed to kill backend web server on completion");
+                    // 伺服器可能已經自行退出，停止失敗時不再處理；只在測試結束後停止一次
+                    let _ = run_backend_server.kill();
+                    let _ = run_backend_server.wait();
+
+                    if !failed_endpoints.is_empty() {
+                        let failed: String = failed_endpoints.join(", ");
+                        return Err(self.attributes.halt(RunError::TestFailed(failed)));
+                    }
+
+                    self.attributes.update_state(AgentState::Reviewing)?;
+                }
 
-                    self.attributes.state = AgentState::Finished;
+                // 構建和端點測試都已通過：記錄結果後完成
+                AgentState::Reviewing => {
+                    self.record_step("passed build and endpoint tests");
+                    self.complete_project_tasks(factsheet);
+                    self.attributes.update_state(AgentState::Finished)?;
                 }
 
-                _ => {}
+                // 審批結束時代理已經回到原來的狀態或失敗，循環中不應出現其他狀態
+                other => {
+                    let unexpected: String = format!("unexpected state {:?} in the backend loop", other);
+                    self.attributes.fail(unexpected.clone());
+                    return Err(unexpected.into());
+                }
             }
         }
         Ok(())
@@ -350,6 +1276,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::patching::PatchRecord;
 
     #[tokio::test]
     async fn tests_backend_developer() {
@@ -378,4 +1305,47 @@ mod tests {
             .await
             .expect("Failed to execute Backend Developer agent");
     }
+
+    // 提示中的代碼被聚焦或裁剪過時，沒有補丁的回應不能替換整個文件
+    #[test]
+    fn tests_reply_without_patches_needs_the_full_code() {
+        let code: &str = "fn main() {\n    println!(\"hi\");\n}\n";
+        let reply: &str = "fn main() { /* unchanged */ }\n";
+
+        let reduced: PatchOutcome = AgentBackendDeveloper::patch_outcome(code, reply, true);
+        assert_eq!(reduced.code, code);
+        assert!(reduced.applied.is_empty());
+        assert!(reduced.rejection_report().unwrap().contains("only part of the code was shown"));
+
+        let full: PatchOutcome = AgentBackendDeveloper::patch_outcome(code, reply, false);
+        assert_eq!(full.code, reply.trim_end());
+
+        let prose: &str = "The code looks fine, I would keep it as it is.";
+        let rejected: PatchOutcome = AgentBackendDeveloper::patch_outcome(code, prose, false);
+        assert_eq!(rejected.code, code);
+        assert!(rejected.applied.is_empty());
+        assert!(rejected.rejection_report().unwrap().contains("not complete Rust code"));
+    }
+
+    // 清單不合法的修改被整個拒絕：代碼不變，問題發回給模型，補丁歷史中記錄為被拒絕
+    #[test]
+    fn tests_invalid_manifest_is_not_saved() {
+        let code: String = "// === FILE: src/main.rs ===\nfn main() {}\n".to_string();
+        let mut factsheet: FactSheet = FactSheet {
+            backend_code: Some(code.clone()),
+            ..FactSheet::default()
+        };
+        let mut agent: AgentBackendDeveloper = AgentBackendDeveloper::new();
+
+        let new_code: &str = "// === FILE: src/main.rs ===\nmod models;\n\nfn main() {}\n";
+        let outcome: PatchOutcome = PatchOutcome::full_rewrite(&code, new_code);
+        agent.save_code_change(&mut factsheet, "fix", outcome).unwrap();
+
+        assert_eq!(factsheet.backend_code, Some(code));
+        assert!(factsheet.project_files.is_none());
+        let record: &PatchRecord = &factsheet.patch_history.as_ref().unwrap()[0];
+        assert!(record.applied.is_empty());
+        assert!(record.rejected[0].reason.contains("module src/models is declared but src/models.rs is missing"));
+        assert!(agent.take_patch_feedback().is_some());
+    }
 }
@@ -0,0 +1,225 @@
+// 使用工具的代理：按流水線配置給出的目標，讓模型通過工具（讀寫文件、運行 cargo、HTTP GET）在項目工作目錄中行動，
+// 直到模型給出最終回答。代理能用哪些工具由配置中授予的權限決定，預設只能讀取文件。
+
+use crate::apis::call_request::LLM_MODEL;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::WEB_SERVER_PROJECT_PATH;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_traits::{AgentOptions, FactField, FactSheet, SpecialFunctions};
+use crate::models::general::llm::Message;
+use crate::models::tools::builtin_tools::builtin_toolbox;
+use crate::models::tools::tool::{ToolBox, ToolContext, ToolPermission};
+use crate::models::tools::tool_loop::{run_tool_loop, ChatModel, OpenAiChat, DEFAULT_MAX_TOOL_ROUNDS};
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+// 流水線配置中使用工具的代理接受的選項
+pub const TOOL_USER_OPTIONS: [&str; 6] = ["model", "name", "goal", "permissions", "workspace", "max_tool_rounds"];
+
+// 沒有配置目標時的預設目標
+const DEFAULT_GOAL: &str = "Review the generated web server project and report any problems you find.";
+
+// 沒有配置權限時授予的權限
+const DEFAULT_PERMISSIONS: [ToolPermission; 1] = [ToolPermission::ReadFiles];
+
+#[derive(Debug)]
+pub struct AgentToolUser {
+    attributes: BasicAgent,
+    toolbox: ToolBox,
+    chat: Box<dyn ChatModel>,
+    max_rounds: usize,
+    answer: Option<String>,
+}
+
+impl AgentToolUser {
+    // 按流水線配置建立：目標、名稱、模型、權限、工作目錄和最多輪數
+    pub fn with_options(options: &AgentOptions) -> Self {
+        let position: String = options.name.clone().unwrap_or_else(|| "Tool User".to_string());
+        let attributes: BasicAgent = BasicAgent {
+            objective: options.goal.clone().unwrap_or_else(|| DEFAULT_GOAL.to_string()),
+            position: position.clone(),
+            state: AgentState::Discovery,
+            memory: vec![],
+            transitions: vec![],
+            model: options.model.clone(),
+        };
+
+        let workspace: PathBuf = PathBuf::from(options.workspace.as_deref().unwrap_or(WEB_SERVER_PROJECT_PATH));
+        let granted: Vec<ToolPermission> = options.permissions.clone().unwrap_or_else(|| DEFAULT_PERMISSIONS.to_vec());
+        let model: String = options.model.clone().unwrap_or_else(|| LLM_MODEL.to_string());
+
+        Self {
+            attributes,
+            toolbox: builtin_toolbox(&position, ToolContext::new(workspace), &granted),
+            chat: Box::new(OpenAiChat { model }),
+            max_rounds: options.max_tool_rounds.unwrap_or(DEFAULT_MAX_TOOL_ROUNDS),
+            answer: None,
+        }
+    }
+
+    // 替換模型（例如測試）
+    #[cfg(test)]
+    pub fn with_chat(mut self, chat: Box<dyn ChatModel>) -> Self {
+        self.chat = chat;
+        self
+    }
+
+    // 替換工具調用的審批（例如測試）
+    #[cfg(test)]
+    pub fn with_approvals(mut self, approvals: crate::helpers::approvals::Approvals) -> Self {
+        self.toolbox = self.toolbox.with_approvals(approvals);
+        self
+    }
+
+    // 模型的最終回答
+    #[cfg(test)]
+    pub fn answer(&self) -> Option<&str> {
+        self.answer.as_deref()
+    }
+
+    // 發給模型的初始對話
+    fn initial_messages(&self, factsheet: &FactSheet) -> Vec<Message> {
+        vec![
+            Message {
+                role: "system".to_string(),
+                content: format!(
+                    "You are the {} of an autonomous web server project. Use the tools to act inside the project workspace. \
+                     When the goal is reached, reply with a short summary and no tool calls.",
+                    self.attributes.position
+                ),
+                ..Message::default()
+            },
+            Message {
+                role: "user".to_string(),
+                content: format!(
+                    "PROJECT: {}\nFACTS: {}\nGOAL: {}",
+                    factsheet.project_description,
+                    factsheet.to_prompt_json(),
+                    self.attributes.objective
+                ),
+                ..Message::default()
+            },
+        ]
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentToolUser {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    // 讀取項目描述和 API 端點；文件通過工具直接讀寫，不經過 FactSheet
+    fn reads(&self) -> Vec<FactField> {
+        vec![FactField::ProjectDescription, FactField::ApiEndpointSchema]
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+        self.attributes.update_state(AgentState::Working)?;
+
+        let mut messages: Vec<Message> = self.initial_messages(factsheet);
+        let outcome: Result<String, Box<dyn std::error::Error>> = run_tool_loop(
+            &mut self.attributes,
+            factsheet,
+            self.chat.as_ref(),
+            &self.toolbox,
+            &mut messages,
+            self.max_rounds,
+        )
+        .await;
+
+        match outcome {
+            Ok(answer) => {
+                PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), answer.as_str());
+                self.answer = Some(answer);
+                self.attributes.update_state(AgentState::Finished)?;
+                Ok(())
+            }
+            Err(error) => Err(self.attributes.halt(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::{ApprovalPolicy, Approvals, GateDecision};
+    use crate::models::general::run_error::{exit_code_for, EXIT_POLICY_DENIED};
+    use crate::models::tools::scripted_chat::{final_answer, tool_call, ScriptedChat};
+
+    #[tokio::test]
+    async fn tests_tool_user_acts_with_granted_permissions() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_tool_user_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+
+        let options: AgentOptions = AgentOptions {
+            name: Some("Docs Writer".to_string()),
+            goal: Some("Write a README".to_string()),
+            permissions: Some(vec![ToolPermission::ReadFiles, ToolPermission::WriteFiles]),
+            workspace: Some(workspace.display().to_string()),
+            ..AgentOptions::default()
+        };
+        let chat: ScriptedChat = ScriptedChat::new(vec![
+            tool_call("call_1", "write_file", r#"{"path": "README.md", "contents": "Todo API"}"#),
+            final_answer("Wrote README.md"),
+        ]);
+        let mut agent: AgentToolUser = AgentToolUser::with_options(&options).with_chat(Box::new(chat));
+
+        let mut factsheet: FactSheet = FactSheet {
+            project_description: "todo api".to_string(),
+            ..FactSheet::default()
+        };
+        agent.execute(&mut factsheet).await.unwrap();
+
+        assert_eq!(agent.answer(), Some("Wrote README.md"));
+        assert_eq!(agent.attributes.state, AgentState::Finished);
+        assert_eq!(std::fs::read_to_string(workspace.join("README.md")).unwrap(), "Todo API");
+        assert_eq!(agent.toolbox.allowed_tools(), vec!["read_file", "write_file"]);
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_tool_user_fails_without_final_answer() {
+        let options: AgentOptions = AgentOptions {
+            max_tool_rounds: Some(1),
+            workspace: Some(std::env::temp_dir().display().to_string()),
+            ..AgentOptions::default()
+        };
+        let chat: ScriptedChat = ScriptedChat::new(vec![tool_call("call_1", "read_file", r#"{"path": "missing.rs"}"#)]);
+        let mut agent: AgentToolUser = AgentToolUser::with_options(&options).with_chat(Box::new(chat));
+
+        let err: String = agent.execute(&mut FactSheet::default()).await.unwrap_err().to_string();
+        assert_eq!(err, "no final answer after 1 tool round(s)");
+        assert!(agent.attributes.state.is_terminal());
+    }
+
+    #[tokio::test]
+    async fn tests_denied_tool_call_exits_with_policy_code() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_tool_user_denied_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+
+        let options: AgentOptions = AgentOptions {
+            permissions: Some(vec![ToolPermission::WriteFiles]),
+            workspace: Some(workspace.display().to_string()),
+            ..AgentOptions::default()
+        };
+        let chat: ScriptedChat = ScriptedChat::new(vec![
+            tool_call("call_1", "write_file", r#"{"path": "README.md", "contents": "x"}"#),
+            final_answer("Wrote README.md"),
+        ]);
+        let mut agent: AgentToolUser = AgentToolUser::with_options(&options)
+            .with_chat(Box::new(chat))
+            .with_approvals(Approvals::new(ApprovalPolicy::uniform(GateDecision::AutoDeny)));
+
+        let error: Box<dyn std::error::Error> = agent.execute(&mut FactSheet::default()).await.unwrap_err();
+        assert_eq!(exit_code_for(error.as_ref()), EXIT_POLICY_DENIED);
+        assert!(matches!(agent.attributes.state, AgentState::Failed(_)));
+        assert!(!workspace.join("README.md").exists());
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -1,5 +1,12 @@
 // 引入所需的模組和結構體
+use crate::helpers::approvals::ApprovalRecord;
+use crate::helpers::clarifications::Clarification;
+use crate::helpers::critique::CritiqueRecord;
+use crate::helpers::patching::PatchRecord;
+use crate::helpers::project_files::FileManifest;
+use crate::helpers::url_checker::UrlCheckReport;
 use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::tools::tool::ToolPermission;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
@@ -19,27 +26,353 @@ pub struct ProjectScope {
     pub is_crud_required: bool,
     pub is_user_login_and_logout: bool,
     pub is_external_urls_required: bool,
+    #[serde(default)]
+    pub is_database_required: bool, // 需要 SQL 數據庫保存數據；舊的回應沒有這個欄位時為 false
 }
 
-// 定義 FactSheet 結構體,用於存儲專案相關的資訊
+// 定義 ApiSample 結構體,用於記錄外部 API 回應的截斷樣本,供後端代理編寫反序列化結構體
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ApiSample {
+    pub url: String,
+    pub status_code: u16,
+    pub content_type: Option<String>,
+    pub body_sample: String,
+    pub json_shape: Option<serde_json::Value>,
+}
+
+// 定義 UrlExclusion 結構體,記錄某個外部 URL 被排除的原因
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UrlExclusion {
+    pub url: String,
+    pub reason: String,
+}
+
+// 定義 TaskKind 枚舉,規劃出的任務類型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    Endpoint,
+    DataEntity,
+    Integration,
+    UiPage,
+}
+
+// 定義 ProjectTask 結構體,規劃步驟把項目目標展開成的任務及其驗收標準。
+// target 按類型寫作端點 "GET /todos"、實體名 "Todo"、外部服務域名 "api.binance.com" 或頁面路徑 "/";
+// 編號由規劃步驟分配,completed_by 記錄完成任務的代理
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProjectTask {
+    #[serde(default)]
+    pub id: usize,
+    pub kind: TaskKind,
+    pub target: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub acceptance_criteria: Vec<String>,
+    #[serde(default)]
+    pub completed_by: Option<String>,
+}
+
+impl ProjectTask {
+    // 端點任務:方法相同且路徑相同,動態段(:id、{id})視為相同
+    pub fn matches_route(&self, route: &RouteObject) -> bool {
+        if self.kind != TaskKind::Endpoint {
+            return false;
+        }
+        let mut parts: std::str::SplitWhitespace = self.target.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return false;
+        };
+        method.eq_ignore_ascii_case(&route.method) && normalize_route(path) == normalize_route(&route.route)
+    }
+
+    // 數據實體任務:代碼中定義了同名的結構體,名稱按整個標識符比較(Todo 不匹配 TodoList)
+    pub fn defined_in(&self, code: &str) -> bool {
+        let target: &str = self.target.trim();
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        self.kind == TaskKind::DataEntity
+            && !target.is_empty()
+            && code.match_indices("struct").any(|(start, keyword)| {
+                let before: Option<char> = code[..start].chars().next_back();
+                let rest: &str = &code[start + keyword.len()..];
+                let name: &str = rest.trim_start();
+                before.is_none_or(|c| !is_ident(c))
+                    && name.len() < rest.len()
+                    && name.starts_with(target)
+                    && !name[target.len()..].starts_with(is_ident)
+            })
+    }
+
+    // 流水線中有代理負責完成的任務;沒有代理生成 UI 頁面
+    pub fn has_owning_agent(&self) -> bool {
+        self.kind != TaskKind::UiPage
+    }
+
+    // 外部集成任務:某個可用的外部 URL 包含目標
+    pub fn integrated_by(&self, urls: &[String]) -> bool {
+        let target: String = self.target.trim().to_lowercase();
+        self.kind == TaskKind::Integration && !target.is_empty() && urls.iter().any(|url| url.to_lowercase().contains(&target))
+    }
+}
+
+// 路徑去掉結尾的斜線,動態段統一寫作 {}
+fn normalize_route(path: &str) -> String {
+    let segments: Vec<&str> = path
+        .trim()
+        .trim_end_matches('/')
+        .split('/')
+        .map(|segment| if segment.starts_with(':') || segment.starts_with('{') { "{}" } else { segment })
+        .collect();
+    segments.join("/")
+}
+
+// 定義 FactField 枚舉,列出代理可以聲明讀寫的 FactSheet 字段。
+// approvals 是只追加的審批記錄,任何代理都可以追加,不需要聲明
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactField {
+    ProjectDescription,
+    ProjectScope,
+    ExternalUrls,
+    BackendCode,
+    ApiEndpointSchema,
+    ApiSamples,
+    ExcludedUrls,
+    UrlCheckReports,
+    PatchHistory,
+    ProjectFiles,
+    ProjectTasks,
+    Critiques,
+}
+
+// 定義 FactSheet 結構體,用於存儲專案相關的資訊
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct FactSheet {
     pub project_description: String,
     pub project_scope: Option<ProjectScope>,
     pub external_urls: Option<Vec<String>>,
-    pub backend_code: Option<String>,
+    pub backend_code: Option<String>, // 整個項目的文件包，見 project_files::FILE_MARKER
     pub api_endpoint_schema: Option<Vec<RouteObject>>,
+    pub api_samples: Option<Vec<ApiSample>>,
+    pub excluded_urls: Option<Vec<UrlExclusion>>,
+    pub url_check_reports: Option<Vec<UrlCheckReport>>,
+    pub approvals: Option<Vec<ApprovalRecord>>,
+    pub patch_history: Option<Vec<PatchRecord>>,
+    pub project_files: Option<FileManifest>,
+    pub project_tasks: Option<Vec<ProjectTask>>,
+    pub clarifications: Option<Vec<Clarification>>, // 規劃前由管理代理寫入，已併入項目描述
+    pub critiques: Option<Vec<CritiqueRecord>>,
+}
+
+impl FactSheet {
+    // 合併代理在自己的副本上做的修改:只複製它聲明寫入的字段,並追加它新增的審批記錄
+    pub fn merge_from(&mut self, changed: &FactSheet, writes: &[FactField], approvals_before: usize) {
+        for field in writes {
+            match field {
+                FactField::ProjectDescription => self.project_description = changed.project_description.clone(),
+                FactField::ProjectScope => self.project_scope = changed.project_scope,
+                FactField::ExternalUrls => self.external_urls = changed.external_urls.clone(),
+                FactField::BackendCode => self.backend_code = changed.backend_code.clone(),
+                FactField::ApiEndpointSchema => self.api_endpoint_schema = changed.api_endpoint_schema.clone(),
+                FactField::ApiSamples => self.api_samples = changed.api_samples.clone(),
+                FactField::ExcludedUrls => self.excluded_urls = changed.excluded_urls.clone(),
+                FactField::UrlCheckReports => self.url_check_reports = changed.url_check_reports.clone(),
+                FactField::PatchHistory => self.patch_history = changed.patch_history.clone(),
+                FactField::ProjectFiles => self.project_files = changed.project_files.clone(),
+                FactField::ProjectTasks => self.project_tasks = changed.project_tasks.clone(),
+                FactField::Critiques => self.critiques = changed.critiques.clone(),
+            }
+        }
+        if let Some(records) = &changed.approvals {
+            if records.len() > approvals_before {
+                self.approvals
+                    .get_or_insert_with(Vec::new)
+                    .extend(records[approvals_before..].iter().cloned());
+            }
+        }
+    }
+
+    // 和代理開始前的副本相比被修改過的字段;審批記錄只追加,澄清只由管理代理寫入,都不算在內
+    pub fn changed_fields(&self, before: &FactSheet) -> Vec<FactField> {
+        let changes: [(FactField, bool); 12] = [
+            (FactField::ProjectDescription, self.project_description != before.project_description),
+            (FactField::ProjectScope, self.project_scope != before.project_scope),
+            (FactField::ExternalUrls, self.external_urls != before.external_urls),
+            (FactField::BackendCode, self.backend_code != before.backend_code),
+            (FactField::ApiEndpointSchema, self.api_endpoint_schema != before.api_endpoint_schema),
+            (FactField::ApiSamples, self.api_samples != before.api_samples),
+            (FactField::ExcludedUrls, self.excluded_urls != before.excluded_urls),
+            (FactField::UrlCheckReports, self.url_check_reports != before.url_check_reports),
+            (FactField::PatchHistory, self.patch_history != before.patch_history),
+            (FactField::ProjectFiles, self.project_files != before.project_files),
+            (FactField::ProjectTasks, self.project_tasks != before.project_tasks),
+            (FactField::Critiques, self.critiques != before.critiques),
+        ];
+        changes.into_iter().filter(|(_, changed)| *changed).map(|(field, _)| field).collect()
+    }
+
+    // 發給模型的精簡 JSON：去掉空字段、代碼本身、已併入項目描述的澄清以及只供記錄用的檢查報告、審批記錄、補丁歷史、文件清單和評審記錄
+    pub fn to_prompt_json(&self) -> String {
+        let mut value: serde_json::Value = serde_json::to_value(self).expect("Failed to serialize factsheet");
+        if let Some(fields) = value.as_object_mut() {
+            for key in [
+                "backend_code",
+                "url_check_reports",
+                "approvals",
+                "patch_history",
+                "project_files",
+                "clarifications",
+                "critiques",
+            ] {
+                fields.remove(key);
+            }
+            fields.retain(|_, field| !field.is_null());
+        }
+        value.to_string()
+    }
+
+    // 把滿足條件的未完成任務標記為由 agent 完成,返回新完成的任務編號
+    pub fn complete_tasks(&mut self, agent: &str, is_done: impl Fn(&ProjectTask) -> bool) -> Vec<usize> {
+        let mut completed: Vec<usize> = vec![];
+        for task in self.project_tasks.iter_mut().flatten() {
+            if task.completed_by.is_none() && is_done(task) {
+                task.completed_by = Some(agent.to_string());
+                completed.push(task.id);
+            }
+        }
+        completed
+    }
+
+    // 還沒有完成的任務
+    pub fn open_tasks(&self) -> Vec<&ProjectTask> {
+        self.project_tasks
+            .iter()
+            .flatten()
+            .filter(|task| task.completed_by.is_none())
+            .collect()
+    }
+}
+
+// 定義 AgentOptions 結構體,流水線配置中每個代理的選項;沒有設置的選項使用代理的預設值,不認識的選項是錯誤
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentOptions {
+    pub model: Option<String>,
+    pub max_fix_rounds: Option<u8>,
+    // 外部代理：顯示的名稱、可執行文件和參數、讀寫的字段、文件操作的工作目錄以及最長運行秒數
+    pub name: Option<String>,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub reads: Vec<FactField>,
+    pub writes: Vec<FactField>,
+    pub workspace: Option<String>,
+    pub timeout_secs: Option<u64>,
+    // 代理被授予的工具權限；未設置時使用代理的預設權限
+    pub permissions: Option<Vec<ToolPermission>>,
+    // 使用工具的代理：目標和最多工具調用輪數
+    pub goal: Option<String>,
+    pub max_tool_rounds: Option<usize>,
 }
 
-// 定義 SpecialFunctions trait,包含兩個方法
+impl AgentOptions {
+    // 設置了的選項名稱，與配置文件中的鍵相同
+    pub fn set_options(&self) -> Vec<&'static str> {
+        [
+            ("model", self.model.is_some()),
+            ("max_fix_rounds", self.max_fix_rounds.is_some()),
+            ("name", self.name.is_some()),
+            ("command", self.command.is_some()),
+            ("args", !self.args.is_empty()),
+            ("reads", !self.reads.is_empty()),
+            ("writes", !self.writes.is_empty()),
+            ("workspace", self.workspace.is_some()),
+            ("timeout_secs", self.timeout_secs.is_some()),
+            ("permissions", self.permissions.is_some()),
+            ("goal", self.goal.is_some()),
+            ("max_tool_rounds", self.max_tool_rounds.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(option, _)| option)
+        .collect()
+    }
+}
+
+// 定義 SpecialFunctions trait,代理可以被調度器移到 tokio 任務中並行運行
 #[async_trait]
-pub trait SpecialFunctions: Debug {
+pub trait SpecialFunctions: Debug + Send {
     // Used to that manager can get attributes from Agents
     fn get_attributes_from_agent(&self) -> &BasicAgent;
 
+    // 代理讀取的 FactSheet 字段,調度器據此決定代理之間的依賴
+    fn reads(&self) -> Vec<FactField> {
+        vec![]
+    }
+
+    // 代理寫入的 FactSheet 字段,只有這些字段的修改會被合併回共享的 FactSheet
+    fn writes(&self) -> Vec<FactField> {
+        vec![]
+    }
+
     // This function will allow agents to execute their logic
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
     ) -> Result<(), Box<dyn std::error::Error>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: usize, kind: TaskKind, target: &str) -> ProjectTask {
+        ProjectTask {
+            id,
+            kind,
+            target: target.to_string(),
+            description: format!("{:?} {}", kind, target),
+            acceptance_criteria: vec![format!("{} is available", target)],
+            completed_by: None,
+        }
+    }
+
+    #[test]
+    fn tests_tasks_are_matched_and_completed_once() {
+        let route: RouteObject = RouteObject {
+            is_route_dynamic: "true".to_string(),
+            method: "get".to_string(),
+            request_body: serde_json::Value::Null,
+            response: serde_json::Value::Null,
+            route: "/todos/:id".to_string(),
+        };
+        assert!(task(1, TaskKind::Endpoint, "GET /todos/{id}").matches_route(&route));
+        assert!(!task(1, TaskKind::Endpoint, "DELETE /todos/{id}").matches_route(&route));
+        assert!(!task(1, TaskKind::DataEntity, "GET /todos/{id}").matches_route(&route));
+        assert!(task(2, TaskKind::DataEntity, "Todo").defined_in("pub struct Todo {"));
+        assert!(task(2, TaskKind::DataEntity, "Todo").defined_in("#[derive(Debug)]\nstruct Todo;"));
+        assert!(!task(2, TaskKind::DataEntity, "Todo").defined_in("pub struct TodoList {"));
+        assert!(!task(2, TaskKind::DataEntity, "Todo").defined_in("pub struct MyTodo {"));
+        assert!(!task(2, TaskKind::DataEntity, "Todo").defined_in("let substructTodo = 1;"));
+        assert!(!task(2, TaskKind::UiPage, "/").has_owning_agent());
+        assert!(task(3, TaskKind::Integration, "api.binance.com")
+            .integrated_by(&["https://API.binance.com/api/v3/ticker".to_string()]));
+
+        let mut factsheet: FactSheet = FactSheet {
+            project_tasks: Some(vec![
+                task(1, TaskKind::Endpoint, "GET /todos/{id}"),
+                task(2, TaskKind::DataEntity, "Todo"),
+                task(3, TaskKind::UiPage, "/"),
+            ]),
+            ..FactSheet::default()
+        };
+        let routes: Vec<RouteObject> = vec![route];
+        let completed: Vec<usize> = factsheet.complete_tasks("Backend", |task| {
+            routes.iter().any(|route| task.matches_route(route)) || task.defined_in("struct Todo")
+        });
+        assert_eq!(completed, vec![1, 2]);
+        assert!(factsheet.complete_tasks("Reviewer", |_| true).contains(&3));
+        assert_eq!(factsheet.project_tasks.as_ref().unwrap()[0].completed_by.as_deref(), Some("Backend"));
+        assert!(factsheet.open_tasks().is_empty());
+    }
+}
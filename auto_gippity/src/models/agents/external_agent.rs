@@ -0,0 +1,571 @@
+// 外部代理：以獨立可執行文件實現的代理，不需要修改本 crate。
+// 主程序和子進程通過 stdin/stdout 上的 JSON-RPC 2.0 通信，每行一條消息：
+//   1. 主程序發送 id 為 0 的 run 請求，參數包含代理信息、聲明的讀寫字段和 FactSheet；
+//   2. 子進程在運行期間可以向主程序請求 llm.call、approval.request、file.read 和 file.write，
+//      或發送 log 通知；
+//   3. 子進程以 run 請求的響應結束，結果為 {"patch": {字段: 值}}，只能包含聲明寫入的字段。
+// 子進程的 stderr 直接輸出到終端。每次 llm.call 前檢查運行預算；超過最長運行時間時子進程被終止。
+
+use crate::helpers::approvals::{ApprovalDenied, ApprovalGate, Approvals};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::{ai_text_task_request_with_memory, WEB_SERVER_PROJECT_PATH};
+use crate::helpers::run_budget::check_run_budget;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_traits::{AgentOptions, FactField, FactSheet, SpecialFunctions};
+use crate::models::general::run_error::RunError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+// 協議版本和 run 請求的編號
+pub const JSONRPC_VERSION: &str = "2.0";
+pub const RUN_REQUEST_ID: u64 = 0;
+
+// 流水線配置中外部代理接受的選項
+pub const EXTERNAL_OPTIONS: [&str; 7] = ["name", "command", "args", "reads", "writes", "workspace", "timeout_secs"];
+
+// 沒有配置 timeout_secs 時外部代理最長的運行時間
+pub const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+// JSON-RPC 錯誤碼
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const REQUEST_FAILED: i64 = -32000;
+
+// JSON-RPC 錯誤對象
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn invalid_params(error: impl std::fmt::Display) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: format!("invalid params: {}", error),
+        }
+    }
+
+    fn failed(message: impl Into<String>) -> Self {
+        Self {
+            code: REQUEST_FAILED,
+            message: message.into(),
+        }
+    }
+}
+
+// 一條 JSON-RPC 消息：有 method 的是請求（沒有 id 時為通知），否則是響應
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct RpcMessage {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcMessage {
+    pub fn request(id: Value, method: &str, params: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(id),
+            method: Some(method.to_string()),
+            params: Some(params),
+            ..Self::default()
+        }
+    }
+
+    pub fn response(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error): (Option<Value>, Option<RpcError>) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(id),
+            result,
+            error,
+            ..Self::default()
+        }
+    }
+}
+
+// 子進程可以請求的方法的參數
+#[derive(Debug, Deserialize)]
+struct LlmCallParams {
+    operation: String,
+    function: String,
+    input: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalParams {
+    gate: ApprovalGate,
+    detail: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileReadParams {
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileWriteParams {
+    path: String,
+    contents: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogParams {
+    message: String,
+}
+
+// 把子進程給出的相對路徑限制在工作目錄內：不允許絕對路徑和 ".."
+pub fn resolve_workspace_path(workspace: &Path, relative: &str) -> Result<PathBuf, String> {
+    let path: &Path = Path::new(relative);
+    let is_confined: bool = !relative.is_empty() && path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if is_confined {
+        Ok(workspace.join(path))
+    } else {
+        Err(format!("path '{}' must be relative to the workspace and must not contain '..'", relative))
+    }
+}
+
+// 把補丁應用到 FactSheet 上：每個字段都必須是聲明寫入的字段，值必須符合字段的類型。返回修改的字段
+pub fn apply_patch(factsheet: &mut FactSheet, patch: &Value, writes: &[FactField]) -> Result<Vec<FactField>, String> {
+    let entries: &serde_json::Map<String, Value> = match patch {
+        Value::Object(entries) => entries,
+        Value::Null => return Ok(vec![]),
+        other => return Err(format!("patch must be an object, got {}", other)),
+    };
+
+    let mut sheet: Value = serde_json::to_value(&*factsheet).expect("Failed to serialize factsheet");
+    let mut changed: Vec<FactField> = vec![];
+    for (key, value) in entries {
+        let field: FactField = serde_json::from_value(Value::String(key.clone()))
+            .map_err(|_| format!("patch writes unknown field '{}'", key))?;
+        if !writes.contains(&field) {
+            return Err(format!("patch writes undeclared field '{}'", key));
+        }
+        sheet[key.as_str()] = value.clone();
+        changed.push(field);
+    }
+    *factsheet = serde_json::from_value(sheet).map_err(|e| format!("patch does not match the factsheet: {}", e))?;
+    Ok(changed)
+}
+
+// 寫入一條消息（一行 JSON）
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &RpcMessage) -> Result<(), String> {
+    let mut line: String = serde_json::to_string(message).expect("Failed to serialize message");
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await.map_err(|e| format!("failed to write to agent: {}", e))?;
+    writer.flush().await.map_err(|e| format!("failed to write to agent: {}", e))
+}
+
+// 外部代理
+#[derive(Debug)]
+pub struct ExternalAgent {
+    attributes: BasicAgent,
+    command: Option<String>,
+    args: Vec<String>,
+    reads: Vec<FactField>,
+    writes: Vec<FactField>,
+    workspace: PathBuf,
+    timeout: Duration,
+    approvals: Approvals,
+    denied: Option<ApprovalDenied>, // 運行期間最後一次被拒絕的審批
+    exceeded: Option<RunError>,     // 運行期間超出的預算
+}
+
+impl ExternalAgent {
+    // 按流水線配置建立
+    pub fn with_options(options: &AgentOptions) -> Self {
+        let name: String = options.name.clone().unwrap_or_else(|| "External Agent".to_string());
+        let attributes: BasicAgent = BasicAgent {
+            objective: format!("Runs the external agent {}", options.command.as_deref().unwrap_or("<none>")),
+            position: name,
+            state: AgentState::Discovery,
+            memory: vec![],
+            transitions: vec![],
+            model: options.model.clone(),
+        };
+
+        Self {
+            attributes,
+            command: options.command.clone(),
+            args: options.args.clone(),
+            reads: options.reads.clone(),
+            writes: options.writes.clone(),
+            workspace: PathBuf::from(options.workspace.as_deref().unwrap_or(WEB_SERVER_PROJECT_PATH)),
+            timeout: Duration::from_secs(options.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)),
+            approvals: Approvals::load_default(),
+            denied: None,
+            exceeded: None,
+        }
+    }
+
+    // 替換審批者（例如測試）
+    #[cfg(test)]
+    pub fn with_approvals(mut self, approvals: Approvals) -> Self {
+        self.approvals = approvals;
+        self
+    }
+
+    // 替換最長運行時間（例如測試）
+    #[cfg(test)]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // 運行失敗的原因：運行期間超出預算或有審批被拒絕時返回 RunError 以便得到正確的退出碼
+    fn failure(&mut self, error: String) -> Box<dyn std::error::Error> {
+        if let Some(exceeded) = self.exceeded.take() {
+            return Box::new(exceeded);
+        }
+        match self.denied.take() {
+            Some(denied) => Box::new(RunError::PolicyDenied(denied)),
+            None => error.into(),
+        }
+    }
+
+    // 請求審批並記住被拒絕的審批
+    fn request_approval(&mut self, gate: ApprovalGate, detail: &str, factsheet: &mut FactSheet) -> Result<(), ApprovalDenied> {
+        let outcome: Result<(), ApprovalDenied> = self.approvals.request_for(&mut self.attributes, gate, detail, factsheet);
+        if let Err(denied) = &outcome {
+            self.denied = Some(denied.clone());
+        }
+        outcome
+    }
+
+    // run 請求的參數
+    fn run_params(&self, factsheet: &FactSheet) -> Value {
+        json!({
+            "agent": {
+                "position": self.attributes.position,
+                "objective": self.attributes.objective,
+            },
+            "reads": self.reads,
+            "writes": self.writes,
+            "factsheet": factsheet,
+        })
+    }
+
+    // 處理子進程的一個請求
+    async fn handle_request(&mut self, method: &str, params: Value, factsheet: &mut FactSheet) -> Result<Value, RpcError> {
+        match method {
+            "llm.call" => {
+                let params: LlmCallParams = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+                if let Err(exceeded) = check_run_budget() {
+                    let message: String = exceeded.to_string();
+                    self.exceeded = Some(exceeded);
+                    return Err(RpcError::failed(message));
+                }
+                let response: String = ai_text_task_request_with_memory(
+                    params.input,
+                    &mut self.attributes,
+                    params.operation.as_str(),
+                    params.function.as_str(),
+                )
+                .await
+                .map_err(|e| RpcError::failed(e.to_string()))?;
+                Ok(json!({ "response": response }))
+            }
+            "approval.request" => {
+                let params: ApprovalParams = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+                let approved: bool = self.request_approval(params.gate, params.detail.as_str(), factsheet).is_ok();
+                Ok(json!({ "approved": approved }))
+            }
+            "file.read" => {
+                let params: FileReadParams = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+                let path: PathBuf = resolve_workspace_path(&self.workspace, &params.path).map_err(RpcError::failed)?;
+                let contents: String = std::fs::read_to_string(&path)
+                    .map_err(|e| RpcError::failed(format!("{}: {}", params.path, e)))?;
+                Ok(json!({ "contents": contents }))
+            }
+            "file.write" => {
+                let params: FileWriteParams = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+                let path: PathBuf = resolve_workspace_path(&self.workspace, &params.path).map_err(RpcError::failed)?;
+
+                // Guard:: 覆蓋已有文件前必須通過審批關卡
+                if path.exists() {
+                    self.request_approval(ApprovalGate::BeforeOverwriteFiles, params.path.as_str(), factsheet)
+                        .map_err(|denied| RpcError::failed(denied.to_string()))?;
+                }
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| RpcError::failed(format!("{}: {}", params.path, e)))?;
+                }
+                std::fs::write(&path, params.contents.as_bytes())
+                    .map_err(|e| RpcError::failed(format!("{}: {}", params.path, e)))?;
+                Ok(json!({ "bytes": params.contents.len() }))
+            }
+            "log" => {
+                let params: LogParams = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+                PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), params.message.as_str());
+                Ok(Value::Null)
+            }
+            other => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method '{}'", other),
+            }),
+        }
+    }
+
+    // 與子進程對話：發送 run 請求，處理子進程的請求直到收到 run 的響應，返回結果中的補丁
+    pub async fn converse<R, W>(&mut self, reader: R, mut writer: W, factsheet: &mut FactSheet) -> Result<Value, String>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let run: RpcMessage = RpcMessage::request(json!(RUN_REQUEST_ID), "run", self.run_params(factsheet));
+        write_message(&mut writer, &run).await?;
+
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await.map_err(|e| format!("failed to read from agent: {}", e))? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: RpcMessage = serde_json::from_str(&line).map_err(|e| format!("invalid message from agent: {}", e))?;
+
+            match message.method {
+                Some(method) => {
+                    let outcome: Result<Value, RpcError> = self
+                        .handle_request(method.as_str(), message.params.unwrap_or(Value::Null), factsheet)
+                        .await;
+                    // 通知沒有 id，不需要響應
+                    if let Some(id) = message.id {
+                        write_message(&mut writer, &RpcMessage::response(id, outcome)).await?;
+                    }
+                }
+                None => {
+                    if message.id != Some(json!(RUN_REQUEST_ID)) {
+                        return Err(format!("unexpected response from agent: {}", line));
+                    }
+                    if let Some(error) = message.error {
+                        return Err(format!("agent failed ({}): {}", error.code, error.message));
+                    }
+                    let result: Value = message.result.unwrap_or(Value::Null);
+                    return Ok(result.get("patch").cloned().unwrap_or(Value::Null));
+                }
+            }
+        }
+        Err("agent exited without returning a result".to_string())
+    }
+
+    // 啟動子進程並在最長運行時間內完成一次運行，返回補丁；超時時子進程隨 Child 一起被終止
+    async fn run_process(&mut self, factsheet: &mut FactSheet) -> Result<Value, String> {
+        let command: String = self.command.clone().ok_or("no command configured for the external agent")?;
+        let mut child: Child = Command::new(&command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to start {}: {}", command, e))?;
+
+        let stdin: ChildStdin = child.stdin.take().expect("Child stdin is piped");
+        let stdout: ChildStdout = child.stdout.take().expect("Child stdout is piped");
+        let timeout: Duration = self.timeout;
+        let run = async {
+            let patch: Value = self.converse(BufReader::new(stdout), stdin, factsheet).await?;
+
+            // stdin 已關閉，子進程應該自行退出
+            let status: std::process::ExitStatus =
+                child.wait().await.map_err(|e| format!("failed to wait for {}: {}", command, e))?;
+            if !status.success() {
+                return Err(format!("{} exited with {}", command, status));
+            }
+            Ok(patch)
+        };
+        match tokio::time::timeout(timeout, run).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(format!("{} did not finish within {} s", command, timeout.as_secs_f64())),
+        }
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for ExternalAgent {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn reads(&self) -> Vec<FactField> {
+        self.reads.clone()
+    }
+
+    fn writes(&self) -> Vec<FactField> {
+        self.writes.clone()
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+        self.attributes.update_state(AgentState::Working)?;
+
+        // 補丁先應用到副本上，成功後才替換，失敗時 FactSheet 不變（審批記錄除外）
+        let mut working: FactSheet = factsheet.clone();
+        let outcome: Result<Vec<FactField>, String> = match self.run_process(&mut working).await {
+            Ok(patch) => {
+                let writes: Vec<FactField> = self.writes.clone();
+                apply_patch(&mut working, &patch, &writes)
+            }
+            Err(error) => Err(error),
+        };
+        factsheet.approvals = working.approvals.clone();
+
+        match outcome {
+            Ok(changed) => {
+                *factsheet = working;
+                let done_msg: String = format!("Updated {:?}", changed);
+                PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), done_msg.as_str());
+                self.attributes.update_state(AgentState::Finished)?;
+                Ok(())
+            }
+            Err(error) => {
+                let failure: Box<dyn std::error::Error> = self.failure(error);
+                Err(self.attributes.halt(failure))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::{ApprovalPolicy, GateDecision};
+    use tokio::io::{duplex, split, DuplexStream, ReadHalf, WriteHalf};
+
+    fn test_agent(workspace: &Path, decision: GateDecision) -> ExternalAgent {
+        let options: AgentOptions = AgentOptions {
+            name: Some("Docs Writer".to_string()),
+            command: Some("docs-agent".to_string()),
+            reads: vec![FactField::ApiEndpointSchema],
+            writes: vec![FactField::ProjectDescription],
+            workspace: Some(workspace.display().to_string()),
+            ..AgentOptions::default()
+        };
+        ExternalAgent::with_options(&options).with_approvals(Approvals::new(ApprovalPolicy::uniform(decision)))
+    }
+
+    // 模擬子進程的一方：讀一行消息
+    async fn next_message(lines: &mut tokio::io::Lines<BufReader<ReadHalf<DuplexStream>>>) -> RpcMessage {
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+    }
+
+    async fn send(writer: &mut WriteHalf<DuplexStream>, message: Value) {
+        writer.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_hanging_agent_is_stopped_at_the_timeout() {
+        let options: AgentOptions = AgentOptions {
+            name: Some("Sleeper".to_string()),
+            command: Some("sleep".to_string()),
+            args: vec!["30".to_string()],
+            ..AgentOptions::default()
+        };
+        let mut agent: ExternalAgent = ExternalAgent::with_options(&options).with_timeout(Duration::from_millis(200));
+        let mut factsheet: FactSheet = FactSheet::default();
+
+        let started: std::time::Instant = std::time::Instant::now();
+        let err: Box<dyn std::error::Error> = agent.execute(&mut factsheet).await.unwrap_err();
+        assert_eq!(err.to_string(), "sleep did not finish within 0.2 s");
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(agent.get_attributes_from_agent().state, AgentState::Failed("sleep did not finish within 0.2 s".to_string()));
+    }
+
+    #[test]
+    fn tests_workspace_paths_are_confined() {
+        let workspace: &Path = Path::new("/tmp/project");
+        assert_eq!(resolve_workspace_path(workspace, "docs/API.md").unwrap(), workspace.join("docs/API.md"));
+        assert!(resolve_workspace_path(workspace, "../secret").is_err());
+        assert!(resolve_workspace_path(workspace, "/etc/passwd").is_err());
+        assert!(resolve_workspace_path(workspace, "").is_err());
+    }
+
+    #[test]
+    fn tests_patch_only_writes_declared_fields() {
+        let mut factsheet: FactSheet = FactSheet::default();
+        let changed: Vec<FactField> = apply_patch(
+            &mut factsheet,
+            &json!({ "project_description": "documented", "external_urls": ["https://example.com"] }),
+            &[FactField::ProjectDescription, FactField::ExternalUrls],
+        )
+        .unwrap();
+        assert_eq!(changed, vec![FactField::ExternalUrls, FactField::ProjectDescription]);
+        assert_eq!(factsheet.project_description, "documented");
+
+        let undeclared: String =
+            apply_patch(&mut factsheet, &json!({ "backend_code": "fn main() {}" }), &[FactField::ProjectDescription]).unwrap_err();
+        assert_eq!(undeclared, "patch writes undeclared field 'backend_code'");
+        let mistyped: String =
+            apply_patch(&mut factsheet, &json!({ "external_urls": 3 }), &[FactField::ExternalUrls]).unwrap_err();
+        assert!(mistyped.starts_with("patch does not match the factsheet"));
+        assert_eq!(factsheet.backend_code, None);
+    }
+
+    #[tokio::test]
+    async fn tests_host_serves_agent_requests_until_patch() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_external_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("README.md"), "old").unwrap();
+
+        let mut agent: ExternalAgent = test_agent(&workspace, GateDecision::AutoDeny);
+        let (host_side, agent_side): (DuplexStream, DuplexStream) = duplex(64 * 1024);
+        let (host_reader, host_writer) = split(host_side);
+        let (agent_reader, mut agent_writer) = split(agent_side);
+
+        // 模擬的外部代理：寫一個新文件、試圖覆蓋已有文件、讀取越界路徑，最後返回補丁
+        let remote = tokio::spawn(async move {
+            let mut lines = BufReader::new(agent_reader).lines();
+            let run: RpcMessage = next_message(&mut lines).await;
+            assert_eq!(run.method.as_deref(), Some("run"));
+            let params: Value = run.params.unwrap();
+            assert_eq!(params["agent"]["position"], "Docs Writer");
+            assert_eq!(params["writes"], json!(["project_description"]));
+
+            send(&mut agent_writer, json!({"jsonrpc": "2.0", "method": "log", "params": {"message": "starting"}})).await;
+            send(&mut agent_writer, json!({"jsonrpc": "2.0", "id": 1, "method": "file.write", "params": {"path": "docs/API.md", "contents": "# API"}})).await;
+            let created: RpcMessage = next_message(&mut lines).await;
+            send(&mut agent_writer, json!({"jsonrpc": "2.0", "id": 2, "method": "file.write", "params": {"path": "README.md", "contents": "new"}})).await;
+            let overwrite: RpcMessage = next_message(&mut lines).await;
+            send(&mut agent_writer, json!({"jsonrpc": "2.0", "id": 3, "method": "file.read", "params": {"path": "../README.md"}})).await;
+            let escaped: RpcMessage = next_message(&mut lines).await;
+            send(&mut agent_writer, json!({"jsonrpc": "2.0", "id": 4, "method": "shell.exec", "params": {}})).await;
+            let unknown: RpcMessage = next_message(&mut lines).await;
+
+            send(&mut agent_writer, json!({"jsonrpc": "2.0", "id": 0, "result": {"patch": {"project_description": "documented"}}})).await;
+            (created, overwrite, escaped, unknown)
+        });
+
+        let mut factsheet: FactSheet = FactSheet::default();
+        let patch: Value = agent.converse(BufReader::new(host_reader), host_writer, &mut factsheet).await.unwrap();
+        let (created, overwrite, escaped, unknown) = remote.await.unwrap();
+
+        assert_eq!(created.id, Some(json!(1)));
+        assert_eq!(created.result, Some(json!({ "bytes": 5 })));
+        assert_eq!(std::fs::read_to_string(workspace.join("docs/API.md")).unwrap(), "# API");
+        assert_eq!(overwrite.error.unwrap().code, REQUEST_FAILED);
+        assert_eq!(std::fs::read_to_string(workspace.join("README.md")).unwrap(), "old");
+        assert!(escaped.error.unwrap().message.contains("must not contain '..'"));
+        assert_eq!(unknown.error.unwrap().code, METHOD_NOT_FOUND);
+        assert_eq!(patch, json!({ "project_description": "documented" }));
+        assert_eq!(factsheet.approvals.unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
 pub mod agent_architect;
 pub mod agent_backend;
+pub mod agent_tool_user;
 pub mod agent_traits;
+pub mod external_agent;
 
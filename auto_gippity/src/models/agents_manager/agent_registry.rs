@@ -0,0 +1,321 @@
+// 代理註冊表和流水線配置：每個代理以名稱、接受的選項和工廠函數註冊，
+// config/pipeline.toml 決定運行哪些代理、按什麼順序以及每個代理的選項（模型、最多修復輪數），
+// 不需要重新編譯就可以定義「只有後端」之類的流水線。"external" 代理運行 command 指定的外部可執行文件，
+// "tool_user" 代理按 goal 使用被授予權限的工具。
+
+use crate::helpers::config::{config_path, load_toml_config};
+use crate::models::agents::agent_architect::{AgentSolutionArchitect, ARCHITECT_OPTIONS};
+use crate::models::agents::agent_backend::{AgentBackendDeveloper, BACKEND_OPTIONS};
+use crate::models::agents::agent_tool_user::{AgentToolUser, TOOL_USER_OPTIONS};
+use crate::models::agents::agent_traits::{AgentOptions, SpecialFunctions};
+use crate::models::agents::external_agent::{ExternalAgent, EXTERNAL_OPTIONS};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// 流水線配置文件名
+pub const PIPELINE_FILE: &str = "pipeline.toml";
+
+// 外部代理在註冊表中的名稱
+pub const EXTERNAL_AGENT: &str = "external";
+
+// 按選項建立代理的工廠函數
+pub type AgentFactory = fn(&AgentOptions) -> Box<dyn SpecialFunctions>;
+
+// 註冊的代理：接受的選項和工廠函數
+#[derive(Debug, Clone)]
+struct RegisteredAgent {
+    options: &'static [&'static str],
+    factory: AgentFactory,
+}
+
+// 代理註冊表：名稱 -> 註冊的代理
+#[derive(Debug, Clone, Default)]
+pub struct AgentRegistry {
+    agents: BTreeMap<String, RegisteredAgent>,
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 內置的代理
+    pub fn with_builtin_agents() -> Self {
+        let mut registry: Self = Self::new();
+        registry.register("architect", &ARCHITECT_OPTIONS, |options| {
+            Box::new(AgentSolutionArchitect::with_options(options))
+        });
+        registry.register("backend", &BACKEND_OPTIONS, |options| Box::new(AgentBackendDeveloper::with_options(options)));
+        registry.register(EXTERNAL_AGENT, &EXTERNAL_OPTIONS, |options| Box::new(ExternalAgent::with_options(options)));
+        registry.register("tool_user", &TOOL_USER_OPTIONS, |options| Box::new(AgentToolUser::with_options(options)));
+        registry
+    }
+
+    // 註冊一個代理及其接受的選項；同名的代理會被替換
+    pub fn register(&mut self, name: &str, options: &'static [&'static str], factory: AgentFactory) {
+        self.agents.insert(name.to_string(), RegisteredAgent { options, factory });
+    }
+
+    // 已註冊的代理名稱
+    pub fn names(&self) -> Vec<String> {
+        self.agents.keys().cloned().collect()
+    }
+
+    // 檢查設置的選項都是代理接受的選項；設置了對這個代理無效的選項是錯誤
+    pub fn check_options(&self, name: &str, options: &AgentOptions) -> Result<(), String> {
+        let registered: &RegisteredAgent = self.registered(name)?;
+        let rejected: Vec<&str> = options
+            .set_options()
+            .into_iter()
+            .filter(|option| !registered.options.contains(option))
+            .collect();
+        if rejected.is_empty() {
+            return Ok(());
+        }
+        let accepted: String = if registered.options.is_empty() {
+            "none".to_string()
+        } else {
+            registered.options.join(", ")
+        };
+        Err(format!(
+            "option(s) {} do not apply to agent '{}' (accepted: {})",
+            rejected.join(", "),
+            name,
+            accepted
+        ))
+    }
+
+    // 按名稱建立代理
+    pub fn create(&self, name: &str, options: &AgentOptions) -> Result<Box<dyn SpecialFunctions>, String> {
+        let registered: &RegisteredAgent = self.registered(name)?;
+        Ok((registered.factory)(options))
+    }
+
+    fn registered(&self, name: &str) -> Result<&RegisteredAgent, String> {
+        self.agents
+            .get(name)
+            .ok_or_else(|| format!("unknown agent '{}' (available: {})", name, self.names().join(", ")))
+    }
+}
+
+// 流水線中的一個步驟；AgentOptions 是攤平的，不認識的鍵收集在 unknown_options 中，由 validate 報告
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct PipelineStep {
+    pub agent: String,
+    #[serde(flatten)]
+    pub options: AgentOptions,
+    #[serde(flatten)]
+    pub unknown_options: BTreeMap<String, toml::Value>,
+}
+
+impl PipelineStep {
+    pub fn named(agent: &str) -> Self {
+        Self {
+            agent: agent.to_string(),
+            options: AgentOptions::default(),
+            unknown_options: BTreeMap::new(),
+        }
+    }
+}
+
+// 流水線：按順序列出的代理
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct PipelineConfig {
+    pub agents: Vec<PipelineStep>,
+}
+
+impl Default for PipelineConfig {
+    // 預設流水線：架構師 -> 後端開發
+    fn default() -> Self {
+        Self {
+            agents: vec![PipelineStep::named("architect"), PipelineStep::named("backend")],
+        }
+    }
+}
+
+impl PipelineConfig {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(PIPELINE_FILE)
+    }
+
+    // 從預設路徑讀取流水線，文件不存在時使用預設流水線
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse pipeline config")
+    }
+
+    // 檢查所有代理都已註冊，沒有拼錯或對代理無效的選項，外部代理都指定了 command
+    pub fn validate(&self, registry: &AgentRegistry) -> Result<(), String> {
+        if self.agents.is_empty() {
+            return Err("pipeline has no agents".to_string());
+        }
+        if let Some(step) = self.agents.iter().find(|step| !step.unknown_options.is_empty()) {
+            let keys: Vec<&str> = step.unknown_options.keys().map(String::as_str).collect();
+            return Err(format!("unknown option(s) for agent '{}': {}", step.agent, keys.join(", ")));
+        }
+        if let Some(step) = self
+            .agents
+            .iter()
+            .find(|step| step.agent == EXTERNAL_AGENT && step.options.command.is_none())
+        {
+            return Err(format!(
+                "external agent '{}' has no command",
+                step.options.name.as_deref().unwrap_or(EXTERNAL_AGENT)
+            ));
+        }
+        let unknown: Vec<String> = self
+            .agents
+            .iter()
+            .filter(|step| !registry.agents.contains_key(&step.agent))
+            .map(|step| step.agent.clone())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "unknown agent(s) in pipeline: {} (available: {})",
+                unknown.join(", "),
+                registry.names().join(", ")
+            ));
+        }
+        self.agents
+            .iter()
+            .try_for_each(|step| registry.check_options(&step.agent, &step.options))
+    }
+
+    // 按順序建立流水線中的代理
+    pub fn build(&self, registry: &AgentRegistry) -> Result<Vec<Box<dyn SpecialFunctions>>, String> {
+        self.validate(registry)?;
+        self.agents
+            .iter()
+            .map(|step| registry.create(&step.agent, &step.options))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::parse_toml_config;
+    use crate::models::agents::agent_traits::FactField;
+
+    #[test]
+    fn tests_pipeline_builds_agents_in_order_with_options() {
+        let pipeline: PipelineConfig = parse_toml_config(
+            r#"
+            [[agents]]
+            agent = "backend"
+            model = "gpt-4o"
+            max_fix_rounds = 4
+
+            [[agents]]
+            agent = "architect"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(pipeline.agents[0].options.max_fix_rounds, Some(4));
+
+        let agents: Vec<Box<dyn SpecialFunctions>> = pipeline.build(&AgentRegistry::with_builtin_agents()).unwrap();
+        let positions: Vec<&str> = agents
+            .iter()
+            .map(|agent| agent.get_attributes_from_agent().position.as_str())
+            .collect();
+        assert_eq!(positions, vec!["Backend Developer", "Solutions Architect"]);
+        assert_eq!(agents[0].get_attributes_from_agent().model.as_deref(), Some("gpt-4o"));
+        assert_eq!(agents[1].get_attributes_from_agent().model, None);
+    }
+
+    #[test]
+    fn tests_unknown_agents_are_rejected() {
+        let pipeline: PipelineConfig = PipelineConfig {
+            agents: vec![PipelineStep::named("architect"), PipelineStep::named("docs")],
+        };
+        let err: String = pipeline.validate(&AgentRegistry::with_builtin_agents()).unwrap_err();
+        assert_eq!(err, "unknown agent(s) in pipeline: docs (available: architect, backend, external, tool_user)");
+
+        let mut registry: AgentRegistry = AgentRegistry::with_builtin_agents();
+        registry.register("docs", &ARCHITECT_OPTIONS, |options| Box::new(AgentSolutionArchitect::with_options(options)));
+        assert!(pipeline.validate(&registry).is_ok());
+    }
+
+    #[test]
+    fn tests_misspelled_options_are_rejected() {
+        let pipeline: PipelineConfig = parse_toml_config(
+            r#"
+            [[agents]]
+            agent = "backend"
+            max_fix_round = 4
+            "#,
+        )
+        .unwrap();
+        let err: String = pipeline.validate(&AgentRegistry::with_builtin_agents()).unwrap_err();
+        assert_eq!(err, "unknown option(s) for agent 'backend': max_fix_round");
+
+        let action: Result<AgentOptions, serde_json::Error> = serde_json::from_str(r#"{"modle": "gpt-4o"}"#);
+        assert!(action.unwrap_err().to_string().contains("unknown field `modle`"));
+    }
+
+    #[test]
+    fn tests_options_must_apply_to_the_agent() {
+        let registry: AgentRegistry = AgentRegistry::with_builtin_agents();
+        let pipeline: PipelineConfig = parse_toml_config(
+            r#"
+            [[agents]]
+            agent = "architect"
+            max_fix_rounds = 4
+
+            [[agents]]
+            agent = "backend"
+            command = "sh"
+            args = ["-c", "true"]
+            "#,
+        )
+        .unwrap();
+        let err: String = pipeline.validate(&registry).unwrap_err();
+        assert_eq!(err, "option(s) max_fix_rounds do not apply to agent 'architect' (accepted: model)");
+
+        let backend: AgentOptions = pipeline.agents[1].options.clone();
+        assert_eq!(
+            registry.check_options("backend", &backend).unwrap_err(),
+            "option(s) command, args do not apply to agent 'backend' (accepted: model, max_fix_rounds)"
+        );
+        let model_only: AgentOptions = AgentOptions {
+            model: Some("gpt-4o".to_string()),
+            ..AgentOptions::default()
+        };
+        assert!(registry.check_options("backend", &model_only).is_ok());
+        assert!(registry.check_options("external", &model_only).is_err());
+    }
+
+    #[test]
+    fn tests_external_agents_need_a_command() {
+        let registry: AgentRegistry = AgentRegistry::with_builtin_agents();
+        let mut pipeline: PipelineConfig = parse_toml_config(
+            r#"
+            [[agents]]
+            agent = "external"
+            name = "Docs Writer"
+            reads = ["api_endpoint_schema"]
+            writes = ["project_description"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(pipeline.validate(&registry).unwrap_err(), "external agent 'Docs Writer' has no command");
+
+        pipeline.agents[0].options.command = Some("docs-agent".to_string());
+        let agents: Vec<Box<dyn SpecialFunctions>> = pipeline.build(&registry).unwrap();
+        assert_eq!(agents[0].get_attributes_from_agent().position, "Docs Writer");
+        assert_eq!(agents[0].reads(), vec![FactField::ApiEndpointSchema]);
+        assert_eq!(agents[0].writes(), vec![FactField::ProjectDescription]);
+    }
+
+    #[test]
+    fn tests_shipped_pipelines_are_valid() {
+        let registry: AgentRegistry = AgentRegistry::with_builtin_agents();
+        assert_eq!(PipelineConfig::load_default(), PipelineConfig::default());
+        let backend_only: PipelineConfig = load_toml_config(&config_path("pipelines/backend_only.toml")).unwrap();
+        assert!(backend_only.validate(&registry).is_ok());
+        assert_eq!(backend_only.agents.len(), 1);
+        let with_docs: PipelineConfig = load_toml_config(&config_path("pipelines/with_docs_agent.toml")).unwrap();
+        assert!(with_docs.validate(&registry).is_ok());
+    }
+}
@@ -0,0 +1,990 @@
+// 目標循環模式：管理代理維護一個目標列表，每一步請規劃器（通常是模型）選擇下一個動作——
+// 運行某個代理、使用工具、詢問用戶、增加或完成目標、結束——執行後把觀察結果記錄下來。
+// 所有目標完成、規劃器結束、達到步數上限、審批被拒絕或超出 token 預算時停止；工具和代理的其他失敗作為觀察結果返回給規劃器。
+// 每一步都追加寫入 JSONL 步驟日誌；ReplayPlanner 可以按日誌重新執行同樣的動作而不再詢問模型。
+// 回放只重放計劃：代理和工具會重新運行，詢問用戶使用記錄的回答；觀察結果與日誌不同的步驟報告為分歧。
+
+use crate::helpers::code_chunks::strip_code_fences;
+use crate::helpers::command_line::{get_user_response, PrintCommand};
+use crate::helpers::config::{config_path, load_toml_config};
+use crate::helpers::general::WEB_SERVER_PROJECT_PATH;
+use crate::helpers::run_budget::{check_run_budget, tokens_used};
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_traits::{AgentOptions, FactSheet, SpecialFunctions};
+use crate::models::agents_manager::agent_registry::AgentRegistry;
+use crate::models::agents_manager::scheduler::run_agents;
+use crate::models::general::llm::{APIMessage, Message};
+use crate::models::tools::builtin_tools::builtin_toolbox;
+use crate::models::general::run_error::RunError;
+use crate::models::tools::tool::{ToolBox, ToolContext, ToolError, ToolPermission};
+use crate::models::tools::tool_loop::ChatModel;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 目標循環配置文件名
+pub const GOAL_LOOP_FILE: &str = "goal_loop.toml";
+
+// 未配置時步驟日誌的位置
+const DEFAULT_STEP_LOG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/.goal_loop/steps.jsonl");
+
+// 觀察結果打印時截斷的長度
+const PRINTED_OBSERVATION_CHARS: usize = 200;
+
+// 目標循環配置
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GoalLoopConfig {
+    pub max_steps: usize,
+    pub history_steps: usize, // 發給規劃器的最近步驟數
+    pub permissions: Vec<ToolPermission>,
+    pub step_log: Option<PathBuf>,
+    pub workspace: Option<PathBuf>,
+}
+
+impl Default for GoalLoopConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: 20,
+            history_steps: 8,
+            permissions: vec![ToolPermission::ReadFiles],
+            step_log: None,
+            workspace: None,
+        }
+    }
+}
+
+impl GoalLoopConfig {
+    // 配置文件的預設路徑
+    pub fn default_path() -> PathBuf {
+        config_path(GOAL_LOOP_FILE)
+    }
+
+    // 從預設路徑讀取配置，文件不存在時使用預設值
+    pub fn load_default() -> Self {
+        load_toml_config(&Self::default_path()).expect("Failed to parse goal loop config")
+    }
+
+    // 步驟日誌的路徑
+    pub fn step_log(&self) -> PathBuf {
+        self.step_log.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_STEP_LOG))
+    }
+
+    // 工具的工作目錄
+    pub fn workspace(&self) -> PathBuf {
+        self.workspace.clone().unwrap_or_else(|| PathBuf::from(WEB_SERVER_PROJECT_PATH))
+    }
+}
+
+// 目標
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Goal {
+    pub description: String,
+    pub completed: bool,
+}
+
+impl Goal {
+    pub fn new(description: &str) -> Self {
+        Self {
+            description: description.to_string(),
+            completed: false,
+        }
+    }
+}
+
+// 規劃器可以選擇的動作
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LoopAction {
+    RunAgent {
+        agent: String,
+        #[serde(default)]
+        options: Box<AgentOptions>,
+    },
+    UseTool {
+        tool: String,
+        #[serde(default)]
+        arguments: Value,
+    },
+    AskUser { question: String },
+    AddGoal { goal: String },
+    CompleteGoal { goal: usize },
+    Finish { summary: String },
+    // 規劃器的回應無法解析；只用於記錄這一步，不執行任何操作
+    InvalidReply { reply: String },
+}
+
+// 規劃器的決定：想法和動作
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlannedAction {
+    #[serde(default)]
+    pub thought: String,
+    pub action: LoopAction,
+}
+
+// 一步的記錄
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepRecord {
+    pub step: usize,
+    pub thought: String,
+    pub action: LoopAction,
+    pub observation: String,
+    pub goals: Vec<Goal>,
+    pub tokens_used: u64,
+    pub at_ms: u64,
+}
+
+// 步驟日誌中的一行：開始時記錄項目描述和初始目標，之後每步一行
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+pub enum LogEntry {
+    Start { project_description: String, goals: Vec<Goal> },
+    Step(Box<StepRecord>),
+}
+
+// 步驟日誌：每一步立即追加寫入，進程中斷時已完成的步驟不會丟失
+pub struct StepLog {
+    file: File,
+}
+
+impl StepLog {
+    // 建立新的日誌（覆蓋同名文件）
+    pub fn create(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        let file: File = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self { file })
+    }
+
+    pub fn append(&mut self, entry: &LogEntry) -> Result<(), String> {
+        let line: String = serde_json::to_string(entry).expect("Failed to serialize step");
+        writeln!(self.file, "{}", line).map_err(|e| format!("failed to write step log: {}", e))?;
+        self.file.flush().map_err(|e| format!("failed to write step log: {}", e))
+    }
+}
+
+// 讀取步驟日誌
+pub fn load_step_log(path: &Path) -> Result<Vec<LogEntry>, String> {
+    let contents: String = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", path.display(), index + 1, e))
+        })
+        .collect()
+}
+
+// 發給規劃器的狀態
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PlannerContext {
+    pub step: usize,
+    pub goals: Vec<Goal>,
+    pub recent_steps: Vec<StepRecord>,
+    pub agents: Vec<String>,
+    pub tools: Vec<String>,
+    pub facts: String,
+}
+
+// 規劃器的錯誤：回應無法解析時這一步記錄為錯誤並繼續，其他錯誤停止循環
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlannerError {
+    InvalidReply { reply: String, reason: String },
+    Failed(String),
+}
+
+impl fmt::Display for PlannerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidReply { reason, .. } => write!(f, "invalid action from planner: {}", reason),
+            Self::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PlannerError {}
+
+// 規劃器：決定下一個動作
+#[async_trait]
+pub trait Planner: Send + Sync {
+    async fn next_action(&self, context: &PlannerContext) -> Result<PlannedAction, PlannerError>;
+
+    // 回放時某一步記錄的觀察結果（用於 ask_user 的回答）；其他規劃器返回 None
+    fn recorded_observation(&self, _step: usize) -> Option<String> {
+        None
+    }
+}
+
+// 由模型決定下一個動作
+#[derive(Debug)]
+pub struct LlmPlanner {
+    chat: Box<dyn ChatModel>,
+}
+
+impl LlmPlanner {
+    pub fn new(chat: Box<dyn ChatModel>) -> Self {
+        Self { chat }
+    }
+
+    fn system_prompt() -> &'static str {
+        r#"You are the project manager of an autonomous team building a Rust web server.
+Each turn, choose exactly one next action to make progress on the open goals.
+Reply with JSON only, in this shape: {"thought": "<why>", "action": <action>}
+where <action> is one of:
+  {"type": "run_agent", "agent": "<name from agents>", "options": {}}
+  {"type": "use_tool", "tool": "<name from tools>", "arguments": {...}}
+  {"type": "ask_user", "question": "<question>"}
+  {"type": "add_goal", "goal": "<description>"}
+  {"type": "complete_goal", "goal": <index into goals>}
+  {"type": "finish", "summary": "<what was achieved>"}
+Complete a goal only when the observations show it is done."#
+    }
+}
+
+#[async_trait]
+impl Planner for LlmPlanner {
+    async fn next_action(&self, context: &PlannerContext) -> Result<PlannedAction, PlannerError> {
+        let messages: Vec<Message> = vec![
+            Message {
+                role: "system".to_string(),
+                content: Self::system_prompt().to_string(),
+                ..Message::default()
+            },
+            Message {
+                role: "user".to_string(),
+                content: serde_json::to_string(context).expect("Failed to serialize planner context"),
+                ..Message::default()
+            },
+        ];
+        let reply: APIMessage = self.chat.complete(&messages, &[]).await.map_err(PlannerError::Failed)?;
+        let content: String = reply.content.unwrap_or_default();
+        serde_json::from_str(&strip_code_fences(&content)).map_err(|e| PlannerError::InvalidReply {
+            reply: content.clone(),
+            reason: e.to_string(),
+        })
+    }
+}
+
+// 按步驟日誌回放：依次返回記錄的動作，ask_user 使用記錄的回答
+#[derive(Debug)]
+pub struct ReplayPlanner {
+    steps: Vec<StepRecord>,
+}
+
+impl ReplayPlanner {
+    pub fn new(entries: &[LogEntry]) -> Self {
+        let steps: Vec<StepRecord> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                LogEntry::Step(record) => Some(record.as_ref().clone()),
+                LogEntry::Start { .. } => None,
+            })
+            .collect();
+        Self { steps }
+    }
+
+    fn record(&self, step: usize) -> Option<&StepRecord> {
+        self.steps.iter().find(|record| record.step == step)
+    }
+}
+
+#[async_trait]
+impl Planner for ReplayPlanner {
+    async fn next_action(&self, context: &PlannerContext) -> Result<PlannedAction, PlannerError> {
+        let record: &StepRecord = self
+            .record(context.step)
+            .ok_or_else(|| PlannerError::Failed(format!("replay log has no step {}", context.step)))?;
+        Ok(PlannedAction {
+            thought: record.thought.clone(),
+            action: record.action.clone(),
+        })
+    }
+
+    fn recorded_observation(&self, step: usize) -> Option<String> {
+        self.record(step).map(|record| record.observation.clone())
+    }
+}
+
+// 目標循環的結束方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoopOutcome {
+    Finished(String),
+    GoalsCompleted,
+    StepCapReached,
+}
+
+// 目標循環的結果；divergences 是回放時觀察結果與日誌不同的步驟
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalLoopReport {
+    pub outcome: LoopOutcome,
+    pub steps: Vec<StepRecord>,
+    pub divergences: Vec<usize>,
+}
+
+// 當前時間（Unix 毫秒）；日誌中的行是帶標籤的枚舉，serde 不支持其中的 u128
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// 截斷打印的觀察結果
+fn preview(text: &str) -> String {
+    if text.chars().count() <= PRINTED_OBSERVATION_CHARS {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(PRINTED_OBSERVATION_CHARS).collect();
+    format!("{}...", kept)
+}
+
+// 目標循環：執行規劃器選擇的動作
+pub struct GoalLoop {
+    position: String,
+    config: GoalLoopConfig,
+    registry: AgentRegistry,
+    toolbox: ToolBox,
+    ask: fn(&str) -> String,
+}
+
+impl GoalLoop {
+    pub fn new(position: &str, config: GoalLoopConfig) -> Self {
+        let toolbox: ToolBox = builtin_toolbox(position, ToolContext::new(config.workspace()), &config.permissions);
+        Self {
+            position: position.to_string(),
+            config,
+            registry: AgentRegistry::with_builtin_agents(),
+            toolbox,
+            ask: get_user_response,
+        }
+    }
+
+    // 替換可以運行的代理（例如測試）
+    #[cfg(test)]
+    pub fn with_registry(mut self, registry: AgentRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    // 替換詢問用戶的方式
+    #[cfg(test)]
+    pub fn with_ask(mut self, ask: fn(&str) -> String) -> Self {
+        self.ask = ask;
+        self
+    }
+
+    // 規劃器給出的代理選項：不能指定要運行的命令、參數或工作目錄（代理在目標循環的工作目錄中運行）；
+    // 工具權限不能超過目標循環配置的權限，未指定時使用配置的權限
+    fn planner_options(&self, options: &AgentOptions) -> Result<AgentOptions, String> {
+        let forbidden: Vec<&str> = [
+            ("command", options.command.is_some()),
+            ("args", !options.args.is_empty()),
+            ("workspace", options.workspace.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(field, _)| field)
+        .collect();
+        if !forbidden.is_empty() {
+            return Err(format!("planner actions may not set {}", forbidden.join(", ")));
+        }
+
+        let mut checked: AgentOptions = options.clone();
+        match &options.permissions {
+            Some(requested) => {
+                let beyond: Vec<&ToolPermission> =
+                    requested.iter().filter(|permission| !self.config.permissions.contains(permission)).collect();
+                if !beyond.is_empty() {
+                    return Err(format!(
+                        "permissions {:?} exceed the goal loop's permissions {:?}",
+                        beyond, self.config.permissions
+                    ));
+                }
+            }
+            None => checked.permissions = Some(self.config.permissions.clone()),
+        }
+        checked.workspace = Some(self.config.workspace().display().to_string());
+        Ok(checked)
+    }
+
+    // 運行註冊表中的一個代理；失敗作為觀察結果返回給規劃器，審批被拒絕和超出預算時停止目標循環
+    async fn run_agent(&self, name: &str, options: &AgentOptions, factsheet: &mut FactSheet) -> Result<String, RunError> {
+        let checked: AgentOptions = match self.planner_options(options) {
+            Ok(checked) => checked,
+            Err(e) => return Ok(format!("error: agent {} refused: {}", name, e)),
+        };
+        // 規劃器設置的選項也必須是代理接受的選項（工作目錄和權限由目標循環補上）
+        if let Err(e) = self.registry.check_options(name, options) {
+            return Ok(format!("error: {}", e));
+        }
+        let agent: Box<dyn SpecialFunctions> = match self.registry.create(name, &checked) {
+            Ok(agent) => agent,
+            Err(e) => return Ok(format!("error: {}", e)),
+        };
+        let mut agents: Vec<Box<dyn SpecialFunctions>> = vec![agent];
+        let observation: String = match run_agents(&mut agents, factsheet).await {
+            Ok(report) if !report.undeclared_writes.is_empty() => {
+                let conflicts: Vec<String> = report.undeclared_writes.iter().map(|write| write.to_string()).collect();
+                format!(
+                    "agent {} finished with conflicts: {}; facts: {}",
+                    name,
+                    conflicts.join("; "),
+                    factsheet.to_prompt_json()
+                )
+            }
+            Ok(_) => format!("agent {} finished; facts: {}", name, factsheet.to_prompt_json()),
+            Err(e) => match e.downcast::<RunError>() {
+                Ok(run_error) if matches!(*run_error, RunError::PolicyDenied(_) | RunError::BudgetExceeded { .. }) => {
+                    return Err(*run_error)
+                }
+                Ok(run_error) => format!("error: agent {} failed: {}", name, run_error),
+                Err(other) => format!("error: agent {} failed: {}", name, other),
+            },
+        };
+        Ok(observation)
+    }
+
+    // 執行一個動作，返回觀察結果；審批被拒絕或超出預算時返回錯誤
+    async fn execute_action(
+        &self,
+        step: usize,
+        action: &LoopAction,
+        planner: &dyn Planner,
+        goals: &mut Vec<Goal>,
+        factsheet: &mut FactSheet,
+    ) -> Result<String, RunError> {
+        let observation: String = match action {
+            LoopAction::RunAgent { agent, options } => return self.run_agent(agent, options, factsheet).await,
+            LoopAction::UseTool { tool, arguments } => {
+                // 規劃器不是流水線中的代理，審批期間的狀態記在臨時的代理上；工具失敗作為觀察結果返回
+                let mut caller: BasicAgent = BasicAgent::new("Use tools for the goal loop".to_string(), self.position.clone());
+                match self.toolbox.call(&mut caller, factsheet, tool, &arguments.to_string()).await {
+                    Ok(output) => output,
+                    Err(ToolError::Failed(message)) => format!("error: {}", message),
+                    Err(ToolError::Denied(denied)) => return Err(RunError::PolicyDenied(denied)),
+                }
+            }
+            LoopAction::AskUser { question } => planner
+                .recorded_observation(step)
+                .unwrap_or_else(|| (self.ask)(question)),
+            LoopAction::AddGoal { goal } => {
+                goals.push(Goal::new(goal));
+                format!("added goal {}", goals.len() - 1)
+            }
+            LoopAction::CompleteGoal { goal } => match goals.get_mut(*goal) {
+                Some(completed) => {
+                    completed.completed = true;
+                    format!("completed goal {}: {}", goal, completed.description)
+                }
+                None => format!("error: there is no goal {}", goal),
+            },
+            LoopAction::Finish { summary } => summary.clone(),
+            LoopAction::InvalidReply { reply } => format!("error: the planner reply is not a valid action: {}", reply),
+        };
+        Ok(observation)
+    }
+
+    // 運行目標循環直到停止條件；規劃器的回應無法解析時這一步記錄為錯誤並繼續；
+    // 超出預算、審批被拒絕、規劃器調用失敗或日誌寫入失敗時返回錯誤（停止的這一步仍寫入日誌）
+    pub async fn run(
+        &self,
+        planner: &dyn Planner,
+        goals: &mut Vec<Goal>,
+        factsheet: &mut FactSheet,
+        log: &mut StepLog,
+    ) -> Result<GoalLoopReport, Box<dyn std::error::Error>> {
+        log.append(&LogEntry::Start {
+            project_description: factsheet.project_description.clone(),
+            goals: goals.clone(),
+        })?;
+
+        let mut steps: Vec<StepRecord> = vec![];
+        let mut divergences: Vec<usize> = vec![];
+        for step in 1..=self.config.max_steps {
+            check_run_budget()?;
+
+            let context: PlannerContext = PlannerContext {
+                step,
+                goals: goals.clone(),
+                recent_steps: steps[steps.len().saturating_sub(self.config.history_steps)..].to_vec(),
+                agents: self.registry.names(),
+                tools: self.toolbox.allowed_tools().iter().map(|tool| tool.to_string()).collect(),
+                facts: factsheet.to_prompt_json(),
+            };
+            let planned: PlannedAction = match planner.next_action(&context).await {
+                Ok(planned) => planned,
+                Err(PlannerError::InvalidReply { reply, reason }) => PlannedAction {
+                    thought: reason,
+                    action: LoopAction::InvalidReply { reply },
+                },
+                Err(e) => return Err(e.into()),
+            };
+            let outcome: Result<String, RunError> = self.execute_action(step, &planned.action, planner, goals, factsheet).await;
+            let observation: String = match &outcome {
+                Ok(observation) => observation.clone(),
+                Err(stop) => format!("stopped: {}", stop),
+            };
+
+            // 回放時代理和工具重新運行，結果可能與日誌不同
+            if let Some(recorded) = planner.recorded_observation(step) {
+                if recorded != observation {
+                    let diverged_msg: String = format!(
+                        "Step {} diverged from the log: recorded {:?}, got {:?}",
+                        step,
+                        preview(&recorded),
+                        preview(&observation)
+                    );
+                    PrintCommand::Issue.print_agent_message(self.position.as_str(), diverged_msg.as_str());
+                    divergences.push(step);
+                }
+            }
+
+            let record: StepRecord = StepRecord {
+                step,
+                thought: planned.thought,
+                action: planned.action,
+                observation,
+                goals: goals.clone(),
+                tokens_used: tokens_used(),
+                at_ms: now_ms(),
+            };
+            log.append(&LogEntry::Step(Box::new(record.clone())))?;
+            let step_msg: String = format!(
+                "Step {}: {} -> {}",
+                step,
+                serde_json::to_string(&record.action).expect("Failed to serialize action"),
+                preview(&record.observation)
+            );
+            PrintCommand::AICall.print_agent_message(self.position.as_str(), step_msg.as_str());
+            if let Err(stop) = outcome {
+                return Err(Box::new(stop));
+            }
+
+            let finished: Option<String> = match &record.action {
+                LoopAction::Finish { summary } => Some(summary.clone()),
+                _ => None,
+            };
+            steps.push(record);
+
+            if let Some(summary) = finished {
+                return Ok(GoalLoopReport {
+                    outcome: LoopOutcome::Finished(summary),
+                    steps,
+                    divergences,
+                });
+            }
+            if !goals.is_empty() && goals.iter().all(|goal| goal.completed) {
+                return Ok(GoalLoopReport {
+                    outcome: LoopOutcome::GoalsCompleted,
+                    steps,
+                    divergences,
+                });
+            }
+        }
+
+        Ok(GoalLoopReport {
+            outcome: LoopOutcome::StepCapReached,
+            steps,
+            divergences,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::{ApprovalDenied, ApprovalGate};
+    use crate::models::agent_basic::basic_agent::AgentState;
+    use crate::models::general::run_error::{exit_code_for, EXIT_POLICY_DENIED};
+    use crate::models::agents::agent_tool_user::TOOL_USER_OPTIONS;
+    use crate::models::agents::agent_traits::FactField;
+    use crate::models::tools::scripted_chat::{final_answer, ScriptedChat};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    // 在項目描述後追加標記的測試代理
+    #[derive(Debug)]
+    struct NoteTakingAgent {
+        attributes: BasicAgent,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for NoteTakingAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn reads(&self) -> Vec<FactField> {
+            vec![FactField::ProjectDescription]
+        }
+
+        fn writes(&self) -> Vec<FactField> {
+            vec![FactField::ProjectDescription]
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+            factsheet.project_description.push_str(" [noted]");
+            self.attributes.update_state(AgentState::Finished)?;
+            Ok(())
+        }
+    }
+
+    // 失敗的測試代理：denied 時審批被拒絕，否則是普通的失敗
+    #[derive(Debug)]
+    struct FailingAgent {
+        attributes: BasicAgent,
+        denied: bool,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for FailingAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        async fn execute(&mut self, _factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+            if self.denied {
+                let denied: ApprovalDenied = ApprovalDenied {
+                    gate: ApprovalGate::BeforeBuild,
+                    reason: "denied by policy".to_string(),
+                };
+                return Err(self.attributes.halt(RunError::PolicyDenied(denied)));
+            }
+            Err(self.attributes.halt("compiler exploded".to_string()))
+        }
+    }
+
+    // 按腳本依次返回動作的規劃器
+    struct ScriptedPlanner {
+        actions: Mutex<Vec<LoopAction>>,
+    }
+
+    impl ScriptedPlanner {
+        fn new(mut actions: Vec<LoopAction>) -> Self {
+            actions.reverse();
+            Self {
+                actions: Mutex::new(actions),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Planner for ScriptedPlanner {
+        async fn next_action(&self, context: &PlannerContext) -> Result<PlannedAction, PlannerError> {
+            let action: LoopAction = self
+                .actions
+                .lock()
+                .unwrap()
+                .pop()
+                .ok_or_else(|| PlannerError::Failed("script exhausted".to_string()))?;
+            Ok(PlannedAction {
+                thought: format!("step {}", context.step),
+                action,
+            })
+        }
+    }
+
+    fn test_loop(workspace: &Path) -> GoalLoop {
+        let config: GoalLoopConfig = GoalLoopConfig {
+            workspace: Some(workspace.to_path_buf()),
+            ..GoalLoopConfig::default()
+        };
+        let mut registry: AgentRegistry = AgentRegistry::new();
+        registry.register("note", &[], |_| {
+            Box::new(NoteTakingAgent {
+                attributes: BasicAgent::new("note".to_string(), "Note Taker".to_string()),
+            })
+        });
+        GoalLoop::new("Project Manager", config)
+            .with_registry(registry)
+            .with_ask(|_| "use sqlite".to_string())
+    }
+
+    #[tokio::test]
+    async fn tests_goal_loop_runs_until_goals_complete_and_replays() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_goal_loop_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"web_template\"\n").unwrap();
+
+        let planner: ScriptedPlanner = ScriptedPlanner::new(vec![
+            LoopAction::AddGoal { goal: "pick a database".to_string() },
+            LoopAction::RunAgent { agent: "note".to_string(), options: Box::default() },
+            LoopAction::UseTool { tool: "read_file".to_string(), arguments: json!({"path": "Cargo.toml"}) },
+            LoopAction::UseTool { tool: "cargo".to_string(), arguments: json!({"command": "build"}) },
+            LoopAction::AskUser { question: "Which database?".to_string() },
+            LoopAction::CompleteGoal { goal: 1 },
+            LoopAction::CompleteGoal { goal: 0 },
+        ]);
+        let mut goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+        let mut factsheet: FactSheet = FactSheet {
+            project_description: "todo app".to_string(),
+            ..FactSheet::default()
+        };
+        let log_path: PathBuf = dir.join("steps.jsonl");
+        let mut log: StepLog = StepLog::create(&log_path).unwrap();
+
+        let report: GoalLoopReport = test_loop(&dir).run(&planner, &mut goals, &mut factsheet, &mut log).await.unwrap();
+
+        assert_eq!(report.outcome, LoopOutcome::GoalsCompleted);
+        let observations: Vec<&str> = report.steps.iter().map(|step| step.observation.as_str()).collect();
+        assert_eq!(observations[0], "added goal 1");
+        assert!(observations[1].starts_with("agent note finished"));
+        assert!(observations[2].contains("web_template"));
+        assert!(observations[3].starts_with("error: Project Manager is not permitted to use 'cargo'"));
+        assert_eq!(observations[4], "use sqlite");
+        assert_eq!(observations[6], "completed goal 0: build a todo app");
+        assert_eq!(factsheet.project_description, "todo app [noted]");
+
+        // 日誌：開始記錄 + 每步一行；回放得到同樣的動作和觀察結果，不再詢問用戶
+        let entries: Vec<LogEntry> = load_step_log(&log_path).unwrap();
+        assert_eq!(entries.len(), 8);
+        assert!(matches!(&entries[0], LogEntry::Start { project_description, .. } if project_description == "todo app"));
+
+        let replay: ReplayPlanner = ReplayPlanner::new(&entries);
+        let mut replay_goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+        let mut replay_factsheet: FactSheet = FactSheet {
+            project_description: "todo app".to_string(),
+            ..FactSheet::default()
+        };
+        let mut replay_log: StepLog = StepLog::create(&dir.join("replay.jsonl")).unwrap();
+        let replayed: GoalLoopReport = test_loop(&dir)
+            .with_ask(|question| panic!("replay asked the user: {}", question))
+            .run(&replay, &mut replay_goals, &mut replay_factsheet, &mut replay_log)
+            .await
+            .unwrap();
+
+        assert_eq!(replayed.outcome, LoopOutcome::GoalsCompleted);
+        let replayed_steps: Vec<(&LoopAction, &str)> = replayed
+            .steps
+            .iter()
+            .map(|step| (&step.action, step.observation.as_str()))
+            .collect();
+        let original_steps: Vec<(&LoopAction, &str)> = report
+            .steps
+            .iter()
+            .map(|step| (&step.action, step.observation.as_str()))
+            .collect();
+        assert_eq!(replayed_steps, original_steps);
+        assert!(replayed.divergences.is_empty());
+
+        // 回放重新運行工具：工作區變了，讀文件的觀察結果與日誌不同，報告為分歧
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"renamed\"\n").unwrap();
+        let mut replay_goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+        let mut replay_factsheet: FactSheet = FactSheet {
+            project_description: "todo app".to_string(),
+            ..FactSheet::default()
+        };
+        let mut replay_log: StepLog = StepLog::create(&dir.join("replay.jsonl")).unwrap();
+        let diverged: GoalLoopReport = test_loop(&dir)
+            .run(&replay, &mut replay_goals, &mut replay_factsheet, &mut replay_log)
+            .await
+            .unwrap();
+        assert_eq!(diverged.outcome, LoopOutcome::GoalsCompleted);
+        assert_eq!(diverged.divergences, vec![3]);
+        assert!(diverged.steps[2].observation.contains("renamed"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_invalid_planner_reply_is_recorded_and_the_loop_continues() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_goal_invalid_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let log_path: PathBuf = dir.join("steps.jsonl");
+        let mut log: StepLog = StepLog::create(&log_path).unwrap();
+        let chat: ScriptedChat = ScriptedChat::new(vec![
+            final_answer("I think we should run the backend agent next."),
+            final_answer(r#"{"thought": "done", "action": {"type": "finish", "summary": "ok"}}"#),
+        ]);
+        let planner: LlmPlanner = LlmPlanner::new(Box::new(chat));
+        let mut goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+
+        let report: GoalLoopReport =
+            test_loop(&dir).run(&planner, &mut goals, &mut FactSheet::default(), &mut log).await.unwrap();
+
+        assert_eq!(report.outcome, LoopOutcome::Finished("ok".to_string()));
+        assert_eq!(
+            report.steps[0].action,
+            LoopAction::InvalidReply {
+                reply: "I think we should run the backend agent next.".to_string()
+            }
+        );
+        assert!(report.steps[0].observation.starts_with("error: the planner reply is not a valid action"));
+        assert_eq!(load_step_log(&log_path).unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_goal_loop_stops_at_step_cap_and_on_finish() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_goal_cap_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut log: StepLog = StepLog::create(&dir.join("steps.jsonl")).unwrap();
+        let mut goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+        let mut factsheet: FactSheet = FactSheet::default();
+
+        let mut capped: GoalLoop = test_loop(&dir);
+        capped.config.max_steps = 2;
+        let planner: ScriptedPlanner = ScriptedPlanner::new(vec![
+            LoopAction::AskUser { question: "Anything else?".to_string() },
+            LoopAction::AskUser { question: "Anything else?".to_string() },
+            LoopAction::Finish { summary: "never reached".to_string() },
+        ]);
+        let report: GoalLoopReport = capped.run(&planner, &mut goals, &mut factsheet, &mut log).await.unwrap();
+        assert_eq!(report.outcome, LoopOutcome::StepCapReached);
+        assert_eq!(report.steps.len(), 2);
+
+        let finishing: ScriptedPlanner = ScriptedPlanner::new(vec![LoopAction::Finish { summary: "done".to_string() }]);
+        let report: GoalLoopReport = test_loop(&dir).run(&finishing, &mut goals, &mut factsheet, &mut log).await.unwrap();
+        assert_eq!(report.outcome, LoopOutcome::Finished("done".to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_denied_approval_stops_the_goal_loop() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_goal_denied_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut registry: AgentRegistry = AgentRegistry::new();
+        registry.register("broken", &[], |_| {
+            Box::new(FailingAgent {
+                attributes: BasicAgent::new("fail".to_string(), "Broken".to_string()),
+                denied: false,
+            })
+        });
+        registry.register("denied", &[], |_| {
+            Box::new(FailingAgent {
+                attributes: BasicAgent::new("fail".to_string(), "Denied".to_string()),
+                denied: true,
+            })
+        });
+        let planner: ScriptedPlanner = ScriptedPlanner::new(vec![
+            LoopAction::RunAgent { agent: "broken".to_string(), options: Box::default() },
+            LoopAction::RunAgent { agent: "denied".to_string(), options: Box::default() },
+            LoopAction::Finish { summary: "never reached".to_string() },
+        ]);
+        let log_path: PathBuf = dir.join("steps.jsonl");
+        let mut log: StepLog = StepLog::create(&log_path).unwrap();
+        let mut goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+
+        let err: Box<dyn std::error::Error> = test_loop(&dir)
+            .with_registry(registry)
+            .run(&planner, &mut goals, &mut FactSheet::default(), &mut log)
+            .await
+            .unwrap_err();
+
+        // 普通的失敗是觀察結果，循環繼續；被拒絕的審批停止循環並得到審批的退出碼，停止的這一步也寫入日誌
+        assert_eq!(exit_code_for(err.as_ref()), EXIT_POLICY_DENIED);
+        let entries: Vec<LogEntry> = load_step_log(&log_path).unwrap();
+        assert_eq!(entries.len(), 3);
+        let LogEntry::Step(first) = &entries[1] else {
+            panic!("expected a step entry");
+        };
+        assert_eq!(first.observation, "error: agent broken failed: compiler exploded");
+        let LogEntry::Step(second) = &entries[2] else {
+            panic!("expected a step entry");
+        };
+        assert!(second.observation.starts_with("stopped: "));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // 一條記錄了 run_agent 動作的回放步驟
+    fn replayed_run_agent(step: usize, options: AgentOptions) -> LogEntry {
+        LogEntry::Step(Box::new(StepRecord {
+            step,
+            thought: "use more tools".to_string(),
+            action: LoopAction::RunAgent {
+                agent: "tool_user".to_string(),
+                options: Box::new(options),
+            },
+            observation: String::new(),
+            goals: vec![],
+            tokens_used: 0,
+            at_ms: 0,
+        }))
+    }
+
+    #[tokio::test]
+    async fn tests_planner_cannot_widen_agent_options() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("auto_gippity_goal_options_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut registry: AgentRegistry = AgentRegistry::new();
+        registry.register("tool_user", &TOOL_USER_OPTIONS, |options| {
+            // 通過檢查的選項只帶有目標循環配置的權限
+            assert_eq!(options.permissions, Some(vec![ToolPermission::ReadFiles]));
+            Box::new(NoteTakingAgent {
+                attributes: BasicAgent::new("note".to_string(), "Tool User".to_string()),
+            })
+        });
+        let entries: Vec<LogEntry> = vec![
+            replayed_run_agent(
+                1,
+                AgentOptions {
+                    permissions: Some(vec![ToolPermission::ReadFiles, ToolPermission::RunCommands, ToolPermission::Network]),
+                    ..AgentOptions::default()
+                },
+            ),
+            replayed_run_agent(
+                2,
+                AgentOptions {
+                    command: Some("sh".to_string()),
+                    args: vec!["-c".to_string(), "rm -rf /".to_string()],
+                    ..AgentOptions::default()
+                },
+            ),
+            replayed_run_agent(3, AgentOptions::default()),
+        ];
+        let mut log: StepLog = StepLog::create(&dir.join("steps.jsonl")).unwrap();
+        let mut goals: Vec<Goal> = vec![Goal::new("build a todo app")];
+        let mut factsheet: FactSheet = FactSheet::default();
+
+        let mut capped: GoalLoop = test_loop(&dir).with_registry(registry);
+        capped.config.max_steps = 3;
+        let report: GoalLoopReport = capped.run(&ReplayPlanner::new(&entries), &mut goals, &mut factsheet, &mut log).await.unwrap();
+
+        assert_eq!(
+            report.steps[0].observation,
+            "error: agent tool_user refused: permissions [RunCommands, Network] exceed the goal loop's permissions [ReadFiles]"
+        );
+        assert_eq!(report.steps[1].observation, "error: agent tool_user refused: planner actions may not set command, args");
+        assert!(report.steps[2].observation.starts_with("agent tool_user finished"));
+        assert_eq!(factsheet.project_description, " [noted]");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tests_planner_actions_parse_from_model_json() {
+        let planned: PlannedAction = serde_json::from_str(
+            r#"{"thought": "need a backend", "action": {"type": "run_agent", "agent": "backend", "options": {"max_fix_rounds": 3}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            planned.action,
+            LoopAction::RunAgent {
+                agent: "backend".to_string(),
+                options: Box::new(AgentOptions {
+                    max_fix_rounds: Some(3),
+                    ..AgentOptions::default()
+                }),
+            }
+        );
+        let finish: PlannedAction = serde_json::from_str(r#"{"action": {"type": "finish", "summary": "ok"}}"#).unwrap();
+        assert_eq!(finish.thought, "");
+    }
+}
@@ -1,12 +1,446 @@
-use crate::models::agent_basic::basic_agent::{AgentState,BasicAgent};
-use crate::models::agents::agent_traits::{FactSheet,SpecialFunctions};
-
+// 管理代理：先就請求中缺失或含糊的需求提出澄清問題，再把用戶請求轉換成項目目標並展開成帶驗收標準的任務列表，建立 FactSheet，並按依賴圖調度各個專職代理完成整個流水線；
+// 也可以在目標循環模式下由規劃器逐步決定運行哪個代理、使用什麼工具。
 
+use crate::ai_functions::aifunc_managing::{
+    convert_user_input_to_goal, print_clarifying_questions, print_project_tasks,
+};
+use crate::helpers::clarifications::{
+    ask_clarifying_questions, ask_user, fold_into_description, Clarification, ClarificationConfig, ClarifyingQuestion,
+};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::{ai_task_request_decoded_with_memory, ai_task_request_with_memory};
+use crate::helpers::run_budget::check_run_budget;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_traits::{FactSheet, ProjectTask, SpecialFunctions};
+use crate::models::agents_manager::agent_registry::{AgentRegistry, PipelineConfig};
+use crate::models::agents_manager::goal_loop::{
+    Goal, GoalLoop, GoalLoopConfig, GoalLoopReport, LogEntry, LoopOutcome, Planner, StepLog,
+};
+use crate::models::agents_manager::scheduler::{run_agents, ScheduleReport};
+use crate::models::general::run_error::RunError;
 
 #[derive(Debug)]
-pub struct ManagingAgent{
-    attributes:BasicAgent,
-    factsheet:FactSheet,
-    agents:Vec<Box<dyn SpecialFunctions>>
+pub struct ManagingAgent {
+    attributes: BasicAgent,
+    factsheet: FactSheet,
+    agents: Vec<Box<dyn SpecialFunctions>>,
+    pipeline: PipelineConfig,
+    goals: Vec<Goal>,
+}
+
+impl ManagingAgent {
+    // 管理代理的屬性
+    fn manager_attributes() -> BasicAgent {
+        BasicAgent {
+            objective: "Manage agents who are building an excellent website for the user".to_string(),
+            position: "Project Manager".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+            transitions: vec![],
+            model: None,
+        }
+    }
+
+    // 根據用戶請求建立管理代理
+    pub async fn new(usr_req: String) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut attributes: BasicAgent = Self::manager_attributes();
+
+        // 澄清步驟：規劃前先回答模型找出的含糊需求
+        let config: ClarificationConfig = ClarificationConfig::load_default();
+        let clarifications: Vec<Clarification> = if config.is_active() {
+            let questions: Vec<ClarifyingQuestion> = ai_task_request_decoded_with_memory::<Vec<ClarifyingQuestion>>(
+                usr_req.clone(),
+                &mut attributes,
+                get_function_string!(print_clarifying_questions),
+                print_clarifying_questions,
+            )
+            .await?;
+            ask_clarifying_questions(&questions, config.max_questions, ask_user)
+        } else {
+            vec![]
+        };
+
+        let goal: String = ai_task_request_with_memory(
+            usr_req,
+            &mut attributes,
+            get_function_string!(convert_user_input_to_goal),
+            convert_user_input_to_goal,
+        )
+        .await?;
+        let project_description: String = fold_into_description(&goal, &clarifications);
+
+        // 規劃步驟：把目標展開成有序的任務列表，任務按順序編號；回應無法解析時不帶任務繼續運行
+        let mut tasks: Vec<ProjectTask> = match ai_task_request_decoded_with_memory::<Vec<ProjectTask>>(
+            project_description.clone(),
+            &mut attributes,
+            get_function_string!(print_project_tasks),
+            print_project_tasks,
+        )
+        .await
+        {
+            Ok(tasks) => tasks,
+            Err(RunError::MalformedReply(reason)) => {
+                let skip_msg: String = format!("Could not read the task plan, running without tasks: {}", reason);
+                PrintCommand::Issue.print_agent_message(attributes.position.as_str(), skip_msg.as_str());
+                vec![]
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for (index, task) in tasks.iter_mut().enumerate() {
+            task.id = index + 1;
+        }
+        let plan_msg: String = format!("Planned {} task(s)", tasks.len());
+        PrintCommand::UnitTest.print_agent_message(attributes.position.as_str(), plan_msg.as_str());
+
+        let mut manager: Self = Self::with_description(attributes, project_description);
+        manager.factsheet.project_tasks = Some(tasks);
+        if !clarifications.is_empty() {
+            manager.factsheet.clarifications = Some(clarifications);
+        }
+        Ok(manager)
+    }
+
+    // 按步驟日誌的開始記錄建立管理代理：項目描述和初始目標都來自日誌，回放從同樣的狀態開始
+    pub fn from_step_log(entries: &[LogEntry]) -> Result<Self, String> {
+        let Some(LogEntry::Start { project_description, goals }) = entries.first() else {
+            return Err("step log does not start with a start entry".to_string());
+        };
+        let mut manager: Self = Self::with_description(Self::manager_attributes(), project_description.clone());
+        manager.goals = goals.clone();
+        Ok(manager)
+    }
+
+    // 以已知的項目描述建立管理代理
+    fn with_description(attributes: BasicAgent, project_description: String) -> Self {
+        let factsheet: FactSheet = FactSheet {
+            project_description,
+            ..FactSheet::default()
+        };
+
+        Self {
+            attributes,
+            factsheet,
+            agents: vec![],
+            pipeline: PipelineConfig::default(),
+            goals: vec![],
+        }
+    }
+
+    // 加入一個代理
+    pub fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>) {
+        self.agents.push(agent);
+    }
+
+    // 使用指定的流水線（預設為架構師 -> 後端開發）
+    pub fn with_pipeline(mut self, pipeline: PipelineConfig) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    // 按流水線配置從註冊表建立代理
+    fn create_agents(&mut self) -> Result<(), String> {
+        for agent in self.pipeline.build(&AgentRegistry::with_builtin_agents())? {
+            self.add_agent(agent);
+        }
+        Ok(())
+    }
+
+    // 執行所有代理；任何代理失敗或超出預算都會停止整個流水線
+    pub async fn execute_project(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.agents.is_empty() {
+            self.create_agents()?;
+        }
+        self.attributes.update_state(AgentState::Working)?;
+
+        let result: Result<(), Box<dyn std::error::Error>> = self.schedule_agents().await;
+        self.report_tasks();
+        if let Err(e) = result {
+            self.attributes.fail(e.to_string());
+            return Err(e);
+        }
+        self.attributes.update_state(AgentState::Finished)?;
+        Ok(())
+    }
+
+    // 還沒有完成的任務的驗收標準，每條寫作 "task <編號> (<目標>): <標準>"
+    pub fn unmet_criteria(&self) -> Vec<String> {
+        self.factsheet
+            .open_tasks()
+            .into_iter()
+            .flat_map(|task| {
+                task.acceptance_criteria
+                    .iter()
+                    .map(move |criterion| format!("task {} ({}): {}", task.id, task.target, criterion))
+            })
+            .collect()
+    }
+
+    // 已完成任務的驗收標準：代理只確認了目標存在，標準本身沒有被檢查
+    pub fn unverified_criteria(&self) -> Vec<String> {
+        self.factsheet
+            .project_tasks
+            .iter()
+            .flatten()
+            .filter(|task| task.completed_by.is_some())
+            .flat_map(|task| {
+                task.acceptance_criteria
+                    .iter()
+                    .map(move |criterion| format!("task {} ({}): {}", task.id, task.target, criterion))
+            })
+            .collect()
+    }
+
+    // 報告任務完成情況、沒有代理負責的任務、沒有滿足和沒有驗證的驗收標準
+    fn report_tasks(&self) {
+        let Some(tasks) = &self.factsheet.project_tasks else {
+            return;
+        };
+        let position: &str = self.attributes.position.as_str();
+        let open: Vec<&ProjectTask> = self.factsheet.open_tasks();
+        let done_msg: String = format!("{} of {} task(s) completed", tasks.len() - open.len(), tasks.len());
+        PrintCommand::UnitTest.print_agent_message(position, done_msg.as_str());
+        for task in open.iter().filter(|task| !task.has_owning_agent()) {
+            let unowned_msg: String = format!("task {} ({}): no agent in the pipeline builds {:?} tasks", task.id, task.target, task.kind);
+            PrintCommand::Issue.print_agent_message(position, unowned_msg.as_str());
+        }
+        for criterion in self.unmet_criteria() {
+            let unmet_msg: String = format!("Unmet: {}", criterion);
+            PrintCommand::Issue.print_agent_message(position, unmet_msg.as_str());
+        }
+        for criterion in self.unverified_criteria() {
+            let unverified_msg: String = format!("Not verified: {}", criterion);
+            PrintCommand::UnitTest.print_agent_message(position, unverified_msg.as_str());
+        }
+    }
+
+    // 目標循環模式：初始目標是項目描述，每一步由規劃器選擇動作，步驟寫入配置的日誌
+    pub async fn execute_goal_loop(
+        &mut self,
+        planner: &dyn Planner,
+        config: GoalLoopConfig,
+    ) -> Result<GoalLoopReport, Box<dyn std::error::Error>> {
+        self.attributes.update_state(AgentState::Working)?;
+        if self.goals.is_empty() {
+            self.goals.push(Goal::new(&self.factsheet.project_description));
+        }
+
+        let mut log: StepLog = StepLog::create(&config.step_log())?;
+        let goal_loop: GoalLoop = GoalLoop::new(&self.attributes.position, config);
+        let result: Result<GoalLoopReport, Box<dyn std::error::Error>> =
+            goal_loop.run(planner, &mut self.goals, &mut self.factsheet, &mut log).await;
+        self.report_tasks();
+        match result {
+            Ok(report) => {
+                let outcome_msg: String = match &report.outcome {
+                    LoopOutcome::Finished(summary) => format!("Finished after {} step(s): {}", report.steps.len(), summary),
+                    LoopOutcome::GoalsCompleted => format!("All goals completed after {} step(s)", report.steps.len()),
+                    LoopOutcome::StepCapReached => format!("Stopped at the step cap ({} step(s))", report.steps.len()),
+                };
+                PrintCommand::UnitTest.print_agent_message(self.attributes.position.as_str(), outcome_msg.as_str());
+                self.attributes.update_state(AgentState::Finished)?;
+                Ok(report)
+            }
+            Err(e) => {
+                self.attributes.fail(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    // 按代理聲明的讀寫字段調度：沒有依賴關係的代理並行運行
+    async fn schedule_agents(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let positions: Vec<String> = self
+            .agents
+            .iter()
+            .map(|agent| agent.get_attributes_from_agent().position.clone())
+            .collect();
+        PrintCommand::AICall.print_agent_message(
+            self.attributes.position.as_str(),
+            format!("Scheduling {}", positions.join(", ")).as_str(),
+        );
+
+        let report: ScheduleReport = run_agents(&mut self.agents, &mut self.factsheet).await?;
+        for write in &report.undeclared_writes {
+            let conflict_msg: String = format!("Conflict: {}", write);
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), conflict_msg.as_str());
+        }
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            format!("Critical path: {}", report.describe_critical_path()).as_str(),
+        );
+
+        check_run_budget()?;
+        Ok(())
+    }
+
+    // 取得 FactSheet
+    #[cfg(test)]
+    pub fn factsheet(&self) -> &FactSheet {
+        &self.factsheet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::event_bus::{event_bus, AgentEvent, EventRecorder, SubscriptionId};
+    use crate::models::agents::agent_traits::{FactField, TaskKind};
+    use async_trait::async_trait;
+
+    // 只記錄自己被執行過的測試代理
+    #[derive(Debug)]
+    struct NoteTakingAgent {
+        attributes: BasicAgent,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for NoteTakingAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn reads(&self) -> Vec<FactField> {
+            vec![FactField::ProjectDescription]
+        }
+
+        fn writes(&self) -> Vec<FactField> {
+            vec![FactField::ProjectDescription]
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+            factsheet.project_description.push_str(" [noted]");
+            self.attributes.update_state(AgentState::Finished)?;
+            Ok(())
+        }
+    }
+
+    // 完成數據實體任務的測試代理
+    #[derive(Debug)]
+    struct EntityAgent {
+        attributes: BasicAgent,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for EntityAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn reads(&self) -> Vec<FactField> {
+            vec![FactField::ProjectTasks]
+        }
+
+        fn writes(&self) -> Vec<FactField> {
+            vec![FactField::ProjectTasks]
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+            factsheet.complete_tasks(&self.attributes.position, |task| task.defined_in("pub struct Todo {}"));
+            self.attributes.update_state(AgentState::Finished)?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_manager_reports_unmet_criteria() {
+        let attributes: BasicAgent = BasicAgent::new("manage".to_string(), "Project Manager".to_string());
+        let mut manager: ManagingAgent = ManagingAgent::with_description(attributes, "build a todo app".to_string());
+        manager.factsheet.project_tasks = Some(vec![
+            ProjectTask {
+                id: 1,
+                kind: TaskKind::DataEntity,
+                target: "Todo".to_string(),
+                description: "Todo item".to_string(),
+                acceptance_criteria: vec!["Todo has id and title".to_string()],
+                completed_by: None,
+            },
+            ProjectTask {
+                id: 2,
+                kind: TaskKind::UiPage,
+                target: "/".to_string(),
+                description: "Todo list page".to_string(),
+                acceptance_criteria: vec!["Lists todos".to_string(), "Has a create form".to_string()],
+                completed_by: None,
+            },
+        ]);
+        manager.add_agent(Box::new(EntityAgent {
+            attributes: BasicAgent::new("entities".to_string(), "Entity Writer".to_string()),
+        }));
+
+        manager.execute_project().await.unwrap();
+
+        let tasks: &[ProjectTask] = manager.factsheet().project_tasks.as_deref().unwrap();
+        assert_eq!(tasks[0].completed_by.as_deref(), Some("Entity Writer"));
+        assert_eq!(
+            manager.unmet_criteria(),
+            vec!["task 2 (/): Lists todos".to_string(), "task 2 (/): Has a create form".to_string()]
+        );
+        assert_eq!(manager.unverified_criteria(), vec!["task 1 (Todo): Todo has id and title".to_string()]);
+    }
+
+    #[test]
+    fn tests_planned_tasks_may_omit_description_and_criteria() {
+        let tasks: Vec<ProjectTask> = serde_json::from_str(r#"[{"kind": "ui_page", "target": "/"}]"#).unwrap();
+        assert_eq!(tasks[0].description, "");
+        assert!(tasks[0].acceptance_criteria.is_empty());
+        assert!(!tasks[0].has_owning_agent());
+    }
+
+    #[tokio::test]
+    async fn tests_managing_agent_runs_agents_in_order() {
+        let attributes: BasicAgent = BasicAgent::new("manage".to_string(), "Project Manager".to_string());
+        let mut manager: ManagingAgent = ManagingAgent::with_description(attributes, "build a todo app".to_string());
+        for _ in 0..2 {
+            manager.add_agent(Box::new(NoteTakingAgent {
+                attributes: BasicAgent::new("note".to_string(), "Note Taker".to_string()),
+            }));
+        }
+
+        manager.execute_project().await.unwrap();
+
+        assert_eq!(manager.factsheet().project_description, "build a todo app [noted] [noted]");
+        assert_eq!(manager.attributes.state, AgentState::Finished);
+    }
+
+    #[tokio::test]
+    async fn tests_manager_publishes_agent_events() {
+        let recorder: std::sync::Arc<EventRecorder> = std::sync::Arc::new(EventRecorder::for_agent("Event Recorder"));
+        let subscription: SubscriptionId = event_bus().subscribe(recorder.clone());
+
+        let attributes: BasicAgent = BasicAgent::new("manage".to_string(), "Project Manager".to_string());
+        let mut manager: ManagingAgent = ManagingAgent::with_description(attributes, "build a todo app".to_string());
+        manager.add_agent(Box::new(NoteTakingAgent {
+            attributes: BasicAgent::new("note".to_string(), "Event Recorder".to_string()),
+        }));
+        manager.execute_project().await.unwrap();
+        event_bus().unsubscribe(subscription);
+
+        assert_eq!(
+            recorder.events(),
+            vec![
+                AgentEvent::AgentStarted { agent: "Event Recorder".to_string() },
+                AgentEvent::StateChanged {
+                    agent: "Event Recorder".to_string(),
+                    from: "Discovery".to_string(),
+                    to: "Finished".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tests_replay_starts_from_the_logged_goals() {
+        let mut done: Goal = Goal::new("pick a database");
+        done.completed = true;
+        let entries: Vec<LogEntry> = vec![LogEntry::Start {
+            project_description: "todo app".to_string(),
+            goals: vec![Goal::new("build a todo app"), done.clone()],
+        }];
 
-}
\ No newline at end of file
+        let manager: ManagingAgent = ManagingAgent::from_step_log(&entries).unwrap();
+        assert_eq!(manager.factsheet.project_description, "todo app");
+        assert_eq!(manager.goals, vec![Goal::new("build a todo app"), done]);
+        assert!(ManagingAgent::from_step_log(&[]).is_err());
+    }
+}
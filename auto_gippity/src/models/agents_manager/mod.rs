@@ -1 +1,4 @@
+pub mod agent_registry;
+pub mod goal_loop;
 pub mod managing_agent;
+pub mod scheduler;
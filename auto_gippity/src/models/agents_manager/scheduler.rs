@@ -0,0 +1,470 @@
+// 代理調度：代理聲明讀寫的 FactSheet 字段，調度器按聲明（和加入順序）建立依賴圖，
+// 沒有依賴關係的代理在 tokio 上並行運行。每個代理在自己的 FactSheet 副本上工作，
+// 完成後只合併它聲明寫入的字段，修改了沒有聲明的字段時作為衝突報告。運行結束時報告關鍵路徑。
+
+use crate::helpers::event_bus::{publish, AgentEvent};
+use crate::helpers::run_budget::check_run_budget;
+use crate::models::agents::agent_traits::{FactField, FactSheet, SpecialFunctions};
+use crate::models::general::run_error::RunError;
+use std::error::Error;
+use std::fmt;
+use std::time::Instant;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+// 代理的讀寫聲明
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentDeclaration {
+    pub name: String,
+    pub reads: Vec<FactField>,
+    pub writes: Vec<FactField>,
+}
+
+impl AgentDeclaration {
+    pub fn of(agent: &dyn SpecialFunctions) -> Self {
+        Self {
+            name: agent.get_attributes_from_agent().position.clone(),
+            reads: agent.reads(),
+            writes: agent.writes(),
+        }
+    }
+}
+
+// 後加入的代理覆蓋了先加入的代理寫入的字段，卻沒有讀取它
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteConflict {
+    pub field: FactField,
+    pub first: String,
+    pub second: String,
+}
+
+impl fmt::Display for WriteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} overwrites {:?} written by {} without reading it",
+            self.second, self.field, self.first
+        )
+    }
+}
+
+// 代理修改了沒有聲明寫入的字段；修改不會被合併
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndeclaredWrite {
+    pub field: FactField,
+    pub agent: String,
+}
+
+impl fmt::Display for UndeclaredWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wrote {:?} without declaring it, the change was discarded", self.agent, self.field)
+    }
+}
+
+// 依賴圖：dependencies[j] 是代理 j 開始前必須完成的代理。
+// 後加入的代理讀取先加入的代理寫入的字段、寫入它讀取的字段、或寫入同一字段時，都要等待它完成
+pub fn dependency_graph(declarations: &[AgentDeclaration]) -> Result<Vec<Vec<usize>>, Vec<WriteConflict>> {
+    let mut dependencies: Vec<Vec<usize>> = vec![vec![]; declarations.len()];
+    let mut conflicts: Vec<WriteConflict> = vec![];
+
+    for (later_index, later) in declarations.iter().enumerate() {
+        for (earlier_index, earlier) in declarations[..later_index].iter().enumerate() {
+            let read_after_write: bool = later.reads.iter().any(|field| earlier.writes.contains(field));
+            let write_after_read: bool = later.writes.iter().any(|field| earlier.reads.contains(field));
+            let mut write_after_write: bool = false;
+            for field in later.writes.iter().filter(|field| earlier.writes.contains(field)) {
+                write_after_write = true;
+                if !later.reads.contains(field) {
+                    conflicts.push(WriteConflict {
+                        field: *field,
+                        first: earlier.name.clone(),
+                        second: later.name.clone(),
+                    });
+                }
+            }
+            if read_after_write || write_after_read || write_after_write {
+                dependencies[later_index].push(earlier_index);
+            }
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(dependencies)
+    } else {
+        Err(conflicts)
+    }
+}
+
+// 按每個代理的耗時計算關鍵路徑：返回路徑上的代理和路徑總耗時。
+// 依賴總是指向先加入的代理，所以加入順序就是拓撲順序
+pub fn critical_path(dependencies: &[Vec<usize>], durations_ms: &[u128]) -> (Vec<usize>, u128) {
+    let mut finish_ms: Vec<u128> = vec![0; dependencies.len()];
+    let mut previous: Vec<Option<usize>> = vec![None; dependencies.len()];
+    for (index, waits_for) in dependencies.iter().enumerate() {
+        let (start_ms, before): (u128, Option<usize>) = waits_for
+            .iter()
+            .map(|&dependency| (finish_ms[dependency], Some(dependency)))
+            .max_by_key(|(finished, _)| *finished)
+            .unwrap_or((0, None));
+        finish_ms[index] = start_ms + durations_ms[index];
+        previous[index] = before;
+    }
+
+    let Some(last) = (0..dependencies.len()).max_by_key(|&index| finish_ms[index]) else {
+        return (vec![], 0);
+    };
+    let mut path: Vec<usize> = vec![last];
+    while let Some(before) = previous[*path.last().unwrap()] {
+        path.push(before);
+    }
+    path.reverse();
+    (path, finish_ms[last])
+}
+
+// 一個代理的運行時間（相對調度開始的毫秒數）
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentTiming {
+    pub name: String,
+    pub started_ms: u128,
+    pub finished_ms: u128,
+}
+
+// 一次調度的結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleReport {
+    pub timings: Vec<AgentTiming>,
+    pub critical_path: Vec<String>,
+    pub critical_path_ms: u128,
+    pub undeclared_writes: Vec<UndeclaredWrite>,
+}
+
+impl ScheduleReport {
+    // 一行關鍵路徑描述
+    pub fn describe_critical_path(&self) -> String {
+        format!("{} ({} ms)", self.critical_path.join(" -> "), self.critical_path_ms)
+    }
+}
+
+// 代理的結果；代理被取消時結果是 None，代理本身仍然會被傳回
+type AgentOutcome = (usize, Box<dyn SpecialFunctions>, FactSheet, Option<Result<(), Box<dyn Error + Send + Sync>>>);
+
+// 代理的錯誤需要跨任務傳回；RunError 保持原樣以便得到正確的退出碼
+fn sendable(error: Box<dyn Error>) -> Box<dyn Error + Send + Sync> {
+    match error.downcast::<RunError>() {
+        Ok(run_error) => run_error,
+        Err(other) => other.to_string().into(),
+    }
+}
+
+// 按依賴圖運行所有代理。任何代理失敗或超出預算時取消仍在運行的代理並返回錯誤；
+// 所有代理（包括被取消和沒有開始的）都會放回 agents 中
+pub async fn run_agents(
+    agents: &mut Vec<Box<dyn SpecialFunctions>>,
+    factsheet: &mut FactSheet,
+) -> Result<ScheduleReport, Box<dyn Error>> {
+    let declarations: Vec<AgentDeclaration> = agents.iter().map(|agent| AgentDeclaration::of(agent.as_ref())).collect();
+    let dependencies: Vec<Vec<usize>> = dependency_graph(&declarations).map_err(|conflicts| {
+        let described: Vec<String> = conflicts.iter().map(|conflict| conflict.to_string()).collect();
+        format!("conflicting writes: {}", described.join("; "))
+    })?;
+
+    let mut waiting: Vec<Option<Box<dyn SpecialFunctions>>> = agents.drain(..).map(Some).collect();
+    let mut finished: Vec<Option<Box<dyn SpecialFunctions>>> = (0..declarations.len()).map(|_| None).collect();
+    let mut timings: Vec<Option<AgentTiming>> = vec![None; declarations.len()];
+    let mut snapshots_before: Vec<FactSheet> = vec![FactSheet::default(); declarations.len()];
+    let mut undeclared_writes: Vec<UndeclaredWrite> = vec![];
+    let (cancel, cancelled): (watch::Sender<bool>, watch::Receiver<bool>) = watch::channel(false);
+    let mut running: JoinSet<AgentOutcome> = JoinSet::new();
+    let mut failure: Option<Box<dyn Error + Send + Sync>> = None;
+    let clock: Instant = Instant::now();
+
+    loop {
+        // 依賴都已完成的代理帶著當前 FactSheet 的副本開始運行
+        while failure.is_none() {
+            let Some(index) = (0..waiting.len()).find(|&index| {
+                waiting[index].is_some() && dependencies[index].iter().all(|&dependency| finished[dependency].is_some())
+            }) else {
+                break;
+            };
+            if let Err(exceeded) = check_run_budget() {
+                failure = Some(Box::new(exceeded));
+                cancel.send_replace(true);
+                break;
+            }
+
+            let mut agent: Box<dyn SpecialFunctions> = waiting[index].take().unwrap();
+            let mut snapshot: FactSheet = factsheet.clone();
+            snapshots_before[index] = factsheet.clone();
+            timings[index] = Some(AgentTiming {
+                name: declarations[index].name.clone(),
+                started_ms: clock.elapsed().as_millis(),
+                finished_ms: 0,
+            });
+            publish(AgentEvent::AgentStarted { agent: declarations[index].name.clone() });
+            let mut cancelled: watch::Receiver<bool> = cancelled.clone();
+            running.spawn(async move {
+                let result: Option<Result<(), Box<dyn Error + Send + Sync>>> = tokio::select! {
+                    result = agent.execute(&mut snapshot) => Some(result.map_err(sendable)),
+                    _ = cancelled.wait_for(|cancelled| *cancelled) => None,
+                };
+                (index, agent, snapshot, result)
+            });
+        }
+
+        let Some(joined) = running.join_next().await else {
+            break;
+        };
+        let (index, agent, snapshot, result): AgentOutcome = match joined {
+            Ok(outcome) => outcome,
+            Err(join_error) => std::panic::resume_unwind(join_error.into_panic()),
+        };
+        let Some(result) = result else {
+            waiting[index] = Some(agent);
+            continue;
+        };
+
+        let before: &FactSheet = &snapshots_before[index];
+        for field in snapshot.changed_fields(before) {
+            if !declarations[index].writes.contains(&field) {
+                undeclared_writes.push(UndeclaredWrite {
+                    field,
+                    agent: declarations[index].name.clone(),
+                });
+            }
+        }
+        let approvals_before: usize = before.approvals.as_ref().map_or(0, Vec::len);
+        if let Some(timing) = timings[index].as_mut() {
+            timing.finished_ms = clock.elapsed().as_millis();
+        }
+        match result {
+            Ok(()) => {
+                factsheet.merge_from(&snapshot, &declarations[index].writes, approvals_before);
+                finished[index] = Some(agent);
+            }
+            // 失敗的代理寫了一半的字段不合併，只保留它的審批記錄
+            Err(e) => {
+                factsheet.merge_from(&snapshot, &[], approvals_before);
+                waiting[index] = Some(agent);
+                if failure.is_none() {
+                    failure = Some(e);
+                    cancel.send_replace(true);
+                }
+            }
+        }
+    }
+
+    for (done, pending) in finished.into_iter().zip(waiting) {
+        agents.extend(done.or(pending));
+    }
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    let durations_ms: Vec<u128> = timings
+        .iter()
+        .map(|timing| timing.as_ref().map_or(0, |timing| timing.finished_ms - timing.started_ms))
+        .collect();
+    let (path, critical_path_ms): (Vec<usize>, u128) = critical_path(&dependencies, &durations_ms);
+    Ok(ScheduleReport {
+        timings: timings.into_iter().flatten().collect(),
+        critical_path: path.into_iter().map(|index| declarations[index].name.clone()).collect(),
+        critical_path_ms,
+        undeclared_writes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::{ApprovalGate, ApprovalRecord};
+    use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+    use crate::models::agent_basic::basic_traits::BasicTraits;
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    fn declare(name: &str, reads: &[FactField], writes: &[FactField]) -> AgentDeclaration {
+        AgentDeclaration {
+            name: name.to_string(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        }
+    }
+
+    // 等待一段時間後修改 FactSheet 的測試代理
+    #[derive(Debug)]
+    struct ScriptedAgent {
+        attributes: BasicAgent,
+        reads: Vec<FactField>,
+        writes: Vec<FactField>,
+        delay_ms: u64,
+        action: fn(&mut FactSheet) -> Result<(), String>,
+    }
+
+    impl ScriptedAgent {
+        fn boxed(
+            name: &str,
+            reads: &[FactField],
+            writes: &[FactField],
+            delay_ms: u64,
+            action: fn(&mut FactSheet) -> Result<(), String>,
+        ) -> Box<dyn SpecialFunctions> {
+            Box::new(Self {
+                attributes: BasicAgent::new("scripted".to_string(), name.to_string()),
+                reads: reads.to_vec(),
+                writes: writes.to_vec(),
+                delay_ms,
+                action,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for ScriptedAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn reads(&self) -> Vec<FactField> {
+            self.reads.clone()
+        }
+
+        fn writes(&self) -> Vec<FactField> {
+            self.writes.clone()
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), Box<dyn std::error::Error>> {
+            tokio::time::sleep(Duration::from_millis(self.delay_ms)).await;
+            (self.action)(factsheet)?;
+            self.attributes.update_state(AgentState::Finished)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tests_dependency_graph_and_conflicts() {
+        let declarations: Vec<AgentDeclaration> = vec![
+            declare("Architect", &[FactField::ProjectDescription], &[FactField::ProjectScope]),
+            declare("Backend", &[FactField::ProjectScope], &[FactField::BackendCode, FactField::ApiEndpointSchema]),
+            declare("Frontend", &[FactField::ApiEndpointSchema], &[FactField::ProjectFiles]),
+            declare("Docs", &[FactField::ApiEndpointSchema], &[]),
+        ];
+        assert_eq!(dependency_graph(&declarations), Ok(vec![vec![], vec![0], vec![1], vec![1]]));
+
+        let conflicting: Vec<AgentDeclaration> = vec![
+            declare("Backend", &[], &[FactField::BackendCode]),
+            declare("Rewriter", &[], &[FactField::BackendCode]),
+        ];
+        let conflicts: Vec<WriteConflict> = dependency_graph(&conflicting).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "Rewriter overwrites BackendCode written by Backend without reading it");
+    }
+
+    #[test]
+    fn tests_critical_path_follows_longest_chain() {
+        let dependencies: Vec<Vec<usize>> = vec![vec![], vec![0], vec![1], vec![1]];
+        assert_eq!(critical_path(&dependencies, &[10, 20, 5, 30]), (vec![0, 1, 3], 60));
+        assert_eq!(critical_path(&[], &[]), (vec![], 0));
+    }
+
+    #[tokio::test]
+    async fn tests_independent_agents_run_concurrently() {
+        let mut agents: Vec<Box<dyn SpecialFunctions>> = vec![
+            ScriptedAgent::boxed("Schema", &[], &[FactField::ApiEndpointSchema], 10, |factsheet| {
+                factsheet.api_endpoint_schema = Some(vec![]);
+                Ok(())
+            }),
+            ScriptedAgent::boxed("Frontend", &[FactField::ApiEndpointSchema], &[FactField::ProjectFiles], 150, |factsheet| {
+                factsheet.project_description.push_str(" [ignored]");
+                Ok(())
+            }),
+            ScriptedAgent::boxed("Docs", &[FactField::ApiEndpointSchema], &[FactField::BackendCode], 150, |factsheet| {
+                factsheet.backend_code = Some(format!("docs for {} endpoints", factsheet.api_endpoint_schema.as_ref().unwrap().len()));
+                Ok(())
+            }),
+        ];
+        let mut factsheet: FactSheet = FactSheet {
+            project_description: "todo app".to_string(),
+            ..FactSheet::default()
+        };
+
+        let report: ScheduleReport = run_agents(&mut agents, &mut factsheet).await.unwrap();
+
+        // Frontend 和 Docs 都在 Schema 完成之後開始，並且彼此的運行時間重疊
+        let [schema, frontend, docs] = report.timings.as_slice() else {
+            panic!("expected three timings, got {:?}", report.timings);
+        };
+        assert!(frontend.started_ms >= schema.finished_ms && docs.started_ms >= schema.finished_ms);
+        assert!(frontend.started_ms < docs.finished_ms && docs.started_ms < frontend.finished_ms);
+        assert_eq!(agents.len(), 3);
+        assert_eq!(factsheet.backend_code.as_deref(), Some("docs for 0 endpoints"));
+        // 未聲明寫入的字段不會被合併，並作為衝突報告
+        assert_eq!(factsheet.project_description, "todo app");
+        assert_eq!(
+            report.undeclared_writes,
+            vec![UndeclaredWrite {
+                field: FactField::ProjectDescription,
+                agent: "Frontend".to_string(),
+            }]
+        );
+        assert_eq!(
+            report.undeclared_writes[0].to_string(),
+            "Frontend wrote ProjectDescription without declaring it, the change was discarded"
+        );
+        assert_eq!(report.critical_path.len(), 2);
+        assert_eq!(report.critical_path[0], "Schema");
+    }
+
+    #[tokio::test]
+    async fn tests_failure_cancels_running_agents() {
+        let mut agents: Vec<Box<dyn SpecialFunctions>> = vec![
+            ScriptedAgent::boxed("Fails", &[], &[FactField::ApiSamples], 10, |_| Err("broken".to_string())),
+            ScriptedAgent::boxed("Slow", &[], &[FactField::BackendCode], 5_000, |factsheet| {
+                factsheet.backend_code = Some("late".to_string());
+                Ok(())
+            }),
+        ];
+        let mut factsheet: FactSheet = FactSheet::default();
+
+        let started: Instant = Instant::now();
+        let err: Box<dyn Error> = run_agents(&mut agents, &mut factsheet).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "broken");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(factsheet.backend_code.is_none());
+        // 被取消的代理也會被放回
+        let names: Vec<&str> = agents.iter().map(|agent| agent.get_attributes_from_agent().position.as_str()).collect();
+        assert_eq!(names, vec!["Fails", "Slow"]);
+    }
+
+    #[tokio::test]
+    async fn tests_failed_agent_writes_are_not_merged() {
+        let mut agents: Vec<Box<dyn SpecialFunctions>> = vec![ScriptedAgent::boxed(
+            "Fails",
+            &[],
+            &[FactField::BackendCode],
+            0,
+            |factsheet| {
+                factsheet.backend_code = Some("half written".to_string());
+                factsheet.approvals.get_or_insert_with(Vec::new).push(ApprovalRecord {
+                    gate: ApprovalGate::BeforeBuild,
+                    detail: "cargo build".to_string(),
+                    approved: false,
+                    decided_by: "policy".to_string(),
+                    reason: "auto-denied by policy".to_string(),
+                });
+                Err("approval denied".to_string())
+            },
+        )];
+        let mut factsheet: FactSheet = FactSheet {
+            backend_code: Some("fn main() {}".to_string()),
+            ..FactSheet::default()
+        };
+
+        let err: Box<dyn Error> = run_agents(&mut agents, &mut factsheet).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "approval denied");
+        assert_eq!(factsheet.backend_code.as_deref(), Some("fn main() {}"));
+        let gates: Vec<ApprovalGate> = factsheet.approvals.unwrap().iter().map(|record| record.gate).collect();
+        assert_eq!(gates, vec![ApprovalGate::BeforeBuild]);
+    }
+}
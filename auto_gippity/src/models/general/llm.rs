@@ -1,9 +1,46 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    // 模型請求的工具調用（assistant 消息）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    // 工具結果對應的調用（tool 消息）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+// 提供給模型的工具（函數調用模式）
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolSchema {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionSchema,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FunctionSchema {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value, // JSON Schema
+}
+
+// 模型請求的一次工具調用，arguments 是 JSON 字符串
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: String,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -11,11 +48,17 @@ pub struct ChatCompletion {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolSchema>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct APIMessage {
-    pub content: String,
+    // 只有工具調用時 content 為 null
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,7 +66,47 @@ pub struct APIChoice {
     pub message: APIMessage,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct APIUsage {
+    pub total_tokens: u64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct APIResponse {
     pub choices: Vec<APIChoice>,
+    pub usage: Option<APIUsage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_tool_calls_round_trip() {
+        let response: APIResponse = serde_json::from_str(
+            r#"{"choices":[{"message":{"role":"assistant","content":null,"tool_calls":[
+                {"id":"call_1","type":"function","function":{"name":"read_file","arguments":"{\"path\":\"src/main.rs\"}"}}
+            ]}}],"usage":{"total_tokens":42}}"#,
+        )
+        .unwrap();
+        let message: &APIMessage = &response.choices[0].message;
+        assert_eq!(message.content, None);
+        let calls: &Vec<ToolCall> = message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].function.name, "read_file");
+
+        let completion: ChatCompletion = ChatCompletion {
+            model: "gpt-4o".to_string(),
+            messages: vec![Message {
+                role: "assistant".to_string(),
+                tool_calls: Some(calls.clone()),
+                ..Message::default()
+            }],
+            temperature: 0.1,
+            tools: vec![],
+        };
+        let sent: Value = serde_json::to_value(&completion).unwrap();
+        assert!(sent.get("tools").is_none());
+        assert_eq!(sent["messages"][0]["tool_calls"][0]["id"], "call_1");
+        assert!(sent["messages"][0].get("tool_call_id").is_none());
+    }
 }
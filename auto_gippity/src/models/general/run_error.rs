@@ -0,0 +1,114 @@
+// 定義整個流水線可能的失敗類型，以及無人值守模式下對應的進程退出碼
+use crate::helpers::approvals::ApprovalDenied;
+use std::fmt;
+
+// 進程退出碼
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_OTHER_ERROR: i32 = 1;
+pub const EXIT_BUILD_FAILED: i32 = 2;
+pub const EXIT_TEST_FAILED: i32 = 3;
+pub const EXIT_BUDGET_EXCEEDED: i32 = 4;
+pub const EXIT_POLICY_DENIED: i32 = 5;
+pub const EXIT_LLM_UNAVAILABLE: i32 = 6;
+pub const EXIT_MALFORMED_REPLY: i32 = 7;
+pub const EXIT_USAGE: i32 = 64;
+
+// 定義 RunError 枚舉，表示流水線失敗的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunError {
+    BuildFailed(String),
+    TestFailed(String),
+    BudgetExceeded { used: u64, limit: u64 },
+    PolicyDenied(ApprovalDenied),
+    LlmUnavailable(String),
+    MalformedReply(String),
+}
+
+impl RunError {
+    // 對應的進程退出碼
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::BuildFailed(_) => EXIT_BUILD_FAILED,
+            Self::TestFailed(_) => EXIT_TEST_FAILED,
+            Self::BudgetExceeded { .. } => EXIT_BUDGET_EXCEEDED,
+            Self::PolicyDenied(_) => EXIT_POLICY_DENIED,
+            Self::LlmUnavailable(_) => EXIT_LLM_UNAVAILABLE,
+            Self::MalformedReply(_) => EXIT_MALFORMED_REPLY,
+        }
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BuildFailed(reason) => write!(f, "build failed: {}", reason),
+            Self::TestFailed(reason) => write!(f, "tests failed: {}", reason),
+            Self::BudgetExceeded { used, limit } => {
+                write!(f, "token budget exceeded: used {} of {}", used, limit)
+            }
+            Self::PolicyDenied(denied) => write!(f, "{}", denied),
+            Self::LlmUnavailable(reason) => write!(f, "LLM unavailable: {}", reason),
+            Self::MalformedReply(reason) => write!(f, "malformed LLM reply: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+impl From<ApprovalDenied> for RunError {
+    fn from(denied: ApprovalDenied) -> Self {
+        Self::PolicyDenied(denied)
+    }
+}
+
+// 根據代理返回的錯誤決定退出碼；不認識的錯誤返回 EXIT_OTHER_ERROR
+pub fn exit_code_for(error: &(dyn std::error::Error + 'static)) -> i32 {
+    if let Some(run_error) = error.downcast_ref::<RunError>() {
+        run_error.exit_code()
+    } else if error.downcast_ref::<ApprovalDenied>().is_some() {
+        EXIT_POLICY_DENIED
+    } else {
+        EXIT_OTHER_ERROR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::ApprovalGate;
+
+    #[test]
+    fn tests_exit_codes_are_distinct() {
+        let denied: ApprovalDenied = ApprovalDenied {
+            gate: ApprovalGate::BeforeRun,
+            reason: "denied by user".to_string(),
+        };
+        let errors: Vec<Box<dyn std::error::Error>> = vec![
+            Box::new(RunError::BuildFailed("E0425".to_string())),
+            Box::new(RunError::TestFailed("/task".to_string())),
+            Box::new(RunError::BudgetExceeded { used: 10, limit: 5 }),
+            Box::new(RunError::from(denied.clone())),
+            Box::new(denied),
+            Box::new(RunError::LlmUnavailable("timed out".to_string())),
+            Box::new(RunError::MalformedReply("expected a JSON list".to_string())),
+            "something else".into(),
+        ];
+        let codes: Vec<i32> = errors.iter().map(|e| exit_code_for(e.as_ref())).collect();
+
+        assert_eq!(
+            codes,
+            vec![
+                EXIT_BUILD_FAILED,
+                EXIT_TEST_FAILED,
+                EXIT_BUDGET_EXCEEDED,
+                EXIT_POLICY_DENIED,
+                EXIT_POLICY_DENIED,
+                EXIT_LLM_UNAVAILABLE,
+                EXIT_MALFORMED_REPLY,
+                EXIT_OTHER_ERROR
+            ]
+        );
+        assert!(!codes.contains(&EXIT_SUCCESS));
+        assert!(!codes.contains(&EXIT_USAGE));
+    }
+}
@@ -2,5 +2,6 @@ pub mod agent_basic;
 pub mod general;
 pub mod agents;
 pub mod agents_manager;
+pub mod tools;
 
 
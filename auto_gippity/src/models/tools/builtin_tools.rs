@@ -0,0 +1,479 @@
+// 內置工具：讀取文件、在工作目錄內寫文件、運行 cargo 子命令、按 URL 策略發送 HTTP GET
+
+use crate::helpers::approvals::ApprovalGate;
+use crate::models::agents::external_agent::resolve_workspace_path;
+use crate::models::tools::tool::{Tool, ToolBox, ToolContext, ToolPermission};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// cargo 工具允許的子命令
+pub const ALLOWED_CARGO_COMMANDS: [&str; 5] = ["build", "check", "test", "fmt", "clippy"];
+
+// cargo 工具允許的選項；--manifest-path、--config、--target-dir 等會讓 cargo 離開工作目錄或改變配置的選項都不在其中。
+// 不以 "-" 或 "+" 開頭的參數（例如測試名稱過濾）也允許
+pub const ALLOWED_CARGO_FLAGS: [&str; 16] = [
+    "--release",
+    "--all-targets",
+    "--lib",
+    "--bins",
+    "--tests",
+    "--quiet",
+    "-q",
+    "--verbose",
+    "-v",
+    "--offline",
+    "--locked",
+    "--all-features",
+    "--no-default-features",
+    "--check",
+    "--",
+    "--nocapture",
+];
+
+// HTTP GET 的超時
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// HTTP GET 最多讀取的響應體字節數，超出的部分丟棄
+pub const MAX_HTTP_BODY_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct PathArgs {
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WriteArgs {
+    path: String,
+    contents: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoArgs {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlArgs {
+    url: String,
+}
+
+fn parse_args<T: for<'de> Deserialize<'de>>(arguments: Value) -> Result<T, String> {
+    serde_json::from_value(arguments).map_err(|e| format!("invalid arguments: {}", e))
+}
+
+impl CargoArgs {
+    // 檢查子命令和選項是否在允許列表中
+    fn check(&self) -> Result<(), String> {
+        if !ALLOWED_CARGO_COMMANDS.contains(&self.command.as_str()) {
+            return Err(format!(
+                "cargo {} is not allowed (allowed: {})",
+                self.command,
+                ALLOWED_CARGO_COMMANDS.join(", ")
+            ));
+        }
+        let rejected: Vec<&str> = self
+            .args
+            .iter()
+            .map(String::as_str)
+            .filter(|arg| (arg.starts_with('-') || arg.starts_with('+')) && !ALLOWED_CARGO_FLAGS.contains(arg))
+            .collect();
+        if !rejected.is_empty() {
+            return Err(format!(
+                "cargo option(s) {} are not allowed (allowed: {})",
+                rejected.join(", "),
+                ALLOWED_CARGO_FLAGS.join(" ")
+            ));
+        }
+        Ok(())
+    }
+
+    // 構建類的命令需要通過 BeforeBuild，cargo test 會運行生成的代碼，需要通過 BeforeRun；
+    // cargo fmt 會就地改寫源文件，需要通過 BeforeOverwriteFiles
+    fn gate(&self) -> Option<ApprovalGate> {
+        match self.command.as_str() {
+            "build" | "check" | "clippy" => Some(ApprovalGate::BeforeBuild),
+            "test" => Some(ApprovalGate::BeforeRun),
+            "fmt" => Some(ApprovalGate::BeforeOverwriteFiles),
+            _ => None,
+        }
+    }
+}
+
+// 讀取工作目錄中的文件
+#[derive(Debug)]
+pub struct ReadFileTool;
+
+#[async_trait]
+impl Tool for ReadFileTool {
+    fn name(&self) -> &'static str {
+        "read_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read a text file from the project workspace. The path is relative to the project root."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"path": {"type": "string", "description": "Relative path, e.g. src/main.rs"}},
+            "required": ["path"]
+        })
+    }
+
+    fn permissions(&self) -> Vec<ToolPermission> {
+        vec![ToolPermission::ReadFiles]
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<String, String> {
+        let args: PathArgs = parse_args(arguments)?;
+        let path: PathBuf = resolve_workspace_path(&context.workspace, &args.path)?;
+        std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", args.path, e))
+    }
+}
+
+// 在工作目錄內寫文件
+#[derive(Debug)]
+pub struct WriteFileTool;
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    fn name(&self) -> &'static str {
+        "write_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create or replace a text file in the project workspace. The path is relative to the project root."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "Relative path, e.g. src/handlers.rs"},
+                "contents": {"type": "string", "description": "Full file contents"}
+            },
+            "required": ["path", "contents"]
+        })
+    }
+
+    fn permissions(&self) -> Vec<ToolPermission> {
+        vec![ToolPermission::WriteFiles]
+    }
+
+    fn approval(&self, arguments: &Value) -> Option<(ApprovalGate, String)> {
+        let path: &str = arguments.get("path").and_then(Value::as_str)?;
+        Some((ApprovalGate::BeforeOverwriteFiles, format!("write_file {}", path)))
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<String, String> {
+        let args: WriteArgs = parse_args(arguments)?;
+        let path: PathBuf = resolve_workspace_path(&context.workspace, &args.path)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", args.path, e))?;
+        }
+        std::fs::write(&path, args.contents.as_bytes()).map_err(|e| format!("{}: {}", args.path, e))?;
+        Ok(format!("wrote {} bytes to {}", args.contents.len(), args.path))
+    }
+}
+
+// 在工作目錄中運行 cargo 子命令
+#[derive(Debug)]
+pub struct CargoTool;
+
+#[async_trait]
+impl Tool for CargoTool {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a cargo command (build, check, test, fmt or clippy) in the project workspace and return its output."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": {"type": "string", "enum": ALLOWED_CARGO_COMMANDS},
+                "args": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn permissions(&self) -> Vec<ToolPermission> {
+        vec![ToolPermission::RunCommands]
+    }
+
+    // 不允許的命令不詢問用戶，直接在 call 中拒絕
+    fn approval(&self, arguments: &Value) -> Option<(ApprovalGate, String)> {
+        let args: CargoArgs = parse_args(arguments.clone()).ok()?;
+        args.check().ok()?;
+        let detail: String = format!("cargo {} {}", args.command, args.args.join(" "));
+        args.gate().map(|gate| (gate, detail.trim_end().to_string()))
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<String, String> {
+        let args: CargoArgs = parse_args(arguments)?;
+        args.check()?;
+
+        let mut command: tokio::process::Command = tokio::process::Command::new("cargo");
+        command
+            .arg(&args.command)
+            .args(&args.args)
+            .current_dir(&context.workspace)
+            .kill_on_drop(true);
+        if let Some(target_dir) = &context.cargo_target_dir {
+            command.env("CARGO_TARGET_DIR", target_dir);
+        }
+        let output: std::process::Output = command
+            .output()
+            .await
+            .map_err(|e| format!("failed to run cargo {}: {}", args.command, e))?;
+
+        Ok(format!(
+            "exit status: {}\n{}{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+// 按 URL 策略發送 HTTP GET
+#[derive(Debug)]
+pub struct HttpGetTool;
+
+#[async_trait]
+impl Tool for HttpGetTool {
+    fn name(&self) -> &'static str {
+        "http_get"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send an HTTP GET request to a URL allowed by the URL policy and return the status and body."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"url": {"type": "string"}},
+            "required": ["url"]
+        })
+    }
+
+    fn permissions(&self) -> Vec<ToolPermission> {
+        vec![ToolPermission::Network]
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<String, String> {
+        let args: UrlArgs = parse_args(arguments)?;
+        let url: reqwest::Url = context.url_policy.evaluate(&args.url).await?;
+        let mut response: reqwest::Response = context
+            .url_policy
+            .build_client(HTTP_TIMEOUT)
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| format!("request failed: {}", e))?;
+        let status: u16 = response.status().as_u16();
+
+        // 分塊讀取響應體，超過上限時停止讀取
+        let mut body: Vec<u8> = vec![];
+        let mut truncated: bool = false;
+        while let Some(chunk) = response.chunk().await.map_err(|e| format!("failed to read body: {}", e))? {
+            let room: usize = MAX_HTTP_BODY_BYTES - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let mut text: String = format!("status: {}\n{}", status, String::from_utf8_lossy(&body));
+        if truncated {
+            text.push_str(format!("\n... (body truncated at {} bytes)", MAX_HTTP_BODY_BYTES).as_str());
+        }
+        Ok(text)
+    }
+}
+
+// 帶全部內置工具的工具箱；代理實際能用哪些取決於授予的權限
+pub fn builtin_toolbox(agent: &str, context: ToolContext, granted: &[ToolPermission]) -> ToolBox {
+    ToolBox::new(agent, context, granted)
+        .with_tool(Box::new(ReadFileTool))
+        .with_tool(Box::new(WriteFileTool))
+        .with_tool(Box::new(CargoTool))
+        .with_tool(Box::new(HttpGetTool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::{ApprovalPolicy, Approvals, GateDecision};
+    use crate::helpers::stub_server::{spawn_stub_server, StubResponse};
+    use crate::helpers::url_policy::UrlPolicy;
+    use crate::models::agent_basic::basic_agent::BasicAgent;
+    use crate::models::agent_basic::basic_traits::BasicTraits;
+    use crate::models::agents::agent_traits::FactSheet;
+    use crate::models::tools::tool::ToolError;
+
+    fn test_context(workspace: PathBuf) -> ToolContext {
+        ToolContext {
+            workspace,
+            url_policy: UrlPolicy {
+                allow_loopback: true,
+                ..UrlPolicy::default()
+            },
+            cargo_target_dir: None,
+        }
+    }
+
+    fn test_toolbox(agent: &str, workspace: PathBuf, granted: &[ToolPermission], decision: GateDecision) -> ToolBox {
+        builtin_toolbox(agent, test_context(workspace), granted)
+            .with_approvals(Approvals::new(ApprovalPolicy::uniform(decision)))
+    }
+
+    #[tokio::test]
+    async fn tests_permissions_are_enforced_per_agent() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_tools_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+        let mut agent: BasicAgent = BasicAgent::new("write docs".to_string(), "Writer".to_string());
+        let mut factsheet: FactSheet = FactSheet::default();
+
+        let reader: ToolBox =
+            test_toolbox("Reviewer", workspace.clone(), &[ToolPermission::ReadFiles], GateDecision::AutoApprove);
+        assert_eq!(reader.allowed_tools(), vec!["read_file"]);
+        assert_eq!(reader.schemas()[0].function.name, "read_file");
+        let denied: String = reader
+            .call(&mut agent, &mut factsheet, "write_file", r#"{"path": "notes.md", "contents": "hi"}"#)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(denied, "Reviewer is not permitted to use 'write_file' (missing [WriteFiles])");
+        assert!(!workspace.join("notes.md").exists());
+
+        let writer: ToolBox = test_toolbox(
+            "Writer",
+            workspace.clone(),
+            &[ToolPermission::ReadFiles, ToolPermission::WriteFiles],
+            GateDecision::AutoApprove,
+        );
+        writer
+            .call(&mut agent, &mut factsheet, "write_file", r#"{"path": "docs/notes.md", "contents": "hi"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            reader.call(&mut agent, &mut factsheet, "read_file", r#"{"path": "docs/notes.md"}"#).await.unwrap(),
+            "hi"
+        );
+        assert!(writer
+            .call(&mut agent, &mut factsheet, "write_file", r#"{"path": "../escape.md", "contents": "x"}"#)
+            .await
+            .is_err());
+        assert_eq!(
+            writer.call(&mut agent, &mut factsheet, "delete_file", "{}").await.unwrap_err().to_string(),
+            "unknown tool 'delete_file'"
+        );
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_risky_tool_calls_pass_approval_gates() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_tool_gates_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+        let mut agent: BasicAgent = BasicAgent::new("write docs".to_string(), "Writer".to_string());
+        let mut factsheet: FactSheet = FactSheet::default();
+
+        let granted: [ToolPermission; 2] = [ToolPermission::WriteFiles, ToolPermission::RunCommands];
+        let toolbox: ToolBox = test_toolbox("Writer", workspace.clone(), &granted, GateDecision::AutoDeny);
+        let denied: ToolError = toolbox
+            .call(&mut agent, &mut factsheet, "write_file", r#"{"path": "src/main.rs", "contents": "fn main() {}"}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(denied, ToolError::Denied(ref denied) if denied.gate == ApprovalGate::BeforeOverwriteFiles));
+        assert!(!workspace.join("src/main.rs").exists());
+
+        let denied: ToolError = toolbox
+            .call(&mut agent, &mut factsheet, "cargo", r#"{"command": "test", "args": ["handlers"]}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(denied, ToolError::Denied(ref denied) if denied.gate == ApprovalGate::BeforeRun));
+
+        let denied: ToolError = toolbox
+            .call(&mut agent, &mut factsheet, "cargo", r#"{"command": "fmt"}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(denied, ToolError::Denied(ref denied) if denied.gate == ApprovalGate::BeforeOverwriteFiles));
+
+        // 不允許的命令直接拒絕，不請求審批
+        let rejected: ToolError = toolbox
+            .call(&mut agent, &mut factsheet, "cargo", r#"{"command": "build", "args": ["--manifest-path", "/etc/Cargo.toml"]}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(rejected, ToolError::Failed(_)));
+
+        let gates: Vec<ApprovalGate> = factsheet.approvals.unwrap().iter().map(|record| record.gate).collect();
+        assert_eq!(
+            gates,
+            vec![ApprovalGate::BeforeOverwriteFiles, ApprovalGate::BeforeRun, ApprovalGate::BeforeOverwriteFiles]
+        );
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_cargo_and_http_tools_check_their_inputs() {
+        let context: ToolContext = test_context(std::env::temp_dir());
+        let cargo: String = CargoTool
+            .call(json!({"command": "install", "args": ["ripgrep"]}), &context)
+            .await
+            .unwrap_err();
+        assert!(cargo.starts_with("cargo install is not allowed"));
+        for flag in ["--manifest-path=/tmp/Cargo.toml", "--config", "--target-dir", "-Zunstable-options", "+nightly"] {
+            let cargo: String = CargoTool
+                .call(json!({"command": "build", "args": [flag, "x"]}), &context)
+                .await
+                .unwrap_err();
+            assert!(cargo.starts_with(format!("cargo option(s) {} are not allowed", flag).as_str()), "{}", cargo);
+        }
+
+        let large_body: String = "x".repeat(MAX_HTTP_BODY_BYTES + 10);
+        let base_url: String = spawn_stub_server(vec![
+            ("/health", StubResponse::json(200, r#"{"ok":true}"#)),
+            ("/large", StubResponse::json(200, large_body.as_str())),
+        ])
+        .await;
+        let body: String = HttpGetTool
+            .call(json!({"url": format!("{}/health", base_url)}), &context)
+            .await
+            .unwrap();
+        assert_eq!(body, "status: 200\n{\"ok\":true}");
+
+        let body: String = HttpGetTool
+            .call(json!({"url": format!("{}/large", base_url)}), &context)
+            .await
+            .unwrap();
+        assert!(body.ends_with(format!("\n... (body truncated at {} bytes)", MAX_HTTP_BODY_BYTES).as_str()));
+        assert_eq!(body.matches('x').count(), MAX_HTTP_BODY_BYTES);
+
+        let strict: ToolContext = ToolContext {
+            url_policy: UrlPolicy::default(),
+            ..context
+        };
+        assert!(HttpGetTool
+            .call(json!({"url": format!("{}/health", base_url)}), &strict)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod builtin_tools;
+pub mod tool;
+pub mod tool_loop;
+
+#[cfg(test)]
+pub mod scripted_chat;
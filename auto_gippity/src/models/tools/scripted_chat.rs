@@ -0,0 +1,61 @@
+// 測試用的模型：按腳本依次回答，並記錄每次收到的工具列表
+
+use crate::models::general::llm::{APIMessage, FunctionCall, Message, ToolCall, ToolSchema};
+use crate::models::tools::tool_loop::ChatModel;
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+#[derive(Debug)]
+pub struct ScriptedChat {
+    replies: Mutex<Vec<APIMessage>>,
+    offered: Mutex<Vec<Vec<String>>>,
+}
+
+impl ScriptedChat {
+    pub fn new(mut replies: Vec<APIMessage>) -> Self {
+        replies.reverse();
+        Self {
+            replies: Mutex::new(replies),
+            offered: Mutex::new(vec![]),
+        }
+    }
+
+    // 每次調用時提供給模型的工具名稱
+    pub fn offered(&self) -> Vec<Vec<String>> {
+        self.offered.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ChatModel for ScriptedChat {
+    async fn complete(&self, _messages: &[Message], tools: &[ToolSchema]) -> Result<APIMessage, String> {
+        self.offered
+            .lock()
+            .unwrap()
+            .push(tools.iter().map(|tool| tool.function.name.clone()).collect());
+        self.replies.lock().unwrap().pop().ok_or("script exhausted".to_string())
+    }
+}
+
+// 只包含一次工具調用的回答
+pub fn tool_call(id: &str, name: &str, arguments: &str) -> APIMessage {
+    APIMessage {
+        content: None,
+        tool_calls: Some(vec![ToolCall {
+            id: id.to_string(),
+            kind: "function".to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }]),
+    }
+}
+
+// 最終回答
+pub fn final_answer(content: &str) -> APIMessage {
+    APIMessage {
+        content: Some(content.to_string()),
+        tool_calls: None,
+    }
+}
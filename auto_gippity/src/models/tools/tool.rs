@@ -0,0 +1,228 @@
+// 代理可以調用的工具：每個工具聲明自己需要的權限，工具箱只向模型提供代理被授予權限的工具，
+// 並在調用時再次檢查權限；有風險的調用（寫文件、構建、運行）還要先通過對應的審批關卡。
+// 工具的參數是模型給出的 JSON，結果是發回給模型的文本。
+
+use crate::helpers::approvals::{ApprovalDenied, ApprovalGate, Approvals};
+use crate::helpers::event_bus::{publish, AgentEvent};
+use crate::helpers::url_policy::UrlPolicy;
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agents::agent_traits::FactSheet;
+use crate::models::general::llm::{FunctionSchema, ToolSchema};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt::{self, Debug};
+use std::path::PathBuf;
+
+// 工具結果發回模型前截斷的長度
+pub const MAX_TOOL_OUTPUT_CHARS: usize = 8000;
+
+// 工具需要的權限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolPermission {
+    ReadFiles,
+    WriteFiles,
+    RunCommands,
+    Network,
+}
+
+// 工具調用失敗的原因：Failed 作為結果發回模型，Denied 表示用戶或策略拒絕了審批，調用方應停止
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolError {
+    Failed(String),
+    Denied(ApprovalDenied),
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(message) => write!(f, "{}", message),
+            Self::Denied(denied) => write!(f, "{}", denied),
+        }
+    }
+}
+
+impl From<String> for ToolError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+// 工具運行的環境：文件操作限制在 workspace 內，網絡請求受 URL 策略約束
+#[derive(Debug, Clone)]
+pub struct ToolContext {
+    pub workspace: PathBuf,
+    pub url_policy: UrlPolicy,
+    pub cargo_target_dir: Option<PathBuf>,
+}
+
+impl ToolContext {
+    pub fn new(workspace: PathBuf) -> Self {
+        Self {
+            workspace,
+            url_policy: UrlPolicy::load_default(),
+            cargo_target_dir: None,
+        }
+    }
+}
+
+// 工具
+#[async_trait]
+pub trait Tool: Debug + Send + Sync {
+    // 模型看到的名稱和說明
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+
+    // 參數的 JSON Schema
+    fn parameters(&self) -> Value;
+
+    // 調用工具需要的權限
+    fn permissions(&self) -> Vec<ToolPermission>;
+
+    // 調用前需要通過的審批關卡和顯示給用戶的說明；不需要審批時返回 None
+    fn approval(&self, _arguments: &Value) -> Option<(ApprovalGate, String)> {
+        None
+    }
+
+    // 調用工具；錯誤會作為結果發回模型
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<String, String>;
+
+    // 提供給模型的工具描述
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            kind: "function".to_string(),
+            function: FunctionSchema {
+                name: self.name().to_string(),
+                description: self.description().to_string(),
+                parameters: self.parameters(),
+            },
+        }
+    }
+}
+
+// 截斷過長的工具輸出
+pub fn truncate_output(output: &str) -> String {
+    if output.chars().count() <= MAX_TOOL_OUTPUT_CHARS {
+        return output.to_string();
+    }
+    let kept: String = output.chars().take(MAX_TOOL_OUTPUT_CHARS).collect();
+    format!("{}\n... (truncated)", kept)
+}
+
+// 代理的工具箱：可用的工具和代理被授予的權限
+#[derive(Debug)]
+pub struct ToolBox {
+    agent: String,
+    tools: Vec<Box<dyn Tool>>,
+    granted: BTreeSet<ToolPermission>,
+    approvals: Approvals,
+    pub context: ToolContext,
+}
+
+impl ToolBox {
+    pub fn new(agent: &str, context: ToolContext, granted: &[ToolPermission]) -> Self {
+        Self {
+            agent: agent.to_string(),
+            tools: vec![],
+            granted: granted.iter().copied().collect(),
+            approvals: Approvals::load_default(),
+            context,
+        }
+    }
+
+    // 替換審批者（例如測試）
+    #[cfg(test)]
+    pub fn with_approvals(mut self, approvals: Approvals) -> Self {
+        self.approvals = approvals;
+        self
+    }
+
+    // 加入一個工具；同名的工具會被替換
+    pub fn add(&mut self, tool: Box<dyn Tool>) {
+        self.tools.retain(|existing| existing.name() != tool.name());
+        self.tools.push(tool);
+    }
+
+    pub fn with_tool(mut self, tool: Box<dyn Tool>) -> Self {
+        self.add(tool);
+        self
+    }
+
+    // 工具需要但代理沒有被授予的權限
+    fn missing_permissions(&self, tool: &dyn Tool) -> Vec<ToolPermission> {
+        tool.permissions()
+            .into_iter()
+            .filter(|permission| !self.granted.contains(permission))
+            .collect()
+    }
+
+    // 代理可以使用的工具名稱
+    pub fn allowed_tools(&self) -> Vec<&'static str> {
+        self.tools
+            .iter()
+            .filter(|tool| self.missing_permissions(tool.as_ref()).is_empty())
+            .map(|tool| tool.name())
+            .collect()
+    }
+
+    // 提供給模型的工具：只包含代理被授予權限的工具
+    pub fn schemas(&self) -> Vec<ToolSchema> {
+        self.tools
+            .iter()
+            .filter(|tool| self.missing_permissions(tool.as_ref()).is_empty())
+            .map(|tool| tool.schema())
+            .collect()
+    }
+
+    // 代理按名稱調用工具；arguments 是模型給出的 JSON 字符串。需要審批的調用先通過關卡，被拒絕時返回 Denied
+    pub async fn call(
+        &self,
+        agent: &mut BasicAgent,
+        factsheet: &mut FactSheet,
+        name: &str,
+        arguments: &str,
+    ) -> Result<String, ToolError> {
+        let tool: &dyn Tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.name() == name)
+            .map(|tool| tool.as_ref())
+            .ok_or_else(|| format!("unknown tool '{}'", name))?;
+
+        let missing: Vec<ToolPermission> = self.missing_permissions(tool);
+        if !missing.is_empty() {
+            publish(AgentEvent::ToolCalled {
+                agent: self.agent.clone(),
+                tool: name.to_string(),
+                allowed: false,
+                success: false,
+            });
+            return Err(format!("{} is not permitted to use '{}' (missing {:?})", self.agent, name, missing).into());
+        }
+
+        let arguments: Value = if arguments.trim().is_empty() {
+            Value::Object(Default::default())
+        } else {
+            serde_json::from_str(arguments).map_err(|e| format!("invalid arguments for '{}': {}", name, e))?
+        };
+        if let Some((gate, detail)) = tool.approval(&arguments) {
+            self.approvals
+                .request_for(agent, gate, detail.as_str(), factsheet)
+                .map_err(ToolError::Denied)?;
+        }
+        let outcome: Result<String, ToolError> = tool
+            .call(arguments, &self.context)
+            .await
+            .map(|output| truncate_output(&output))
+            .map_err(ToolError::Failed);
+        publish(AgentEvent::ToolCalled {
+            agent: self.agent.clone(),
+            tool: name.to_string(),
+            allowed: true,
+            success: outcome.is_ok(),
+        });
+        outcome
+    }
+}
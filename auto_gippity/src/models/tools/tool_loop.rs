@@ -0,0 +1,194 @@
+// 工具調用循環：把對話和可用的工具發給模型，執行模型請求的工具並把結果發回，
+// 直到模型不再調用工具（返回最終回答）或超出輪數。
+
+use crate::apis::call_request::call_gpt_with_tools;
+use crate::helpers::event_bus::{publish, AgentEvent};
+use crate::helpers::run_budget::check_run_budget;
+use crate::models::agent_basic::agent_memory::estimate_messages_tokens;
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agents::agent_traits::FactSheet;
+use crate::models::general::llm::{APIMessage, Message, ToolCall, ToolSchema};
+use crate::models::general::run_error::RunError;
+use crate::models::tools::tool::{ToolBox, ToolError};
+use async_trait::async_trait;
+use std::error::Error;
+use std::fmt::Debug;
+
+// 預設最多輪數
+pub const DEFAULT_MAX_TOOL_ROUNDS: usize = 8;
+
+// 支持工具調用的模型；測試中可以用按腳本回答的模型替換
+#[async_trait]
+pub trait ChatModel: Debug + Send + Sync {
+    async fn complete(&self, messages: &[Message], tools: &[ToolSchema]) -> Result<APIMessage, String>;
+}
+
+// OpenAI 聊天模型
+#[derive(Debug, Clone)]
+pub struct OpenAiChat {
+    pub model: String,
+}
+
+#[async_trait]
+impl ChatModel for OpenAiChat {
+    async fn complete(&self, messages: &[Message], tools: &[ToolSchema]) -> Result<APIMessage, String> {
+        call_gpt_with_tools(&self.model, messages.to_vec(), tools.to_vec())
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+// 運行工具調用循環；對話（包括工具調用和結果）追加到 messages，返回模型的最終回答。
+// 超出運行預算或工具調用的審批被拒絕時停止循環，並返回 RunError 以便得到正確的退出碼
+pub async fn run_tool_loop(
+    agent: &mut BasicAgent,
+    factsheet: &mut FactSheet,
+    chat: &dyn ChatModel,
+    toolbox: &ToolBox,
+    messages: &mut Vec<Message>,
+    max_rounds: usize,
+) -> Result<String, Box<dyn Error>> {
+    let tools: Vec<ToolSchema> = toolbox.schemas();
+
+    for _ in 0..max_rounds {
+        check_run_budget()?;
+        publish(AgentEvent::LlmRequest {
+            agent: agent.position.clone(),
+            operation: "tool_loop".to_string(),
+            prompt_tokens: estimate_messages_tokens(messages),
+        });
+        let reply: APIMessage = chat.complete(messages, &tools).await?;
+        let content: String = reply.content.unwrap_or_default();
+        publish(AgentEvent::LlmResponse {
+            agent: agent.position.clone(),
+            operation: "tool_loop".to_string(),
+            response_chars: content.chars().count(),
+        });
+
+        let tool_calls: Vec<ToolCall> = reply.tool_calls.unwrap_or_default();
+        messages.push(Message {
+            role: "assistant".to_string(),
+            content: content.clone(),
+            tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls.clone()) },
+            tool_call_id: None,
+        });
+        if tool_calls.is_empty() {
+            return Ok(content);
+        }
+
+        // 工具錯誤作為結果發回模型，讓模型自行調整
+        for call in tool_calls {
+            let result: String = match toolbox.call(agent, factsheet, &call.function.name, &call.function.arguments).await {
+                Ok(output) => output,
+                Err(ToolError::Denied(denied)) => return Err(Box::new(RunError::PolicyDenied(denied))),
+                Err(ToolError::Failed(error)) => format!("error: {}", error),
+            };
+            messages.push(Message {
+                role: "tool".to_string(),
+                content: result,
+                tool_calls: None,
+                tool_call_id: Some(call.id),
+            });
+        }
+    }
+
+    Err(format!("no final answer after {} tool round(s)", max_rounds).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::approvals::{ApprovalPolicy, Approvals, GateDecision};
+    use crate::models::agent_basic::basic_agent::AgentState;
+    use crate::models::agent_basic::basic_traits::BasicTraits;
+    use crate::models::tools::builtin_tools::builtin_toolbox;
+    use crate::models::tools::scripted_chat::{final_answer, tool_call, ScriptedChat};
+    use crate::models::tools::tool::{ToolContext, ToolPermission};
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn tests_loop_runs_tools_until_final_answer() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_tool_loop_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("Cargo.toml"), "[package]\nname = \"web_template\"\n").unwrap();
+
+        let toolbox: ToolBox = builtin_toolbox("Reviewer", ToolContext::new(workspace.clone()), &[ToolPermission::ReadFiles]);
+        let chat: ScriptedChat = ScriptedChat::new(vec![
+            tool_call("call_1", "read_file", r#"{"path": "Cargo.toml"}"#),
+            tool_call("call_2", "cargo", r#"{"command": "build"}"#),
+            final_answer("The package is web_template."),
+        ]);
+
+        let mut messages: Vec<Message> = vec![Message {
+            role: "user".to_string(),
+            content: "What is the package name?".to_string(),
+            ..Message::default()
+        }];
+        let mut agent: BasicAgent = BasicAgent::new("review".to_string(), "Reviewer".to_string());
+        let answer: String = run_tool_loop(
+            &mut agent,
+            &mut FactSheet::default(),
+            &chat,
+            &toolbox,
+            &mut messages,
+            DEFAULT_MAX_TOOL_ROUNDS,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(answer, "The package is web_template.");
+        assert_eq!(chat.offered()[0], vec!["read_file"]);
+        let roles: Vec<&str> = messages.iter().map(|message| message.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "assistant", "tool", "assistant"]);
+        assert_eq!(messages[2].tool_call_id.as_deref(), Some("call_1"));
+        assert!(messages[2].content.contains("web_template"));
+        assert!(messages[4].content.starts_with("error: Reviewer is not permitted to use 'cargo'"));
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_loop_stops_after_max_rounds() {
+        let toolbox: ToolBox = builtin_toolbox("Reviewer", ToolContext::new(std::env::temp_dir()), &[]);
+        let chat: ScriptedChat = ScriptedChat::new(vec![
+            tool_call("call_1", "read_file", "{}"),
+            tool_call("call_2", "read_file", "{}"),
+        ]);
+        let mut messages: Vec<Message> = vec![];
+        let mut agent: BasicAgent = BasicAgent::new("review".to_string(), "Reviewer".to_string());
+        let error: String = run_tool_loop(&mut agent, &mut FactSheet::default(), &chat, &toolbox, &mut messages, 2)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "no final answer after 2 tool round(s)");
+    }
+
+    #[tokio::test]
+    async fn tests_denied_approval_stops_the_loop() {
+        let workspace: PathBuf = std::env::temp_dir().join(format!("auto_gippity_tool_denied_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(&workspace).unwrap();
+
+        let toolbox: ToolBox = builtin_toolbox("Writer", ToolContext::new(workspace.clone()), &[ToolPermission::WriteFiles])
+            .with_approvals(Approvals::new(ApprovalPolicy::uniform(GateDecision::AutoDeny)));
+        let chat: ScriptedChat = ScriptedChat::new(vec![
+            tool_call("call_1", "write_file", r#"{"path": "README.md", "contents": "x"}"#),
+            final_answer("Wrote README.md"),
+        ]);
+        let mut agent: BasicAgent = BasicAgent::new("write docs".to_string(), "Writer".to_string());
+        agent.update_state(AgentState::Working).unwrap();
+        let mut factsheet: FactSheet = FactSheet::default();
+        let mut messages: Vec<Message> = vec![];
+
+        let error: Box<dyn Error> = run_tool_loop(&mut agent, &mut factsheet, &chat, &toolbox, &mut messages, 4)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "approval denied at BeforeOverwriteFiles: auto-denied by policy");
+        assert!(matches!(error.downcast_ref::<RunError>(), Some(RunError::PolicyDenied(_))));
+        assert!(!workspace.join("README.md").exists());
+        assert_eq!(factsheet.approvals.map(|records| records.len()), Some(1));
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+}
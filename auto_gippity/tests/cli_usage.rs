@@ -0,0 +1,35 @@
+// 參數錯誤時主程序打印用法並以 EX_USAGE（64）退出，而不是 panic
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_auto_gippity")).args(args).output().unwrap()
+}
+
+#[test]
+fn tests_bad_arguments_print_usage() {
+    for args in [
+        vec!["--bogus"],
+        vec!["--max-steps", "ten"],
+        vec!["--max-tokens"],
+        vec!["--pipeline"],
+        vec!["Issue", "Agent"],
+    ] {
+        let output: Output = run(&args);
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+        assert_eq!(output.status.code(), Some(64), "{:?}: {}", args, stderr);
+        assert!(stderr.starts_with("error: "), "{:?}: {}", args, stderr);
+        assert!(stderr.contains("usage:"), "{:?}: {}", args, stderr);
+        assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+    }
+
+    let output: Output = run(&["--max-steps", "ten"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--max-steps must be a number, got 'ten'"));
+}
+
+#[test]
+fn tests_help_prints_usage() {
+    let output: Output = run(&["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("usage:"));
+}
@@ -0,0 +1,94 @@
+// 以最小的主程序驅動示例外部代理，檢查 stdio JSON-RPC 協議的消息順序
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+fn send(stdin: &mut ChildStdin, message: Value) {
+    writeln!(stdin, "{}", message).unwrap();
+    stdin.flush().unwrap();
+}
+
+fn read(lines: &mut std::io::Lines<BufReader<ChildStdout>>) -> Value {
+    serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+}
+
+fn run_request() -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "run",
+        "params": {
+            "agent": {"position": "Docs Writer", "objective": "Document the API"},
+            "reads": ["api_endpoint_schema"],
+            "writes": ["project_description"],
+            "factsheet": {
+                "project_description": "todo app",
+                "api_endpoint_schema": [
+                    {"is_route_dynamic": "false", "method": "get", "request_body": null, "response": null, "route": "/tasks"}
+                ]
+            }
+        }
+    })
+}
+
+#[test]
+fn tests_example_agent_writes_docs_and_returns_patch() {
+    let mut child: Child = Command::new(env!("CARGO_BIN_EXE_example_external_agent"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin: ChildStdin = child.stdin.take().unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    send(&mut stdin, run_request());
+
+    let log: Value = read(&mut lines);
+    assert_eq!(log["method"], "log");
+    assert!(log.get("id").is_none());
+    assert_eq!(log["params"]["message"], "documenting 1 endpoint(s)");
+
+    let write: Value = read(&mut lines);
+    assert_eq!(write["method"], "file.write");
+    assert_eq!(write["params"]["path"], "API.md");
+    assert!(write["params"]["contents"].as_str().unwrap().contains("| GET | `/tasks` |"));
+    send(&mut stdin, json!({"jsonrpc": "2.0", "id": write["id"], "result": {"bytes": 1}}));
+
+    let done: Value = read(&mut lines);
+    assert_eq!(done["id"], 0);
+    assert_eq!(
+        done["result"]["patch"]["project_description"],
+        "todo app (API documented in API.md)"
+    );
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn tests_example_agent_reports_denied_writes() {
+    let mut child: Child = Command::new(env!("CARGO_BIN_EXE_example_external_agent"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin: ChildStdin = child.stdin.take().unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    send(&mut stdin, run_request());
+    read(&mut lines);
+    let write: Value = read(&mut lines);
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": write["id"], "error": {"code": -32000, "message": "approval denied"}}),
+    );
+
+    let done: Value = read(&mut lines);
+    assert_eq!(done["id"], 0);
+    assert_eq!(done["error"]["message"], "approval denied");
+    assert!(done.get("result").is_none());
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,124 @@
+use actix_cors::Cors;
+use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use std::time::Duration;
+
+// 允許代理的外部 API 來源：名稱 -> URL
+// 只讀代理：僅轉發 GET 請求，不保存任何數據
+const UPSTREAMS: &[(&str, &str)] = &[
+    ("timezones", "http://worldtimeapi.org/api/timezone"),
+    ("btc_price", "https://api.binance.com/api/v3/ticker/price?symbol=BTCUSDT"),
+];
+
+// 描述一個外部來源
+#[derive(Serialize, Debug, Clone)]
+struct Source {
+    name: String,
+    url: String,
+}
+
+// 應用程式狀態，包含共用的 HTTP 客戶端
+struct AppState {
+    client: HttpClient,
+}
+
+fn find_upstream(name: &str) -> Option<&'static str> {
+    UPSTREAMS
+        .iter()
+        .find(|(upstream_name, _)| *upstream_name == name)
+        .map(|(_, url)| *url)
+}
+
+async fn health() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+// 列出所有可用的外部來源
+async fn list_sources() -> impl Responder {
+    let sources: Vec<Source> = UPSTREAMS
+        .iter()
+        .map(|(name, url)| Source {
+            name: name.to_string(),
+            url: url.to_string(),
+        })
+        .collect();
+    HttpResponse::Ok().json(sources)
+}
+
+// 將請求轉發到外部來源，並原樣返回其 JSON 內容
+async fn proxy(app_state: web::Data<AppState>, name: web::Path<String>) -> impl Responder {
+    let url: &str = match find_upstream(&name.into_inner()) {
+        Some(url) => url,
+        None => return HttpResponse::NotFound().body("Unknown source"),
+    };
+
+    match app_state.client.get(url).send().await {
+        Ok(response) if response.status().is_success() => {
+            match response.json::<serde_json::Value>().await {
+                Ok(body) => HttpResponse::Ok().json(body),
+                Err(_) => HttpResponse::BadGateway().body("Upstream did not return JSON"),
+            }
+        }
+        Ok(response) => HttpResponse::BadGateway().body(format!("Upstream returned {}", response.status())),
+        Err(e) => HttpResponse::BadGateway().body(e.to_string()),
+    }
+}
+
+// 註冊所有路由，main 和冒煙測試共用
+fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/health", web::get().to(health))
+        .route("/sources", web::get().to(list_sources))
+        .route("/sources/{name}", web::get().to(proxy));
+}
+
+fn build_state() -> web::Data<AppState> {
+    let client = HttpClient::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to build HTTP client");
+    web::Data::new(AppState { client })
+}
+
+// 主函數，啟動 HTTP 伺服器
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
+    let data = build_state();
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(Cors::permissive())
+            .app_data(data.clone())
+            .configure(configure)
+    })
+    .bind(("127.0.0.1", port))?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod smoke_tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn smoke_health_and_sources() {
+        let app = test::init_service(App::new().app_data(build_state()).configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/sources").to_request();
+        let sources: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(sources.as_array().unwrap().len(), UPSTREAMS.len());
+    }
+
+    #[actix_web::test]
+    async fn smoke_unknown_source_is_not_found() {
+        let app = test::init_service(App::new().app_data(build_state()).configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/sources/does_not_exist").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+}
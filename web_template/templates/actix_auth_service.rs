@@ -0,0 +1,165 @@
+use actix_cors::Cors;
+use actix_web::{http::header, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 註冊 / 登入請求的憑證
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+// 登入成功後返回的會話令牌
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Session {
+    token: String,
+}
+
+// 用戶存儲：用戶名 -> 密碼，以及令牌 -> 用戶名
+struct AuthStore {
+    users: HashMap<String, String>,
+    sessions: HashMap<String, String>,
+}
+
+struct AppState {
+    store: Mutex<AuthStore>,
+}
+
+// 產生一個不可預測的會話令牌
+fn new_token(username: &str) -> String {
+    let nanos: u128 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write(username.as_bytes());
+    hasher.write_u128(nanos);
+    format!("{:016x}{:08x}", hasher.finish(), nanos as u32)
+}
+
+// 從 Authorization: Bearer <token> 標頭取出令牌
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.to_string())
+}
+
+async fn register(app_state: web::Data<AppState>, creds: web::Json<Credentials>) -> impl Responder {
+    let mut store = app_state.store.lock().unwrap();
+    if store.users.contains_key(&creds.username) {
+        return HttpResponse::BadRequest().body("User already exists");
+    }
+    store.users.insert(creds.username.clone(), creds.password.clone());
+    HttpResponse::Ok().finish()
+}
+
+async fn login(app_state: web::Data<AppState>, creds: web::Json<Credentials>) -> impl Responder {
+    let mut store = app_state.store.lock().unwrap();
+    match store.users.get(&creds.username) {
+        Some(password) if *password == creds.password => {
+            let token: String = new_token(&creds.username);
+            store.sessions.insert(token.clone(), creds.username.clone());
+            HttpResponse::Ok().json(Session { token })
+        }
+        _ => HttpResponse::Unauthorized().body("Invalid username or password"),
+    }
+}
+
+async fn logout(app_state: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let mut store = app_state.store.lock().unwrap();
+    match bearer_token(&req).and_then(|token| store.sessions.remove(&token)) {
+        Some(_) => HttpResponse::Ok().body("logged out!"),
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+// 返回當前令牌所屬的用戶
+async fn me(app_state: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let store = app_state.store.lock().unwrap();
+    match bearer_token(&req).and_then(|token| store.sessions.get(&token).cloned()) {
+        Some(username) => HttpResponse::Ok().json(serde_json::json!({ "username": username })),
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+// 註冊所有路由，main 和冒煙測試共用
+fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/register", web::post().to(register))
+        .route("/login", web::post().to(login))
+        .route("/logout", web::post().to(logout))
+        .route("/me", web::get().to(me));
+}
+
+fn build_state() -> web::Data<AppState> {
+    web::Data::new(AppState {
+        store: Mutex::new(AuthStore {
+            users: HashMap::new(),
+            sessions: HashMap::new(),
+        }),
+    })
+}
+
+// 主函數，啟動 HTTP 伺服器
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
+    let data = build_state();
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(Cors::permissive())
+            .app_data(data.clone())
+            .configure(configure)
+    })
+    .bind(("127.0.0.1", port))?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod smoke_tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn smoke_login_me_logout() {
+        let app = test::init_service(App::new().app_data(build_state()).configure(configure)).await;
+        let creds = Credentials { username: "carol".to_string(), password: "pw".to_string() };
+
+        let req = test::TestRequest::post().uri("/register").set_json(&creds).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post().uri("/login").set_json(&creds).to_request();
+        let session: Session = test::call_and_read_body_json(&app, req).await;
+        let auth = (header::AUTHORIZATION, format!("Bearer {}", session.token));
+
+        let req = test::TestRequest::get().uri("/me").insert_header(auth.clone()).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post().uri("/logout").insert_header(auth.clone()).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/me").insert_header(auth).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+    }
+
+    #[actix_web::test]
+    async fn smoke_rejects_bad_password() {
+        let app = test::init_service(App::new().app_data(build_state()).configure(configure)).await;
+        let creds = Credentials { username: "dave".to_string(), password: "pw".to_string() };
+
+        let req = test::TestRequest::post().uri("/register").set_json(&creds).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let wrong = Credentials { password: "nope".to_string(), ..creds };
+        let req = test::TestRequest::post().uri("/login").set_json(&wrong).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+    }
+}
@@ -1,12 +1,10 @@
 use actix_cors::Cors;
-use actix_web::{http::header, web, App, HttpServer, Responder, HttpResponse};
+use actix_web::{http::header, web, App, HttpResponse, HttpServer, Responder};
 use serde::{Deserialize, Serialize};
-use reqwest::Client as HttpClient;
-use async_trait::async_trait;
-use std::sync::Mutex;
 use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
+use std::sync::Mutex;
 
 // 定義 Task 結構體，表示一個任務
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -71,35 +69,31 @@ impl Database {
     }
 
     // 保存和加載 Database 到文件
-    fn save_to_file(&self) -> std::io::Result<()> {
+    fn save_to_file(&self, path: &str) -> std::io::Result<()> {
         let data = serde_json::to_string(&self)?;
-        let mut file = fs::File::create("database.json")?;
+        let mut file = fs::File::create(path)?;
         file.write_all(data.as_bytes())?;
         Ok(())
     }
 
-    fn load_from_file() -> std::io::Result<Self> {
-        let file_content = fs::read_to_string("database.json")?;
+    fn load_from_file(path: &str) -> std::io::Result<Self> {
+        let file_content = fs::read_to_string(path)?;
         let db: Database = serde_json::from_str(&file_content)?;
         Ok(db)
     }
 }
 
-// 應用程式狀態，包含一個互斥的 Database
+// 應用程式狀態，包含一個互斥的 Database 以及它的文件路徑
 struct AppState {
     db: Mutex<Database>,
+    db_path: String,
 }
 
 // 創建任務的處理器
-// 這裡定義了一個名為 create_task 的異步函數 (async fn)。該函數接受兩個參數：
-// app_state: 一個共享的應用程式狀態物件，類型為 web::Data<AppState>，包含了互斥保護的數據庫。
-// task: 一個包含在請求體中的JSON任務數據，類型為 web::Json<Task>。
-// 函數的返回類型是 impl Responder，表示該函數返回的一個可響應HTTP請求的類型。
-
 async fn create_task(app_state: web::Data<AppState>, task: web::Json<Task>) -> impl Responder {
     let mut db = app_state.db.lock().unwrap();
     db.insert(task.into_inner());
-    let _ = db.save_to_file();
+    let _ = db.save_to_file(&app_state.db_path);
     HttpResponse::Ok().finish()
 }
 
@@ -111,33 +105,30 @@ async fn read_task(app_state: web::Data<AppState>, id: web::Path<u64>) -> impl R
     }
 }
 
-
 async fn read_all_tasks(app_state: web::Data<AppState>) -> impl Responder {
     let db = app_state.db.lock().unwrap();
-    let tasks:Vec<&Task>=db.get_all();
+    let tasks: Vec<&Task> = db.get_all();
     HttpResponse::Ok().json(tasks)
+}
 
-    }
-
-async fn update_task(app_state: web::Data<AppState>, task:web::Json<Task>) -> impl Responder {
+async fn update_task(app_state: web::Data<AppState>, task: web::Json<Task>) -> impl Responder {
     let mut db = app_state.db.lock().unwrap();
     db.update(task.into_inner());
-    let _ = db.save_to_file();
+    let _ = db.save_to_file(&app_state.db_path);
     HttpResponse::Ok().finish()
-
-    }
+}
 
 async fn delete_task(app_state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
     let mut db = app_state.db.lock().unwrap();
     db.delete(&id.into_inner());
-    let _ = db.save_to_file();
+    let _ = db.save_to_file(&app_state.db_path);
     HttpResponse::Ok().finish()
 }
 
-async fn register(app_state:web::Data<AppState>,user:web::Json<User>) -> impl Responder{
-    let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+async fn register(app_state: web::Data<AppState>, user: web::Json<User>) -> impl Responder {
+    let mut db = app_state.db.lock().unwrap();
     db.insert_user(user.into_inner());
-    let _ = db.save_to_file();
+    let _ = db.save_to_file(&app_state.db_path);
     HttpResponse::Ok().finish()
 }
 
@@ -154,17 +145,31 @@ async fn login(app_state: web::Data<AppState>, user: web::Json<User>) -> impl Re
     }
 }
 
+// 註冊所有路由，main 和冒煙測試共用
+fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/task", web::post().to(create_task))
+        .route("/task", web::get().to(read_all_tasks))
+        .route("/task", web::put().to(update_task))
+        .route("/task/{id}", web::get().to(read_task))
+        .route("/task/{id}", web::delete().to(delete_task))
+        .route("/register", web::post().to(register))
+        .route("/login", web::post().to(login));
+}
 
 // 主函數，啟動 HTTP 伺服器
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let db_path: String = std::env::var("DATABASE_PATH").unwrap_or("database.json".to_string());
+    let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
+
     // 嘗試從文件加載 Database，若失敗則新建一個空的 Database
-    let db = match Database::load_from_file() {
+    let db = match Database::load_from_file(&db_path) {
         Ok(db) => db,
         Err(_) => Database::new(),
     };
     let data = web::Data::new(AppState {
         db: Mutex::new(db),
+        db_path,
     });
 
     HttpServer::new(move || {
@@ -181,15 +186,54 @@ async fn main() -> std::io::Result<()> {
                     .max_age(3600),
             )
             .app_data(data.clone())
-            .route("/task", web::post().to(create_task))
-            .route("/task", web::get().to(read_all_tasks))
-            .route("/task", web::put().to(update_task))
-            .route("/task/{id}", web::get().to(read_task))
-            .route("/task/{id}", web::delete().to(delete_task))
-            .route("/register", web::post().to(register))
-            .route("/login", web::post().to(login))
+            .configure(configure)
     })
-    .bind("127.0.0.1:8080")?
+    .bind(("127.0.0.1", port))?
     .run()
     .await
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod smoke_tests {
+    use super::*;
+    use actix_web::test;
+
+    fn test_state() -> web::Data<AppState> {
+        let db_path = std::env::temp_dir().join(format!("json_crud_smoke_{}.json", std::process::id()));
+        web::Data::new(AppState {
+            db: Mutex::new(Database::new()),
+            db_path: db_path.to_string_lossy().to_string(),
+        })
+    }
+
+    #[actix_web::test]
+    async fn smoke_task_crud_roundtrip() {
+        let app = test::init_service(App::new().app_data(test_state()).configure(configure)).await;
+
+        let task = Task { id: 1, name: "write tests".to_string(), completed: false };
+        let req = test::TestRequest::post().uri("/task").set_json(&task).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/task/1").to_request();
+        let fetched: Task = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(fetched.name, "write tests");
+
+        let req = test::TestRequest::delete().uri("/task/1").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/task/1").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn smoke_register_and_login() {
+        let app = test::init_service(App::new().app_data(test_state()).configure(configure)).await;
+
+        let user = User { id: 1, username: "alice".to_string(), password: "secret".to_string() };
+        let req = test::TestRequest::post().uri("/register").set_json(&user).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post().uri("/login").set_json(&user).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+}
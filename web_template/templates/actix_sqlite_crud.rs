@@ -0,0 +1,228 @@
+use actix_cors::Cors;
+use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// 定義 Task 結構體，表示一個任務
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Task {
+    id: u64,
+    name: String,
+    completed: bool,
+}
+
+// 定義 User 結構體，表示一個用戶
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct User {
+    id: u64,
+    username: String,
+    password: String,
+}
+
+// 應用程式狀態，包含一個互斥的 SQLite 連接
+struct AppState {
+    conn: Mutex<Connection>,
+}
+
+// 建立資料表（若不存在）
+fn init_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tasks (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            completed INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password TEXT NOT NULL
+        );",
+    )
+}
+
+fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<Task> {
+    Ok(Task {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        completed: row.get(2)?,
+    })
+}
+
+// 創建或覆蓋任務
+async fn create_task(app_state: web::Data<AppState>, task: web::Json<Task>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    match conn.execute(
+        "INSERT OR REPLACE INTO tasks (id, name, completed) VALUES (?1, ?2, ?3)",
+        params![task.id, task.name, task.completed],
+    ) {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+async fn read_task(app_state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    let task = conn
+        .query_row(
+            "SELECT id, name, completed FROM tasks WHERE id = ?1",
+            params![id.into_inner()],
+            row_to_task,
+        )
+        .optional();
+    match task {
+        Ok(Some(task)) => HttpResponse::Ok().json(task),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+async fn read_all_tasks(app_state: web::Data<AppState>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    let mut stmt = match conn.prepare("SELECT id, name, completed FROM tasks ORDER BY id") {
+        Ok(stmt) => stmt,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let tasks: rusqlite::Result<Vec<Task>> = stmt
+        .query_map([], row_to_task)
+        .and_then(|rows| rows.collect());
+    match tasks {
+        Ok(tasks) => HttpResponse::Ok().json(tasks),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+async fn update_task(app_state: web::Data<AppState>, task: web::Json<Task>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    match conn.execute(
+        "UPDATE tasks SET name = ?2, completed = ?3 WHERE id = ?1",
+        params![task.id, task.name, task.completed],
+    ) {
+        Ok(0) => HttpResponse::NotFound().finish(),
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+async fn delete_task(app_state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    match conn.execute("DELETE FROM tasks WHERE id = ?1", params![id.into_inner()]) {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+async fn register(app_state: web::Data<AppState>, user: web::Json<User>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    match conn.execute(
+        "INSERT INTO users (id, username, password) VALUES (?1, ?2, ?3)",
+        params![user.id, user.username, user.password],
+    ) {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(_) => HttpResponse::BadRequest().body("User already exists"),
+    }
+}
+
+async fn login(app_state: web::Data<AppState>, user: web::Json<User>) -> impl Responder {
+    let conn = app_state.conn.lock().unwrap();
+    let stored_password: Option<String> = conn
+        .query_row(
+            "SELECT password FROM users WHERE username = ?1",
+            params![user.username],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap_or(None);
+    match stored_password {
+        Some(password) if password == user.password => HttpResponse::Ok().body("logged in!"),
+        _ => HttpResponse::BadRequest().body("Invalid username or password"),
+    }
+}
+
+// 註冊所有路由，main 和冒煙測試共用
+fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/task", web::post().to(create_task))
+        .route("/task", web::get().to(read_all_tasks))
+        .route("/task", web::put().to(update_task))
+        .route("/task/{id}", web::get().to(read_task))
+        .route("/task/{id}", web::delete().to(delete_task))
+        .route("/register", web::post().to(register))
+        .route("/login", web::post().to(login));
+}
+
+// 主函數，啟動 HTTP 伺服器
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let db_path: String = std::env::var("DATABASE_PATH").unwrap_or("database.sqlite".to_string());
+    let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
+
+    let conn = Connection::open(&db_path).expect("Failed to open SQLite database");
+    init_schema(&conn).expect("Failed to create SQLite schema");
+    let data = web::Data::new(AppState {
+        conn: Mutex::new(conn),
+    });
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(Cors::permissive())
+            .app_data(data.clone())
+            .configure(configure)
+    })
+    .bind(("127.0.0.1", port))?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod smoke_tests {
+    use super::*;
+    use actix_web::test;
+
+    fn test_state() -> web::Data<AppState> {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        web::Data::new(AppState {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    #[actix_web::test]
+    async fn smoke_task_crud_roundtrip() {
+        let app = test::init_service(App::new().app_data(test_state()).configure(configure)).await;
+
+        let task = Task { id: 7, name: "ship it".to_string(), completed: false };
+        let req = test::TestRequest::post().uri("/task").set_json(&task).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let updated = Task { completed: true, ..task };
+        let req = test::TestRequest::put().uri("/task").set_json(&updated).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/task").to_request();
+        let tasks: Vec<Task> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(tasks.len(), 1);
+        assert!(tasks[0].completed);
+
+        let req = test::TestRequest::delete().uri("/task/7").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/task/7").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn smoke_register_and_login() {
+        let app = test::init_service(App::new().app_data(test_state()).configure(configure)).await;
+
+        let user = User { id: 1, username: "bob".to_string(), password: "hunter2".to_string() };
+        let req = test::TestRequest::post().uri("/register").set_json(&user).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post().uri("/login").set_json(&user).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let wrong = User { password: "nope".to_string(), ..user };
+        let req = test::TestRequest::post().uri("/login").set_json(&wrong).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+}